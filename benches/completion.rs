@@ -0,0 +1,39 @@
+//! Benchmark of completing command names and argument values with growing command sets
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use reedline_repl_rs::bench;
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+fn noop(_args: ArgMatches, _context: &mut ()) -> Result<Option<String>> {
+    Ok(None)
+}
+
+fn repl_with_commands(count: usize) -> Repl<(), reedline_repl_rs::Error> {
+    (0..count).fold(Repl::new(()), |repl, index| {
+        repl.with_command(
+            Command::new(format!("command{}", index))
+                .about("Benchmark command")
+                .arg(Arg::new("mode").value_parser(["fast", "slow", "safe"])),
+            noop,
+        )
+    })
+}
+
+fn completion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("completion");
+    for count in [10, 1_000, 100_000] {
+        let repl = repl_with_commands(count);
+        group.bench_with_input(BenchmarkId::new("command name", count), &repl, |b, repl| {
+            b.iter(|| bench::complete(repl, black_box("command1")))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("argument value", count),
+            &repl,
+            |b, repl| b.iter(|| bench::complete(repl, black_box("command1 s"))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, completion);
+criterion_main!(benches);
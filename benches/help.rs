@@ -0,0 +1,38 @@
+//! Benchmark of rendering the help overview and the help of a single command
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use reedline_repl_rs::bench;
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+fn noop(_args: ArgMatches, _context: &mut ()) -> Result<Option<String>> {
+    Ok(None)
+}
+
+fn repl_with_commands(count: usize) -> Repl<(), reedline_repl_rs::Error> {
+    (0..count).fold(Repl::new(()), |repl, index| {
+        repl.with_command(
+            Command::new(format!("command{}", index))
+                .about("Benchmark command with a description long enough to be wrapped")
+                .arg(Arg::new("name").required(true).help("Name of the thing"))
+                .arg(Arg::new("verbose").long("verbose").short('v')),
+            noop,
+        )
+    })
+}
+
+fn help(c: &mut Criterion) {
+    let mut group = c.benchmark_group("help");
+    for count in [10, 1_000] {
+        let repl = repl_with_commands(count);
+        group.bench_with_input(BenchmarkId::new("overview", count), &repl, |b, repl| {
+            b.iter(|| bench::render_help(repl, None, black_box(80)))
+        });
+        group.bench_with_input(BenchmarkId::new("command", count), &repl, |b, repl| {
+            b.iter(|| bench::render_help(repl, Some("command1"), black_box(80)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, help);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+//! Benchmark of executing entered lines, from splitting the line to the command callback
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+fn count(args: ArgMatches, context: &mut usize) -> Result<Option<String>> {
+    *context += args
+        .get_many::<String>("words")
+        .map(|words| words.count())
+        .unwrap_or_default();
+    Ok(None)
+}
+
+fn process_line(c: &mut Criterion) {
+    let mut repl = Repl::new(0).with_command(
+        Command::new("count").arg(Arg::new("words").num_args(0..)),
+        count,
+    );
+    c.bench_function("process_line plain", |b| {
+        b.iter(|| repl.eval_line(black_box("count one two three four five six")))
+    });
+    c.bench_function("process_line quoted", |b| {
+        b.iter(|| repl.eval_line(black_box(r#"count "one two" three "four five" six"#)))
+    });
+}
+
+criterion_group!(benches, process_line);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Example registering the commands of a clap derive enum
+use reedline_repl_rs::clap::Subcommand;
+use reedline_repl_rs::{Repl, Result};
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Add two numbers together
+    Add { first: i32, second: i32 },
+    /// Greetings!
+    Hello {
+        who: String,
+        /// Greet loudly
+        #[arg(long, short)]
+        loud: bool,
+    },
+}
+
+/// Execute a parsed command
+fn dispatch(command: Commands, _context: &mut ()) -> Result<Option<String>> {
+    match command {
+        Commands::Add { first, second } => Ok(Some((first + second).to_string())),
+        Commands::Hello { who, loud: false } => Ok(Some(format!("Hello, {}", who))),
+        Commands::Hello { who, loud: true } => Ok(Some(format!("HELLO, {}!", who.to_uppercase()))),
+    }
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_derived_commands::<Commands>(dispatch);
+    repl.run()
+}
@@ -0,0 +1,32 @@
+//! Example using the same commands as a one-shot CLI and as an interactive REPL
+//!
+//! `cargo run --example hybrid_cli -- hello World` prints the greeting and exits,
+//! `cargo run --example hybrid_cli` starts the REPL. Failing commands result in exit code 1.
+//! With the `completions` feature, `cargo run --example hybrid_cli --features completions --
+//! completions bash` prints a bash completion script for the commands.
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+/// Write "Hello" with given name
+fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    Ok(Some(format!(
+        "Hello, {}",
+        args.get_one::<String>("who").unwrap()
+    )))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_banner("Welcome to MyApp")
+        .with_command(
+            Command::new("hello")
+                .arg(Arg::new("who").required(true))
+                .about("Greetings!"),
+            hello,
+        );
+    repl.run_with_args(std::env::args())?;
+    std::process::exit(repl.exit_code())
+}
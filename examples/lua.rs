@@ -0,0 +1,51 @@
+//! Example evaluating Lua code with the `eval` built-in command
+//!
+//! Try `eval for i = 1, 3 do run("hello " .. i) end` or `eval run("add 2 3") * 10`
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{ArgMatchesExt, Repl, Result};
+
+#[derive(Default)]
+struct Context {
+    greeted: usize,
+}
+
+/// Write "Hello" with given name
+fn hello(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
+    context.greeted += 1;
+    Ok(Some(format!(
+        "Hello, {}",
+        args.get_one::<String>("who").unwrap()
+    )))
+}
+
+/// Add two numbers
+fn add(args: ArgMatches, _context: &mut Context) -> Result<Option<String>> {
+    let first: i32 = args.req("first")?;
+    let second: i32 = args.req("second")?;
+
+    Ok(Some((first + second).to_string()))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(Context::default())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_eval_command(true)
+        // expose the number of greetings as a Lua global
+        .with_on_eval(|lua, context| lua.globals().set("greeted", context.greeted))
+        .with_command(
+            Command::new("hello")
+                .arg(Arg::new("who").required(true))
+                .about("Greetings!"),
+            hello,
+        )
+        .with_command(
+            Command::new("add")
+                .arg(Arg::new("first").required(true))
+                .arg(Arg::new("second").required(true))
+                .about("Add two numbers together"),
+            add,
+        );
+    repl.run()
+}
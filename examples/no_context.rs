@@ -1,18 +1,21 @@
 //! Example using Repl without Context (or, more precisely, a Context of ())
 use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
-use reedline_repl_rs::{Repl, Result};
+use reedline_repl_rs::{ArgMatchesExt, Repl, Result};
 
 /// Add two numbers. Have to make this generic to be able to pass a Context of type ()
 fn add<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
-    let first: i32 = args.value_of("first").unwrap().parse()?;
-    let second: i32 = args.value_of("second").unwrap().parse()?;
+    let first: i32 = args.req("first")?;
+    let second: i32 = args.req("second")?;
 
     Ok(Some((first + second).to_string()))
 }
 
 /// Write "Hello"
 fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
-    Ok(Some(format!("Hello, {}", args.value_of("who").unwrap())))
+    Ok(Some(format!(
+        "Hello, {}",
+        args.get_one::<String>("who").unwrap()
+    )))
 }
 
 fn main() -> Result<()> {
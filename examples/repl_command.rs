@@ -0,0 +1,30 @@
+//! Example generating commands from typed functions with the repl_command attribute
+use reedline_repl_rs::{repl_command, Repl, Result};
+
+/// Greetings!
+#[repl_command(name = "hello")]
+fn hello(who: String, times: Option<u32>, loud: bool) -> Result<Option<String>> {
+    let greeting = format!("Hello, {}", who);
+    let greeting = if loud {
+        greeting.to_uppercase()
+    } else {
+        greeting
+    };
+    Ok(Some(vec![greeting; times.unwrap_or(1) as usize].join("\n")))
+}
+
+#[repl_command(about = "Add numbers and remember the sum")]
+fn add(numbers: Vec<i64>, total: &mut i64) -> Result<Option<String>> {
+    *total += numbers.iter().sum::<i64>();
+    Ok(Some(total.to_string()))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(0)
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(hello::command(), hello::callback)
+        .with_command(add::command(), add::callback);
+    repl.run()
+}
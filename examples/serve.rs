@@ -0,0 +1,37 @@
+//! Example serving a Repl with a shared counter on a TCP port
+//!
+//! Connect with `nc localhost 4000` or `telnet localhost 4000`, several clients at once
+use reedline_repl_rs::clap::{ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result, Server};
+use std::sync::{Arc, RwLock};
+
+type Counter = Arc<RwLock<u64>>;
+
+/// Increment the counter shared by all connections
+fn increment(_args: ArgMatches, context: &mut Counter) -> Result<Option<String>> {
+    let mut counter = context.write().expect("counter lock poisoned");
+    *counter += 1;
+    Ok(Some(counter.to_string()))
+}
+
+/// Show the counter, sessions read it concurrently
+fn show(_args: ArgMatches, context: &mut Counter) -> Result<Option<String>> {
+    let counter = context.read().expect("counter lock poisoned");
+    Ok(Some(counter.to_string()))
+}
+
+fn main() -> Result<()> {
+    let counter = Counter::default();
+    Server::new(move |session| {
+        Repl::new(Arc::clone(&counter))
+            .with_name(&format!("MyApp[{}]", session.id))
+            .with_banner("Welcome to MyApp")
+            .with_command(
+                Command::new("increment").about("Increment the shared counter"),
+                increment,
+            )
+            .with_command(Command::new("show").about("Show the shared counter"), show)
+    })
+    .with_on_connection_error(|session, err| eprintln!("session {}: {}", session.id, err))
+    .serve("127.0.0.1:4000")
+}
@@ -0,0 +1,31 @@
+//! Example driving the REPL from the application's own loop with Repl::step
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::{Repl, Result};
+
+/// Write "Hello" with given name
+fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    Ok(Some(format!(
+        "Hello, {}",
+        args.get_one::<String>("who").unwrap()
+    )))
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_command(
+            Command::new("hello")
+                .arg(Arg::new("who").required(true))
+                .about("Greetings!"),
+            hello,
+        );
+    let mut steps = 0;
+    while repl.step()? {
+        // the host application does its own work between the steps
+        steps += 1;
+    }
+    println!("Left after {} steps", steps);
+    Ok(())
+}
@@ -10,7 +10,7 @@ struct Context {
 
 /// Append name to list
 fn append(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
-    let name: String = args.value_of("name").unwrap().to_string();
+    let name: String = args.get_one::<String>("name").unwrap().to_string();
     context.list.push_back(name);
     let list: Vec<String> = context.list.clone().into();
 
@@ -19,7 +19,7 @@ fn append(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
 
 /// Prepend name to list
 fn prepend(args: ArgMatches, context: &mut Context) -> Result<Option<String>> {
-    let name: String = args.value_of("name").unwrap().to_string();
+    let name: String = args.get_one::<String>("name").unwrap().to_string();
     context.list.push_front(name);
     let list: Vec<String> = context.list.clone().into();
 
@@ -43,6 +43,7 @@ fn main() -> Result<()> {
                 .about("Prepend name to front of list"),
             prepend,
         )
-        .with_on_after_command(|context| Ok(Some(format!("MyList [{}]", context.list.len()))));
+        .with_on_after_command(|context| Ok(Some(format!("MyList [{}]", context.list.len()))))
+        .with_on_exit(|context| Ok(Some(format!("Bye, {} names left", context.list.len()))));
     repl.run()
 }
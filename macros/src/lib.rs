@@ -0,0 +1,240 @@
+//! Procedural macros for reedline-repl-rs, use them through the `macro` feature of the
+//! `reedline-repl-rs` crate
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Expr, ExprLit, FnArg, GenericArgument, ItemFn, Lit, LitStr, Meta, Pat,
+    PathArguments, Type,
+};
+
+/// Generate the clap `Command` and the `ArgMatches` unpacking glue for a function with
+/// typed parameters
+///
+/// Next to the function a module of the same name is generated, containing `command()`
+/// and `callback`, which are passed to `Repl::with_command`:
+///
+/// ```ignore
+/// #[repl_command(name = "hello", about = "Greetings")]
+/// fn hello(who: String, times: Option<u32>) -> Result<Option<String>> {
+///     Ok(Some(format!("Hello, {}", who).repeat(times.unwrap_or(1) as usize)))
+/// }
+///
+/// let mut repl = Repl::new(()).with_command(hello::command(), hello::callback);
+/// ```
+///
+/// Parameters become arguments depending on their type:
+/// - `T` is a required positional argument
+/// - `Option<T>` is an optional `--name <value>` argument
+/// - `bool` is a `--name` flag
+/// - `Vec<T>` takes the remaining positional values
+/// - `&mut Context` receives the Repl's context
+///
+/// Values are parsed with `clap::value_parser!`, so every type clap can parse is supported.
+/// `name` defaults to the function name and `about` to its doc comment
+#[proc_macro_attribute]
+pub fn repl_command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let mut about: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("about") {
+            about = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `name` or `about`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    match expand(function, name, about) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum Kind {
+    Required(Type),
+    Optional(Type),
+    Flag,
+    Many(Type),
+    Context(Type),
+}
+
+fn expand(
+    function: ItemFn,
+    name: Option<LitStr>,
+    about: Option<LitStr>,
+) -> syn::Result<TokenStream2> {
+    let ident = &function.sig.ident;
+    let vis = &function.vis;
+    let output = &function.sig.output;
+    let name = name.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+    let about = about.or_else(|| doc_comment(&function));
+
+    let mut args = vec![];
+    let mut values = vec![];
+    let mut context = None;
+    for input in &function.sig.inputs {
+        let input = match input {
+            FnArg::Typed(input) => input,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "repl_command can't be used on methods",
+                ))
+            }
+        };
+        let param = match &*input.pat {
+            Pat::Ident(pat) => pat.ident.to_string(),
+            pat => {
+                return Err(syn::Error::new_spanned(
+                    pat,
+                    "expected a plain parameter name",
+                ))
+            }
+        };
+        let id = param.trim_start_matches('_').replace('_', "-");
+        match classify(&input.ty) {
+            Kind::Required(ty) => {
+                args.push(quote! {
+                    .arg(::reedline_repl_rs::clap::Arg::new(#id)
+                        .required(true)
+                        .value_parser(::reedline_repl_rs::clap::value_parser!(#ty)))
+                });
+                values.push(quote! {
+                    args.get_one::<#ty>(#id).cloned().expect("required arg")
+                });
+            }
+            Kind::Optional(ty) => {
+                args.push(quote! {
+                    .arg(::reedline_repl_rs::clap::Arg::new(#id)
+                        .long(#id)
+                        .value_parser(::reedline_repl_rs::clap::value_parser!(#ty)))
+                });
+                values.push(quote! { args.get_one::<#ty>(#id).cloned() });
+            }
+            Kind::Flag => {
+                args.push(quote! {
+                    .arg(::reedline_repl_rs::clap::Arg::new(#id)
+                        .long(#id)
+                        .action(::reedline_repl_rs::clap::ArgAction::SetTrue))
+                });
+                values.push(quote! { args.get_flag(#id) });
+            }
+            Kind::Many(ty) => {
+                args.push(quote! {
+                    .arg(::reedline_repl_rs::clap::Arg::new(#id)
+                        .num_args(0..)
+                        .value_parser(::reedline_repl_rs::clap::value_parser!(#ty)))
+                });
+                values.push(quote! {
+                    args.get_many::<#ty>(#id)
+                        .map(|values| values.cloned().collect())
+                        .unwrap_or_default()
+                });
+            }
+            Kind::Context(ty) => {
+                if context.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        &input.ty,
+                        "only one `&mut Context` parameter is allowed",
+                    ));
+                }
+                context = Some(ty);
+                values.push(quote! { context });
+            }
+        }
+    }
+
+    let about = about.map(|about| quote! { .about(#about) });
+    let (generics, context) = match context {
+        Some(ty) => (quote! {}, quote! { #ty }),
+        None => (quote! { <Context> }, quote! { Context }),
+    };
+    let module = format_ident!("{}", ident);
+    Ok(quote! {
+        #function
+
+        #[allow(non_snake_case)]
+        #vis mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// The clap definition of the command
+            pub fn command() -> ::reedline_repl_rs::clap::Command {
+                ::reedline_repl_rs::clap::Command::new(#name)
+                    #about
+                    #(#args)*
+            }
+
+            /// Unpacks the arguments and calls the command function
+            #[allow(unused_variables)]
+            pub fn callback #generics(
+                args: ::reedline_repl_rs::clap::ArgMatches,
+                context: &mut #context,
+            ) #output {
+                super::#ident(#(#values),*)
+            }
+        }
+    })
+}
+
+fn classify(ty: &Type) -> Kind {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_some() => {
+            Kind::Context((*reference.elem).clone())
+        }
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().expect("path has a segment");
+            if segment.ident == "bool" {
+                return Kind::Flag;
+            }
+            match (
+                segment.ident.to_string().as_str(),
+                inner_type(&segment.arguments),
+            ) {
+                ("Option", Some(inner)) => Kind::Optional(inner),
+                ("Vec", Some(inner)) => Kind::Many(inner),
+                _ => Kind::Required(ty.clone()),
+            }
+        }
+        _ => Kind::Required(ty.clone()),
+    }
+}
+
+fn inner_type(arguments: &PathArguments) -> Option<Type> {
+    match arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first() {
+                Some(GenericArgument::Type(ty)) => Some(ty.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn doc_comment(function: &ItemFn) -> Option<LitStr> {
+    let lines: Vec<String> = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(LitStr::new(&lines.join(" "), function.sig.ident.span()))
+    }
+}
@@ -0,0 +1,154 @@
+//! Abbreviations expanded in the line while typing, see
+//! [Repl::with_abbreviation](crate::Repl::with_abbreviation)
+//!
+//! reedline offers no hook to edit the line on a key press, but menus replace text in it
+//! when they are accepted. [AbbreviationEditMode] turns space into activating
+//! [AbbreviationMenu] and accepting it right away, which expands an abbreviation before
+//! the cursor, and then inserting the space. Keybindings can't do this, as reedline
+//! inserts characters typed without modifiers before looking them up.
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use reedline::{
+    Completer, EditCommand, EditMode, Emacs, LineBuffer, Menu, MenuEvent, Painter, PromptEditMode,
+    ReedlineEvent, Suggestion,
+};
+use std::collections::BTreeMap;
+
+/// Name of the menu expanding abbreviations
+pub(crate) const ABBREVIATION_MENU: &str = "abbreviation_menu";
+
+/// Emacs editing with space expanding abbreviations
+pub(crate) struct AbbreviationEditMode {
+    emacs: Emacs,
+}
+
+impl AbbreviationEditMode {
+    pub(crate) fn new(emacs: Emacs) -> Self {
+        Self { emacs }
+    }
+}
+
+impl EditMode for AbbreviationEditMode {
+    fn parse_event(&mut self, event: Event) -> ReedlineEvent {
+        match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(' '),
+                modifiers: KeyModifiers::NONE,
+            }) => expand_event(),
+            event => self.emacs.parse_event(event),
+        }
+    }
+
+    fn edit_mode(&self) -> PromptEditMode {
+        self.emacs.edit_mode()
+    }
+}
+
+/// Event of typing space
+fn expand_event() -> ReedlineEvent {
+    let insert_space = ReedlineEvent::Edit(vec![EditCommand::InsertChar(' ')]);
+    // history search handles neither menus nor multiple events, it only inserts the space
+    ReedlineEvent::UntilFound(vec![
+        ReedlineEvent::Multiple(vec![
+            // a completion menu which is open is closed, so the expansion can be accepted
+            ReedlineEvent::Esc,
+            ReedlineEvent::Menu(ABBREVIATION_MENU.to_string()),
+            ReedlineEvent::Enter,
+            insert_space.clone(),
+        ]),
+        insert_space,
+    ])
+}
+
+/// Menu without entries which replaces an abbreviation typed as first word of the line by
+/// its expansion when accepted
+pub(crate) struct AbbreviationMenu {
+    abbreviations: BTreeMap<String, String>,
+    active: bool,
+}
+
+impl AbbreviationMenu {
+    pub(crate) fn new(abbreviations: BTreeMap<String, String>) -> Self {
+        Self {
+            abbreviations,
+            active: false,
+        }
+    }
+}
+
+impl Menu for AbbreviationMenu {
+    fn name(&self) -> &str {
+        ABBREVIATION_MENU
+    }
+
+    fn indicator(&self) -> &str {
+        ""
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Deactivate => self.active = false,
+            _ => {}
+        }
+    }
+
+    fn can_quick_complete(&self) -> bool {
+        false
+    }
+
+    fn can_partially_complete(
+        &mut self,
+        _values_updated: bool,
+        _line_buffer: &mut LineBuffer,
+        _completer: &mut dyn Completer,
+    ) -> bool {
+        false
+    }
+
+    fn update_values(&mut self, _line_buffer: &mut LineBuffer, _completer: &mut dyn Completer) {}
+
+    fn update_working_details(
+        &mut self,
+        _line_buffer: &mut LineBuffer,
+        _completer: &mut dyn Completer,
+        _painter: &Painter,
+    ) {
+    }
+
+    fn replace_in_buffer(&self, line_buffer: &mut LineBuffer) {
+        let cursor = line_buffer.insertion_point();
+        let before = &line_buffer.get_buffer()[..cursor];
+        let start = before.len() - before.trim_start().len();
+        let word = &before[start..];
+        // only the command is expanded, like fish does
+        if word.contains(char::is_whitespace) {
+            return;
+        }
+        if let Some(expansion) = self.abbreviations.get(word) {
+            let expansion = expansion.clone();
+            line_buffer.replace(start..cursor, &expansion);
+            line_buffer.set_insertion_point(start + expansion.len());
+        }
+    }
+
+    fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
+        0
+    }
+
+    fn menu_string(&self, _available_lines: u16, _use_ansi_coloring: bool) -> String {
+        String::new()
+    }
+
+    fn min_rows(&self) -> u16 {
+        0
+    }
+
+    fn get_values(&self) -> &[Suggestion] {
+        &[]
+    }
+}
@@ -0,0 +1,173 @@
+//! Completion menu for screen readers, see
+//! [Repl::with_accessible_mode](crate::Repl::with_accessible_mode)
+
+use reedline::menu_functions::find_common_string;
+use reedline::{Completer, LineBuffer, Menu, MenuEvent, Painter, Suggestion};
+
+/// Lists the completions as plain lines below the prompt, without colors, columns or
+/// reversed video, marking the selected one with `>`
+pub(crate) struct PlainMenu {
+    active: bool,
+    event: Option<MenuEvent>,
+    values: Vec<Suggestion>,
+    selected: usize,
+}
+
+impl PlainMenu {
+    pub(crate) fn new() -> Self {
+        Self {
+            active: false,
+            event: None,
+            values: vec![],
+            selected: 0,
+        }
+    }
+
+    fn select_next(&mut self) {
+        if !self.values.is_empty() {
+            self.selected = (self.selected + 1) % self.values.len();
+        }
+    }
+
+    fn select_previous(&mut self) {
+        if !self.values.is_empty() {
+            self.selected = self
+                .selected
+                .checked_sub(1)
+                .unwrap_or(self.values.len() - 1);
+        }
+    }
+}
+
+impl Menu for PlainMenu {
+    fn name(&self) -> &str {
+        "completion_menu"
+    }
+
+    fn indicator(&self) -> &str {
+        ""
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Deactivate => self.active = false,
+            _ => {}
+        }
+        self.event = Some(event);
+    }
+
+    fn can_quick_complete(&self) -> bool {
+        true
+    }
+
+    fn can_partially_complete(
+        &mut self,
+        values_updated: bool,
+        line_buffer: &mut LineBuffer,
+        completer: &mut dyn Completer,
+    ) -> bool {
+        if !values_updated {
+            self.update_values(line_buffer, completer);
+        }
+        let (span, matching) = match find_common_string(&self.values) {
+            (Some(Suggestion { value, span, .. }), Some(index)) => {
+                (*span, value[..index.min(value.len())].to_string())
+            }
+            _ => return false,
+        };
+        // only complete what extends the input, never overwrite it
+        let input = &line_buffer.get_buffer()[span.start..span.end];
+        if matching.len() <= input.len() || !matching.starts_with(input) {
+            return false;
+        }
+        let offset = line_buffer.insertion_point() + matching.len() - input.len();
+        line_buffer.replace(span.start..span.end, &matching);
+        line_buffer.set_insertion_point(offset);
+        self.update_values(line_buffer, completer);
+        true
+    }
+
+    fn update_values(&mut self, line_buffer: &mut LineBuffer, completer: &mut dyn Completer) {
+        let buffer = line_buffer.get_buffer().replace('\n', " ");
+        self.values = completer.complete(&buffer, line_buffer.insertion_point());
+        self.selected = 0;
+    }
+
+    fn update_working_details(
+        &mut self,
+        line_buffer: &mut LineBuffer,
+        completer: &mut dyn Completer,
+        _painter: &Painter,
+    ) {
+        match self.event.take() {
+            Some(MenuEvent::Activate(updated)) | Some(MenuEvent::Edit(updated)) if !updated => {
+                self.update_values(line_buffer, completer)
+            }
+            Some(MenuEvent::NextElement)
+            | Some(MenuEvent::MoveDown)
+            | Some(MenuEvent::MoveRight)
+            | Some(MenuEvent::NextPage) => self.select_next(),
+            Some(MenuEvent::PreviousElement)
+            | Some(MenuEvent::MoveUp)
+            | Some(MenuEvent::MoveLeft)
+            | Some(MenuEvent::PreviousPage) => self.select_previous(),
+            _ => {}
+        }
+    }
+
+    fn replace_in_buffer(&self, line_buffer: &mut LineBuffer) {
+        if let Some(suggestion) = self.values.get(self.selected) {
+            let start = suggestion.span.start.min(line_buffer.len());
+            let end = suggestion.span.end.min(line_buffer.len());
+            let mut value = suggestion.value.clone();
+            if suggestion.append_whitespace {
+                value.push(' ');
+            }
+            line_buffer.replace(start..end, &value);
+            let offset = line_buffer.insertion_point() + value.len().saturating_sub(end - start);
+            line_buffer.set_insertion_point(offset);
+        }
+    }
+
+    fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
+        self.values.len().max(1) as u16
+    }
+
+    fn menu_string(&self, available_lines: u16, _use_ansi_coloring: bool) -> String {
+        if self.values.is_empty() {
+            return "No completions".to_string();
+        }
+        // keep the selected completion within the lines shown
+        let available = (available_lines as usize).max(1);
+        let first = (self.selected + 1).saturating_sub(available);
+        self.values
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(available)
+            .map(|(index, suggestion)| {
+                let marker = if index == self.selected { ">" } else { " " };
+                match &suggestion.description {
+                    Some(description) => {
+                        format!("{} {}: {}", marker, suggestion.value, description)
+                    }
+                    None => format!("{} {}", marker, suggestion.value),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    }
+
+    fn min_rows(&self) -> u16 {
+        self.values.len().min(1) as u16
+    }
+
+    fn get_values(&self) -> &[Suggestion] {
+        &self.values
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::{Error, Result};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Aliases defined with the `alias` built-in command, optionally persisted to a file.
+/// The file is only read once the first line is expanded, so it can be configured in any
+/// order on the builder.
+#[derive(Default)]
+pub(crate) struct Aliases {
+    path: Option<PathBuf>,
+    loaded: bool,
+    aliases: BTreeMap<String, String>,
+    defaults: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            loaded: false,
+            aliases: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+        }
+    }
+
+    fn io_error(&self, err: std::io::Error) -> Error {
+        let path = self
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        Error::IoError(path, err.to_string())
+    }
+
+    fn ensure_loaded(&mut self) -> Result<()> {
+        if self.loaded {
+            return Ok(());
+        }
+        self.loaded = true;
+        if let Some(path) = &self.path {
+            match fs::read_to_string(path) {
+                Ok(content) => {
+                    for line in content.lines() {
+                        if let Some((name, expansion)) = parse_definition(line) {
+                            self.aliases.insert(name, expansion);
+                        }
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(self.io_error(err)),
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            let content: String = self
+                .aliases
+                .iter()
+                .map(|(name, expansion)| format!("{} = {}\n", name, expansion))
+                .collect();
+            fs::write(path, content).map_err(|err| self.io_error(err))?;
+        }
+        Ok(())
+    }
+
+    /// Replace the first word of `line` if it is an alias. Expansion happens only once,
+    /// so an alias may refer to a command of the same name.
+    pub(crate) fn expand<'a>(&mut self, line: &'a str) -> Result<Cow<'a, str>> {
+        self.ensure_loaded()?;
+        let (first, rest) = match line.split_once(char::is_whitespace) {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (line, None),
+        };
+        let expansion = self.aliases.get(first).or_else(|| self.defaults.get(first));
+        Ok(match (expansion, rest) {
+            (Some(expansion), Some(rest)) => Cow::Owned(format!("{} {}", expansion, rest)),
+            (Some(expansion), None) => Cow::Owned(expansion.clone()),
+            (None, _) => Cow::Borrowed(line),
+        })
+    }
+
+    pub(crate) fn get(&mut self, name: &str) -> Result<Option<String>> {
+        self.ensure_loaded()?;
+        Ok(self
+            .aliases
+            .get(name)
+            .or_else(|| self.defaults.get(name))
+            .cloned())
+    }
+
+    pub(crate) fn list(&mut self) -> Result<Vec<(String, String)>> {
+        self.ensure_loaded()?;
+        let mut aliases = self.defaults.clone();
+        aliases.extend(self.aliases.clone());
+        Ok(aliases.into_iter().collect())
+    }
+
+    /// Remove the aliases defined with [define_default](Aliases::define_default)
+    #[cfg(feature = "config")]
+    pub(crate) fn clear_defaults(&mut self) {
+        self.defaults.clear();
+    }
+
+    /// Define an alias which isn't saved, e.g. from a configuration file. Aliases defined
+    /// with the `alias` command take precedence
+    #[cfg(feature = "config")]
+    pub(crate) fn define_default(&mut self, name: String, expansion: String) {
+        self.defaults.insert(name, expansion);
+    }
+
+    pub(crate) fn define(&mut self, name: String, expansion: String) -> Result<()> {
+        self.ensure_loaded()?;
+        self.aliases.insert(name, expansion);
+        self.save()
+    }
+
+    /// Returns false if there was no alias with the given name
+    pub(crate) fn remove(&mut self, name: &str) -> Result<bool> {
+        self.ensure_loaded()?;
+        if self.aliases.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+/// Parse `name = expansion`
+pub(crate) fn parse_definition(definition: &str) -> Option<(String, String)> {
+    let (name, expansion) = definition.split_once('=')?;
+    let (name, expansion) = (name.trim(), expansion.trim());
+    if name.is_empty() || name.contains(char::is_whitespace) || expansion.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), expansion.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definition_is_trimmed() {
+        assert_eq!(
+            parse_definition(" ll =  list --long "),
+            Some(("ll".to_string(), "list --long".to_string()))
+        );
+    }
+
+    #[test]
+    fn expansion_may_contain_equals() {
+        assert_eq!(
+            parse_definition("dev=set env=dev"),
+            Some(("dev".to_string(), "set env=dev".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_definitions_are_rejected() {
+        assert_eq!(parse_definition("ll"), None);
+        assert_eq!(parse_definition("= list"), None);
+        assert_eq!(parse_definition("l l = list"), None);
+        assert_eq!(parse_definition("ll ="), None);
+    }
+}
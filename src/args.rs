@@ -0,0 +1,73 @@
+//! Typed access to the values of [ArgMatches]
+
+use crate::error::{Error, Result};
+use clap::ArgMatches;
+use std::str::FromStr;
+
+/// Extension trait over [ArgMatches] parsing argument values into any [FromStr] type,
+/// with errors which name the argument:
+///
+/// ```rust
+/// # use reedline_repl_rs::clap::ArgMatches;
+/// # use reedline_repl_rs::{ArgMatchesExt, Result};
+/// # use std::path::PathBuf;
+/// fn add<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+///     let first: i32 = args.req("first")?;
+///     let second = args.req::<i32>("second")?;
+///     let out = args.opt::<PathBuf>("out")?;
+///     # let _ = out;
+///     Ok(Some((first + second).to_string()))
+/// }
+/// ```
+pub trait ArgMatchesExt {
+    /// Parse the value of a required argument
+    fn req<T: FromStr>(&self, name: &str) -> Result<T>
+    where
+        T::Err: ToString;
+
+    /// Parse the value of an optional argument, `Ok(None)` if it wasn't given
+    fn opt<T: FromStr>(&self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: ToString;
+
+    /// Parse all values of an argument, an empty Vec if it wasn't given
+    fn all<T: FromStr>(&self, name: &str) -> Result<Vec<T>>
+    where
+        T::Err: ToString;
+}
+
+impl ArgMatchesExt for ArgMatches {
+    fn req<T: FromStr>(&self, name: &str) -> Result<T>
+    where
+        T::Err: ToString,
+    {
+        self.opt(name)?
+            .ok_or_else(|| Error::MissingArgument(name.to_string()))
+    }
+
+    fn opt<T: FromStr>(&self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: ToString,
+    {
+        Ok(self.all(name)?.into_iter().next())
+    }
+
+    fn all<T: FromStr>(&self, name: &str) -> Result<Vec<T>>
+    where
+        T::Err: ToString,
+    {
+        let values = match self.try_get_raw(name) {
+            Ok(Some(values)) => values,
+            Ok(None) => return Ok(vec![]),
+            Err(_) => return Err(Error::MissingArgument(name.to_string())),
+        };
+        values
+            .map(|value| {
+                let value = value.to_string_lossy();
+                value.parse().map_err(|err: T::Err| {
+                    Error::InvalidArgument(name.to_string(), value.to_string(), err.to_string())
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,89 @@
+//! Asking for the values of missing required arguments on the terminal, see
+//! [Repl::with_prompt_missing_args](crate::Repl::with_prompt_missing_args)
+
+use crate::output::Output;
+use clap::{Arg, Command};
+
+/// Ask for the required arguments missing in `argv`, returning the words to append to it.
+/// None if the missing arguments can't be determined or an answer was left empty. The
+/// questions are written to `output` and answered on stdin, so the caller has to make sure
+/// both are the terminal
+pub(crate) fn missing_args(
+    command: &Command,
+    argv: &[&str],
+    output: &Output,
+) -> Option<Vec<String>> {
+    let required: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| arg.is_required_set())
+        .collect();
+    // parsing without the required arguments tells which ones have been given
+    let mut relaxed = command.clone();
+    for arg in &required {
+        relaxed = relaxed.mut_arg(arg.get_id(), |arg| arg.required(false));
+    }
+    let matches = relaxed.try_get_matches_from(argv).ok()?;
+    let mut words = vec![];
+    for arg in required {
+        if matches.contains_id(arg.get_id().as_str()) {
+            continue;
+        }
+        let value = ask(arg, output)?;
+        match (arg.get_long(), arg.get_short()) {
+            (Some(long), _) => words.push(format!("--{}={}", long, value)),
+            (None, Some(short)) => words.push(format!("-{}{}", short, value)),
+            (None, None) => words.push(value),
+        }
+    }
+    Some(words)
+}
+
+/// Ask for the value of `arg`, offering its possible values as numbered choices
+fn ask(arg: &Arg, output: &Output) -> Option<String> {
+    let name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_string());
+    let choices: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if let Some(help) = arg.get_help() {
+        output.println(&format!("{}: {}", name, help)).ok()?;
+    }
+    for (index, choice) in choices.iter().enumerate() {
+        output
+            .println(&format!("  {}) {}", index + 1, choice))
+            .ok()?;
+    }
+    loop {
+        let question = match choices.len() {
+            0 => format!("{}: ", name),
+            count => format!("{} [1-{}]: ", name, count),
+        };
+        output.print(&question).ok()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return None;
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return None;
+        }
+        if choices.is_empty() {
+            return Some(answer.to_string());
+        }
+        let chosen = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .and_then(|index| choices.get(index))
+            .or_else(|| choices.iter().find(|choice| choice.as_str() == answer));
+        if let Some(choice) = chosen {
+            return Some(choice.clone());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Audit log of executed commands, see [Repl::with_audit_log](crate::Repl::with_audit_log)
+//!
+//! Each command is written as a line of JSON:
+//!
+//! ```text
+//! {"timestamp":"2023-05-01T12:00:00.000Z","user":"admin","command":"delete \"my file\"","duration_ms":1.52,"success":false,"error":"Error: permission denied"}
+//! ```
+use crate::error::{Error, Result};
+use serde_json::json;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) struct AuditLog {
+    name: String,
+    path: Option<PathBuf>,
+    // opened on the first entry when logging to a file
+    writer: Option<Box<dyn Write + Send>>,
+    user: String,
+}
+
+impl AuditLog {
+    /// Append to the file at `path`, creating it if it doesn't exist
+    pub(crate) fn file(path: PathBuf) -> Self {
+        Self {
+            name: path.display().to_string(),
+            path: Some(path),
+            writer: None,
+            user: default_user(),
+        }
+    }
+
+    pub(crate) fn writer(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            name: "<audit log>".to_string(),
+            path: None,
+            writer: Some(writer),
+            user: default_user(),
+        }
+    }
+
+    pub(crate) fn set_user(&mut self, user: &str) {
+        self.user = user.to_string();
+    }
+
+    /// Write the entry of an executed command, `error` is None if it succeeded
+    pub(crate) fn record(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        duration: Duration,
+        error: Option<String>,
+    ) -> Result<()> {
+        let entry = json!({
+            "timestamp": timestamp(SystemTime::now()),
+            "user": self.user,
+            "command": command_line(command, args),
+            "duration_ms": duration.as_secs_f64() * 1000.0,
+            "success": error.is_none(),
+            "error": error,
+        });
+        let io_error = |err: std::io::Error| Error::IoError(self.name.clone(), err.to_string());
+        if self.writer.is_none() {
+            if let Some(path) = &self.path {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(io_error)?;
+                self.writer = Some(Box::new(file));
+            }
+        }
+        if let Some(writer) = &mut self.writer {
+            writeln!(writer, "{}", entry)
+                .and_then(|_| writer.flush())
+                .map_err(io_error)?;
+        }
+        Ok(())
+    }
+}
+
+fn default_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// The command with its arguments, quoting arguments which would otherwise be split
+fn command_line(command: &str, args: &[&str]) -> String {
+    let mut line = command.to_string();
+    for arg in args {
+        line.push(' ');
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            line.push_str(&format!("\"{}\"", arg));
+        } else {
+            line.push_str(arg);
+        }
+    }
+    line
+}
+
+/// Format as RFC 3339 in UTC with milliseconds
+fn timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    // days since the epoch to the proleptic Gregorian calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        elapsed.subsec_millis()
+    )
+}
@@ -0,0 +1,53 @@
+//! ASCII-art banners rendered with the standard figlet font
+
+use figlet_rs::FIGfont;
+use yansi::{Color, Paint};
+
+/// Coloring of a banner rendered by [Repl::with_banner_figlet](crate::Repl::with_banner_figlet)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BannerStyle {
+    /// No colors
+    Plain,
+    /// All characters in one color
+    Color(Color),
+    /// Colors fading from the first RGB color on the left to the second on the right
+    Gradient((u8, u8, u8), (u8, u8, u8)),
+}
+
+/// Render `text` in the standard figlet font, falling back to the text itself if the font
+/// has no glyphs for it
+pub(crate) fn render_figlet(text: &str, style: BannerStyle) -> String {
+    let art = FIGfont::standard()
+        .ok()
+        .and_then(|font| font.convert(text).map(|figure| figure.to_string()))
+        .unwrap_or_else(|| text.to_string());
+    let lines: Vec<&str> = art.lines().map(str::trim_end).collect();
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| match style {
+            BannerStyle::Plain => line.to_string(),
+            BannerStyle::Color(color) => Paint::new(line).fg(color).to_string(),
+            BannerStyle::Gradient(from, to) => line
+                .chars()
+                .enumerate()
+                .map(|(column, c)| {
+                    let (r, g, b) = blend(from, to, column, width);
+                    Paint::new(c).fg(Color::RGB(r, g, b)).to_string()
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The color at `column` of a gradient over `width` columns
+fn blend(from: (u8, u8, u8), to: (u8, u8, u8), column: usize, width: usize) -> (u8, u8, u8) {
+    let t = column as f32 / width.saturating_sub(1).max(1) as f32;
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
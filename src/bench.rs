@@ -0,0 +1,26 @@
+//! Internals exposed for the benchmarks in `benches/`, not part of the public API
+
+use crate::error::Error;
+use crate::Repl;
+use reedline::{Completer, Suggestion};
+use std::fmt::Display;
+
+/// Suggestions of the completer for the cursor at the end of `line`
+pub fn complete<Context, E>(repl: &Repl<Context, E>, line: &str) -> Vec<Suggestion>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    repl.completer().complete(line, line.len())
+}
+
+/// Help of a command, or the overview for None, rendered for a terminal of `width` columns
+pub fn render_help<Context, E>(
+    repl: &Repl<Context, E>,
+    command: Option<&str>,
+    width: usize,
+) -> Option<String>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    repl.render_help(command, Some(width)).ok().flatten()
+}
@@ -0,0 +1,165 @@
+//! clap definitions of the optional built-in commands
+
+use crate::settings::Setting;
+use clap::{Arg, ArgAction, Command};
+
+/// Built-in commands which get the rest of the line unparsed, because they have a syntax
+/// of their own
+pub(crate) const RAW_INPUT_BUILTINS: &[&str] = &["eval"];
+
+pub(crate) fn record_command() -> Command {
+    Command::new("record")
+        .about("Record entered commands to a script file")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("start")
+                .about("Start recording to the given file")
+                .arg(Arg::new("file").required(true))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .action(ArgAction::SetTrue)
+                        .help("Also record command output as comments"),
+                ),
+        )
+        .subcommand(Command::new("stop").about("Stop recording"))
+}
+
+pub(crate) fn source_command() -> Command {
+    Command::new("source")
+        .about("Execute the commands of a file in the current session")
+        .arg(
+            Arg::new("file")
+                .required(true)
+                .help("Path of the file, relative paths are resolved against the sourcing file"),
+        )
+}
+
+pub(crate) fn alias_command() -> Command {
+    Command::new("alias")
+        .about("Define or list aliases, e.g. `alias st = status --all`")
+        .arg(
+            Arg::new("definition")
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .help("`name = command`, or just `name` to show a single alias"),
+        )
+}
+
+pub(crate) fn unalias_command() -> Command {
+    Command::new("unalias")
+        .about("Remove an alias")
+        .arg(Arg::new("name").required(true))
+}
+
+pub(crate) fn session_command() -> Command {
+    Command::new("session")
+        .about("Manage named sessions, each with its own state")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("new")
+                .about("Start a new session and switch to it")
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(Command::new("list").about("List the sessions, marking the current one"))
+        .subcommand(
+            Command::new("switch")
+                .about("Continue another session")
+                .arg(Arg::new("name").required(true)),
+        )
+}
+
+pub(crate) fn checkpoint_command() -> Command {
+    Command::new("checkpoint")
+        .about("Save the current state under a name to return to with `undo <name>`")
+        .arg(Arg::new("name").help("Name of the checkpoint, lists all checkpoints if missing"))
+}
+
+pub(crate) fn undo_command() -> Command {
+    Command::new("undo")
+        .about("Revert the changes of the last command, or return to a checkpoint")
+        .arg(Arg::new("checkpoint"))
+}
+
+pub(crate) fn set_command() -> Command {
+    let command = Command::new("set")
+        .about("Change a setting of the session, `show options` lists them")
+        .arg(
+            Arg::new("option")
+                .required(true)
+                .value_parser(Setting::NAMES),
+        )
+        .arg(Arg::new("value").required(true).value_parser(["on", "off"]));
+    #[cfg(feature = "config")]
+    let command = command.arg(
+        Arg::new("save")
+            .long("save")
+            .action(ArgAction::SetTrue)
+            .help("Also save the setting to the configuration file"),
+    );
+    command
+}
+
+#[cfg(feature = "config")]
+pub(crate) fn reload_config_command() -> Command {
+    Command::new("reload-config").about("Read the configuration file again and apply it")
+}
+
+pub(crate) fn stats_command() -> Command {
+    Command::new("stats")
+        .about("Show how often each command ran, failed and how long it took on average")
+        .arg(
+            Arg::new("reset")
+                .long("reset")
+                .action(ArgAction::SetTrue)
+                .help("Clear the statistics"),
+        )
+}
+
+#[cfg(feature = "clipboard")]
+pub(crate) fn copy_last_command() -> Command {
+    Command::new("copy-last").about("Copy the output of the previous command to the clipboard")
+}
+
+#[cfg(feature = "clipboard")]
+pub(crate) fn paste_command() -> Command {
+    Command::new("paste").about("Execute the lines of the clipboard")
+}
+
+pub(crate) fn examples_command() -> Command {
+    Command::new("examples")
+        .about("Show usage examples of a command")
+        .arg(Arg::new("command").required(true))
+}
+
+pub(crate) fn results_command() -> Command {
+    Command::new("results")
+        .about("Show the outputs of the last commands, referenced as $_, $1, $2, ...")
+}
+
+pub(crate) fn version_command() -> Command {
+    Command::new("version").about("Show the name and version")
+}
+
+pub(crate) fn about_command() -> Command {
+    Command::new("about").about("Show the name, version, description and build information")
+}
+
+pub(crate) fn show_command() -> Command {
+    Command::new("show")
+        .about("Show the settings of the session")
+        .arg(Arg::new("what").required(true).value_parser(["options"]))
+}
+
+#[cfg(feature = "lua")]
+pub(crate) fn eval_command() -> Command {
+    Command::new("eval")
+        .about(
+            "Evaluate Lua code, `run(\"command args\")` executes a command and returns its output",
+        )
+        .arg(Arg::new("code").required(true))
+}
@@ -0,0 +1,33 @@
+//! Capture of what command callbacks print to stdout and stderr themselves, e.g. with
+//! `println!`, see [Repl::with_stdio_capture](crate::Repl::with_stdio_capture)
+
+use gag::BufferRedirect;
+use std::io::{Read, Write};
+
+pub(crate) struct StdioCapture {
+    stdout: BufferRedirect,
+    stderr: BufferRedirect,
+}
+
+impl StdioCapture {
+    /// Redirect stdout and stderr into buffers, None if they can't be redirected, e.g.
+    /// because a capture is already running
+    pub(crate) fn start() -> Option<Self> {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        let stdout = BufferRedirect::stdout().ok()?;
+        let stderr = BufferRedirect::stderr().ok()?;
+        Some(Self { stdout, stderr })
+    }
+
+    /// Restore stdout and stderr, returning what has been printed to them in the meantime
+    pub(crate) fn finish(mut self) -> (String, String) {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let _ = self.stdout.read_to_string(&mut stdout);
+        let _ = self.stderr.read_to_string(&mut stderr);
+        (stdout, stderr)
+    }
+}
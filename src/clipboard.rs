@@ -0,0 +1,26 @@
+//! Access to the system clipboard for the `copy-last` and `paste` built-in commands
+
+use crate::error::{Error, Result};
+use arboard::Clipboard;
+use reedline::ReedlineEvent;
+
+/// Event pasting the clipboard, to bind it to a key, e.g.
+/// `keybindings.add_binding(KeyModifiers::CONTROL, KeyCode::Char('v'), paste_event())`.
+/// reedline can't insert text into the line being edited from outside, so the event runs
+/// the `paste` built-in command, which executes the lines of the clipboard like entered
+/// ones. Requires [with_clipboard_commands](crate::Repl::with_clipboard_commands)
+pub fn paste_event() -> ReedlineEvent {
+    ReedlineEvent::ExecuteHostCommand("paste".to_string())
+}
+
+pub(crate) fn copy(text: &str) -> Result<()> {
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text.to_string()))
+        .map_err(|err| Error::ClipboardError(err.to_string()))
+}
+
+pub(crate) fn paste() -> Result<String> {
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| Error::ClipboardError(err.to_string()))
+}
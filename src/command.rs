@@ -1,17 +1,50 @@
+use crate::ask;
+use crate::output::Output;
 #[cfg(feature = "async")]
-use crate::AsyncCallback;
-use crate::Callback;
-use clap::Command;
+use crate::{AsyncCallback, AsyncHandleCallback};
+use crate::{Callback, CommandErrorHandler, HandleCallback};
+use clap::error::ErrorKind;
+use clap::{ArgMatches, Command};
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-/// Struct to define a command in the REPL
+/// What happens after a command failed, returned by its
+/// [CommandErrorHandler](crate::CommandErrorHandler)
+pub enum CommandErrorAction {
+    /// Print the error with the error handler of the REPL
+    Report,
+    /// The error has been dealt with and isn't printed
+    Ignore,
+    /// Execute the command again
+    Retry,
+    /// Ask the user the question and execute the command again if the answer starts with
+    /// 'y'. Sessions without line editing take the next line as answer
+    AskRetry(String),
+}
 
+/// Callback of a command registered from a clap derive, which turns the matches back into
+/// the derived type before dispatching
+pub(crate) type DerivedCallback<Context, E> =
+    Box<dyn Fn(ArgMatches, &mut Context) -> Result<Option<String>, E>>;
+
+/// Struct to define a command in the REPL
 pub(crate) struct ReplCommand<Context, E> {
     pub(crate) name: String,
-    pub(crate) command: Command<'static>,
+    /// The definition, shared with the completer, highlighter and help
+    pub(crate) command: Arc<Command>,
+    // clap needs mutable access to parse, so parsing uses a copy built on first use
+    parser: Option<Command>,
     pub(crate) callback: Option<Callback<Context, E>>,
+    pub(crate) handle_callback: Option<HandleCallback<Context, E>>,
+    pub(crate) derived_callback: Option<DerivedCallback<Context, E>>,
     #[cfg(feature = "async")]
     pub(crate) async_callback: Option<AsyncCallback<Context, E>>,
+    #[cfg(feature = "async")]
+    pub(crate) async_handle_callback: Option<AsyncHandleCallback<Context, E>>,
+    pub(crate) error_handler: Option<CommandErrorHandler<Context, E>>,
+    /// Capabilities the session needs to see and execute the command
+    pub(crate) capabilities: Vec<String>,
 }
 
 impl<Context, E> fmt::Debug for ReplCommand<Context, E> {
@@ -28,28 +61,190 @@ impl<Context, E> PartialEq for ReplCommand<Context, E> {
 
 impl<Context, E> ReplCommand<Context, E> {
     /// Create a new command with the given name and callback function
-    pub fn new(name: &str, command: Command<'static>, callback: Callback<Context, E>) -> Self {
+    pub fn new(name: &str, command: Command, callback: Callback<Context, E>) -> Self {
         Self {
             name: name.to_string(),
-            command,
+            command: Arc::new(command),
+            parser: None,
             callback: Some(callback),
+            handle_callback: None,
+            derived_callback: None,
+            #[cfg(feature = "async")]
+            async_callback: None,
+            #[cfg(feature = "async")]
+            async_handle_callback: None,
+            error_handler: None,
+            capabilities: vec![],
+        }
+    }
+
+    /// Create a new command with the given name and a callback function receiving a ReplHandle
+    pub fn new_with_handle(
+        name: &str,
+        command: Command,
+        callback: HandleCallback<Context, E>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            command: Arc::new(command),
+            parser: None,
+            callback: None,
+            handle_callback: Some(callback),
+            derived_callback: None,
             #[cfg(feature = "async")]
             async_callback: None,
+            #[cfg(feature = "async")]
+            async_handle_callback: None,
+            error_handler: None,
+            capabilities: vec![],
+        }
+    }
+
+    /// Create a new command from a subcommand of a clap derive, the callback receives the
+    /// matches of a multicall command containing it, see [try_get_matches](Self::try_get_matches)
+    pub fn new_derived(
+        name: &str,
+        command: Command,
+        callback: DerivedCallback<Context, E>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            command: Arc::new(command),
+            parser: None,
+            callback: None,
+            handle_callback: None,
+            derived_callback: Some(callback),
+            #[cfg(feature = "async")]
+            async_callback: None,
+            #[cfg(feature = "async")]
+            async_handle_callback: None,
+            error_handler: None,
+            capabilities: vec![],
+        }
+    }
+
+    /// Parse the arguments of the command, `argv` starting with the command name
+    ///
+    /// Derived commands are parsed as subcommand of a multicall command, so the derived
+    /// type can be restored with `FromArgMatches`
+    pub fn try_get_matches(&mut self, argv: Vec<&str>) -> clap::error::Result<ArgMatches> {
+        let (name, command, derived) = (&self.name, &self.command, self.derived_callback.is_some());
+        self.parser
+            .get_or_insert_with(|| {
+                if derived {
+                    Command::new(name.clone())
+                        .multicall(true)
+                        .subcommand(command.as_ref().clone())
+                } else {
+                    command.as_ref().clone()
+                }
+            })
+            .try_get_matches_from_mut(argv)
+    }
+
+    /// Parse the arguments like [try_get_matches](Self::try_get_matches), asking for the
+    /// values of missing required arguments on the terminal instead of failing
+    pub(crate) fn try_get_matches_asking(
+        &mut self,
+        argv: Vec<&str>,
+        output: &Output,
+    ) -> clap::error::Result<ArgMatches> {
+        match self.try_get_matches(argv.clone()) {
+            Err(err) if err.kind() == ErrorKind::MissingRequiredArgument => {
+                match ask::missing_args(&self.command, &argv, output) {
+                    Some(answers) => {
+                        let mut argv = argv;
+                        argv.extend(answers.iter().map(String::as_str));
+                        self.try_get_matches(argv)
+                    }
+                    None => Err(err),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Mutable access to the definition, e.g. to add global arguments while building the Repl
+    pub(crate) fn command_mut(&mut self) -> &mut Command {
+        self.parser = None;
+        Arc::make_mut(&mut self.command)
+    }
+
+    /// The matches of the command itself, without the multicall command wrapping
+    /// derived commands
+    pub fn command_matches<'a>(&self, matches: &'a ArgMatches) -> &'a ArgMatches {
+        match matches.subcommand() {
+            Some((_, command_matches)) if self.derived_callback.is_some() => command_matches,
+            _ => matches,
         }
     }
 
     /// Create a new async command with the given name and callback function
     #[cfg(feature = "async")]
-    pub fn new_async(
+    pub fn new_async(name: &str, command: Command, callback: AsyncCallback<Context, E>) -> Self {
+        Self {
+            name: name.to_string(),
+            command: Arc::new(command),
+            parser: None,
+            callback: None,
+            handle_callback: None,
+            derived_callback: None,
+            async_callback: Some(callback),
+            async_handle_callback: None,
+            error_handler: None,
+            capabilities: vec![],
+        }
+    }
+
+    /// Create a new async command with the given name and a callback function receiving
+    /// a ReplHandle
+    #[cfg(feature = "async")]
+    pub fn new_async_with_handle(
         name: &str,
-        command: Command<'static>,
-        callback: AsyncCallback<Context, E>,
+        command: Command,
+        callback: AsyncHandleCallback<Context, E>,
     ) -> Self {
         Self {
             name: name.to_string(),
-            command,
+            command: Arc::new(command),
+            parser: None,
             callback: None,
-            async_callback: Some(callback),
+            handle_callback: None,
+            derived_callback: None,
+            async_callback: None,
+            async_handle_callback: Some(callback),
+            error_handler: None,
+            capabilities: vec![],
         }
     }
+
+    /// Returns true if the command can only be executed by the async Repl
+    #[cfg(all(feature = "async", feature = "lua"))]
+    pub fn is_async(&self) -> bool {
+        self.callback.is_none() && self.handle_callback.is_none() && self.derived_callback.is_none()
+    }
+}
+
+/// The definitions of all commands and built-ins, shared by the Repl with the completer and
+/// highlighter of its line editors. Registering or removing a command updates them in
+/// place, instead of rebuilding them from all commands
+#[derive(Clone, Default)]
+pub(crate) struct CommandIndex(Arc<RwLock<HashMap<String, Arc<Command>>>>);
+
+impl CommandIndex {
+    pub(crate) fn insert(&self, name: &str, command: Arc<Command>) {
+        self.write().insert(name.to_string(), command);
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.write().remove(name);
+    }
+
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Arc<Command>>> {
+        self.0.read().expect("command index lock poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Arc<Command>>> {
+        self.0.write().expect("command index lock poisoned")
+    }
 }
@@ -1,24 +1,64 @@
-use crate::command::ReplCommand;
-use clap::Command;
+use crate::command::CommandIndex;
+use clap::{Arg, Command};
 use reedline::{Completer, Span, Suggestion};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Completion of the arguments of a command, from a [CompletionCallback](crate::CompletionCallback)
+/// or a [ContextCompletionCallback](crate::ContextCompletionCallback) reading the Context
+pub(crate) type CompletionProvider = Arc<dyn Fn(&[&str], &str) -> Vec<String> + Send + Sync>;
 
 pub(crate) struct ReplCompleter {
-    commands: HashMap<String, Command<'static>>,
+    commands: CommandIndex,
+    completions: HashMap<String, CompletionProvider>,
+    // show the extra information of suggestions after their description
+    details: bool,
+    // description of the help command, from the Messages of the Repl
+    help_description: String,
 }
 
 impl Completer for ReplCompleter {
     fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
         let mut completions = vec![];
+        let line = &line[0..pos];
         completions.extend(if line.contains(' ') {
-            let mut words = line[0..pos].split(' ');
-            let first_word = words.next().unwrap();
+            let mut words = line.split(' ');
+            let Some(first_word) = words.next() else {
+                return vec![];
+            };
             let mut words_rev = words.rev();
-            if let Some(command) = self.commands.get(first_word) {
-                let last_word = words_rev.next().unwrap();
-                let last_word_start_pos = line.len() - last_word.len();
+            if let Some(command) = self.commands.read().get(first_word) {
+                let Some(last_word) = words_rev.next() else {
+                    return vec![];
+                };
+                let last_word_start_pos = pos - last_word.len();
                 let span = Span::new(last_word_start_pos, pos);
-                self.parameter_values_starting_with(command, words_rev.count(), last_word, span)
+                let mut args: Vec<&str> = words_rev.filter(|word| !word.is_empty()).collect();
+                args.reverse();
+                if let Some((arg, search, start)) = Self::inline_value(command, last_word) {
+                    let span = Span::new(last_word_start_pos + start, pos);
+                    let mut values = self.arg_values_starting_with(arg, search, span);
+                    if let Some(completion) = self.completions.get(first_word) {
+                        values.extend(
+                            completion(&args, search)
+                                .iter()
+                                .filter(|value| value.starts_with(search))
+                                .map(|value| self.build_suggestion(value, None, span)),
+                        );
+                    }
+                    return values;
+                }
+                let mut values =
+                    self.parameter_values_starting_with(command, args.len(), last_word, span);
+                if let Some(completion) = self.completions.get(first_word) {
+                    values.extend(
+                        completion(&args, last_word)
+                            .iter()
+                            .filter(|value| value.starts_with(last_word))
+                            .map(|value| self.build_suggestion(value, None, span)),
+                    );
+                }
+                values
             } else {
                 vec![]
             }
@@ -32,27 +72,123 @@ impl Completer for ReplCompleter {
 }
 
 impl ReplCompleter {
-    pub fn new<Context, E>(repl_commands: &HashMap<String, ReplCommand<Context, E>>) -> Self {
-        let mut commands = HashMap::new();
-        for (name, repl_command) in repl_commands.iter() {
-            commands.insert(name.clone(), repl_command.command.clone());
+    pub fn new(commands: CommandIndex, completions: &HashMap<String, CompletionProvider>) -> Self {
+        ReplCompleter {
+            commands,
+            completions: completions.clone(),
+            details: false,
+            help_description: String::new(),
         }
-        ReplCompleter { commands }
     }
 
-    fn build_suggestion(&self, value: &str, help: Option<&str>, span: Span) -> Suggestion {
+    /// Describe the help command with the given text
+    pub fn with_help_description(mut self, description: &str) -> Self {
+        self.help_description = description.to_string();
+        self
+    }
+
+    /// Append the extra information of suggestions, like the type of a flag's value or if
+    /// it is required, to their description shown in the menu
+    pub fn with_details(mut self, details: bool) -> Self {
+        self.details = details;
+        self
+    }
+
+    fn build_suggestion(&self, value: &str, help: Option<String>, span: Span) -> Suggestion {
         Suggestion {
             value: value.to_string(),
-            description: help.map(|n| n.to_string()),
+            description: help,
             extra: None,
             span,
             append_whitespace: true,
         }
     }
 
+    /// Suggestion of a flag of `arg`, with its value type, default and whether it is
+    /// required as extra information
+    fn build_flag_suggestion(&self, value: &str, arg: &Arg, span: Span) -> Suggestion {
+        let mut extra = vec![];
+        if arg.get_action().takes_values() {
+            match arg.get_value_names() {
+                Some(names) => extra.extend(names.iter().map(|name| format!("<{}>", name))),
+                None => extra.push(format!("<{}>", arg.get_id().as_str().to_uppercase())),
+            }
+        } else {
+            extra.push("flag".to_string());
+        }
+        let defaults: Vec<String> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy().into_owned())
+            .collect();
+        if !defaults.is_empty() {
+            extra.push(format!("default: {}", defaults.join(",")));
+        }
+        if arg.is_required_set() {
+            extra.push("required".to_string());
+        }
+        let help = arg.get_help().map(|help| help.to_string());
+        let description = match help {
+            Some(help) if self.details => Some(format!("{} [{}]", help, extra.join(", "))),
+            None if self.details => Some(format!("[{}]", extra.join(", "))),
+            help => help,
+        };
+        Suggestion {
+            extra: Some(extra),
+            ..self.build_suggestion(value, description, span)
+        }
+    }
+
+    /// The argument whose value is being entered attached to its flag, as `--mode=val` or
+    /// `-mval`, with the value so far and its offset in the word
+    fn inline_value<'a, 'w>(
+        command: &'a Command,
+        word: &'w str,
+    ) -> Option<(&'a Arg, &'w str, usize)> {
+        let takes_values = |arg: &&Arg| arg.get_action().takes_values();
+        if let Some((long, value)) = word
+            .strip_prefix("--")
+            .and_then(|word| word.split_once('='))
+        {
+            let arg = command
+                .get_arguments()
+                .filter(takes_values)
+                .find(|arg| arg.get_long() == Some(long))?;
+            return Some((arg, value, long.len() + 3));
+        }
+        let short = word
+            .strip_prefix('-')
+            .filter(|rest| !rest.starts_with('-'))?;
+        let mut chars = short.chars();
+        let flag = chars.next()?;
+        let value = chars.as_str();
+        if value.is_empty() {
+            return None;
+        }
+        let arg = command
+            .get_arguments()
+            .filter(takes_values)
+            .find(|arg| arg.get_short() == Some(flag))?;
+        Some((arg, value, 1 + flag.len_utf8()))
+    }
+
+    fn arg_values_starting_with(&self, arg: &Arg, search: &str, span: Span) -> Vec<Suggestion> {
+        arg.get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set() && value.get_name().starts_with(search))
+            .map(|value| {
+                self.build_suggestion(
+                    value.get_name(),
+                    value.get_help().map(|help| help.to_string()),
+                    span,
+                )
+            })
+            .collect()
+    }
+
     fn parameter_values_starting_with(
         &self,
-        command: &Command<'static>,
+        command: &Command,
         _parameter_idx: usize,
         search: &str,
         span: Span,
@@ -63,28 +199,30 @@ impl ReplCompleter {
             if arg.is_global_set() {
                 continue;
             }
-            if let Some(possible_values) = arg.get_possible_values() {
-                completions.extend(
-                    possible_values
-                        .iter()
-                        .filter(|value| value.get_name().starts_with(search))
-                        .map(|value| {
-                            self.build_suggestion(value.get_name(), value.get_help(), span)
-                        }),
-                );
-            }
+            completions.extend(
+                arg.get_possible_values()
+                    .iter()
+                    .filter(|value| value.get_name().starts_with(search))
+                    .map(|value| {
+                        self.build_suggestion(
+                            value.get_name(),
+                            value.get_help().map(|help| help.to_string()),
+                            span,
+                        )
+                    }),
+            );
 
             if let Some(long) = arg.get_long() {
                 let value = "--".to_string() + long;
                 if value.starts_with(search) {
-                    completions.push(self.build_suggestion(&value, arg.get_help(), span));
+                    completions.push(self.build_flag_suggestion(&value, arg, span));
                 }
             }
 
             if let Some(short) = arg.get_short() {
                 let value = "-".to_string() + &short.to_string();
                 if value.starts_with(search) {
-                    completions.push(self.build_suggestion(&value, arg.get_help(), span));
+                    completions.push(self.build_flag_suggestion(&value, arg, span));
                 }
             }
         }
@@ -94,15 +232,21 @@ impl ReplCompleter {
     fn commands_starting_with(&self, search: &str, span: Span) -> Vec<Suggestion> {
         let mut result: Vec<Suggestion> = self
             .commands
+            .read()
             .iter()
             .filter(|(key, _)| key.starts_with(search))
             .map(|(_, command)| {
-                self.build_suggestion(command.get_name(), command.get_about(), span)
+                self.build_suggestion(
+                    command.get_name(),
+                    command.get_about().map(|about| about.to_string()),
+                    span,
+                )
             })
             .collect();
 
         if "help".starts_with(search) {
-            result.push(self.build_suggestion("help", Some("show help"), span));
+            let about = self.help_description.clone();
+            result.push(self.build_suggestion("help", Some(about), span));
         }
 
         result
@@ -0,0 +1,26 @@
+//! Shell completion scripts for the commands of a Repl used as one-shot CLI
+
+use crate::error::{Error, Result};
+use clap::Command;
+use clap_complete::{generate, Shell};
+use std::str::FromStr;
+
+/// Name of the command line subcommand printing a completion script, see
+/// [Repl::run_with_args](crate::Repl::run_with_args)
+pub(crate) const COMPLETIONS_COMMAND: &str = "completions";
+
+/// Generate the completion script for `shell` (bash, elvish, fish, powershell or zsh)
+pub(crate) fn script<'a>(
+    shell: &str,
+    bin_name: &str,
+    commands: impl Iterator<Item = &'a Command>,
+) -> Result<String> {
+    let shell = Shell::from_str(shell).map_err(|_| Error::UnknownShell(shell.to_string()))?;
+    let mut app = Command::new(bin_name.to_string());
+    for command in commands {
+        app = app.subcommand(command.clone());
+    }
+    let mut script: Vec<u8> = Vec::new();
+    generate(shell, &mut app, bin_name, &mut script);
+    Ok(String::from_utf8_lossy(&script).into_owned())
+}
@@ -0,0 +1,295 @@
+//! Settings loaded from a TOML configuration file, see
+//! [Repl::with_config_file](crate::Repl::with_config_file)
+
+use crate::error::{Error, Result};
+use crate::settings::format_switch;
+use crate::theme::Theme;
+use crossterm::event::{KeyCode, KeyModifiers};
+use reedline::ReedlineEvent;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use yansi::{Color, Style};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) theme: Option<String>,
+    pub(crate) prompt: Option<PromptConfig>,
+    pub(crate) history: Option<HistoryConfig>,
+    pub(crate) completion: CompletionConfig,
+    pub(crate) keybindings: BTreeMap<String, String>,
+    pub(crate) aliases: BTreeMap<String, String>,
+    pub(crate) settings: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PromptConfig {
+    text: Option<String>,
+    color: Option<String>,
+    bold: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HistoryConfig {
+    pub(crate) file: PathBuf,
+    #[serde(default = "default_history_capacity")]
+    pub(crate) capacity: usize,
+}
+
+fn default_history_capacity() -> usize {
+    1000
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CompletionConfig {
+    pub(crate) quick: Option<bool>,
+    pub(crate) partial: Option<bool>,
+    pub(crate) hints: Option<bool>,
+}
+
+impl Config {
+    /// Read the file at `path`, None if it doesn't exist
+    pub(crate) fn load(path: &Path) -> Result<Option<Self>> {
+        let config_error =
+            |message: String| Error::ConfigError(path.display().to_string(), message);
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(config_error(err.to_string())),
+        };
+        let config: Config =
+            toml::from_str(&content).map_err(|err| config_error(err.to_string()))?;
+        // report invalid entries when loading instead of ignoring them
+        for (key, action) in &config.keybindings {
+            parse_key(key).ok_or_else(|| config_error(format!("invalid key '{}'", key)))?;
+            parse_action(action)
+                .ok_or_else(|| config_error(format!("invalid action '{}'", action)))?;
+        }
+        if let Some(theme) = &config.theme {
+            parse_theme(theme).ok_or_else(|| config_error(format!("invalid theme '{}'", theme)))?;
+        }
+        if let Some(color) = config
+            .prompt
+            .as_ref()
+            .and_then(|prompt| prompt.color.as_ref())
+        {
+            parse_color(color).ok_or_else(|| config_error(format!("invalid color '{}'", color)))?;
+        }
+        Ok(Some(config))
+    }
+
+    /// The settings as name and value, like they are given to the `set` command
+    pub(crate) fn settings(&self) -> Vec<(String, String)> {
+        self.settings
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    toml::Value::Boolean(value) => format_switch(*value).to_string(),
+                    toml::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    /// The theme preset named in the file
+    pub(crate) fn theme(&self) -> Option<Theme> {
+        self.theme.as_deref().and_then(parse_theme)
+    }
+
+    /// The keybindings as key combination and event
+    pub(crate) fn key_events(&self) -> Vec<(KeyModifiers, KeyCode, ReedlineEvent)> {
+        self.keybindings
+            .iter()
+            .filter_map(|(key, action)| {
+                let (modifiers, code) = parse_key(key)?;
+                Some((modifiers, code, parse_action(action)?))
+            })
+            .collect()
+    }
+}
+
+impl PromptConfig {
+    /// The prompt with the configured style, None if no text is configured
+    pub(crate) fn render(&self) -> Option<String> {
+        let text = self.text.as_ref()?;
+        let mut style = self
+            .color
+            .as_deref()
+            .and_then(parse_color)
+            .map(Style::new)
+            .unwrap_or_default();
+        if self.bold {
+            style = style.bold();
+        }
+        Some(style.paint(text).to_string())
+    }
+}
+
+/// Store a setting changed with `set --save` in the `[settings]` table of the file at
+/// `path`, creating the file if it doesn't exist. Only that key is touched, comments and
+/// formatting of the rest of the file are kept
+pub(crate) fn save_setting(path: &Path, name: &str, value: &str) -> Result<()> {
+    let config_error = |message: String| Error::ConfigError(path.display().to_string(), message);
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(config_error(err.to_string())),
+    };
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|err: toml_edit::TomlError| config_error(err.to_string()))?;
+    let settings = document
+        .entry("settings")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| config_error("'settings' is not a table".to_string()))?;
+    settings.insert(name, toml_edit::value(value));
+    std::fs::write(path, document.to_string()).map_err(|err| config_error(err.to_string()))
+}
+
+/// Parse keys like `ctrl-l`, `alt-enter` or `f5`
+fn parse_key(key: &str) -> Option<(KeyModifiers, KeyCode)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = key.split('-').collect();
+    let name = parts.pop()?.to_lowercase();
+    for modifier in parts {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let code = match name.as_str() {
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        name if name.len() > 1 && name.starts_with('f') => KeyCode::F(name[1..].parse().ok()?),
+        name if name.chars().count() == 1 => KeyCode::Char(name.chars().next()?),
+        _ => return None,
+    };
+    Some((modifiers, code))
+}
+
+/// Parse actions like `clear-screen`, or `run:<line>` executing a line
+fn parse_action(action: &str) -> Option<ReedlineEvent> {
+    if let Some(line) = action.strip_prefix("run:") {
+        return Some(ReedlineEvent::ExecuteHostCommand(line.trim().to_string()));
+    }
+    Some(match action {
+        "complete" => ReedlineEvent::Menu("completion_menu".to_string()),
+        "clear-screen" => ReedlineEvent::ClearScreen,
+        "submit" => ReedlineEvent::Enter,
+        "history-previous" => ReedlineEvent::PreviousHistory,
+        "history-next" => ReedlineEvent::NextHistory,
+        "history-search" => ReedlineEvent::SearchHistory,
+        "hint-complete" => ReedlineEvent::HistoryHintComplete,
+        "editor" => ReedlineEvent::OpenEditor,
+        "none" => ReedlineEvent::None,
+        _ => return None,
+    })
+}
+
+fn parse_theme(name: &str) -> Option<Theme> {
+    Some(match name.to_lowercase().as_str() {
+        "dark" => Theme::dark(),
+        "light" => Theme::light(),
+        "monochrome" => Theme::monochrome(),
+        "solarized" => Theme::solarized(),
+        _ => return None,
+    })
+}
+
+fn parse_color(color: &str) -> Option<Color> {
+    Some(match color.to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "default" => Color::Default,
+        color => Color::Fixed(color.parse().ok()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_with_modifiers() {
+        assert_eq!(
+            parse_key("ctrl-alt-x"),
+            Some((
+                KeyModifiers::CONTROL | KeyModifiers::ALT,
+                KeyCode::Char('x')
+            ))
+        );
+        assert_eq!(
+            parse_key("Control-Space"),
+            Some((KeyModifiers::CONTROL, KeyCode::Char(' ')))
+        );
+        assert_eq!(
+            parse_key("shift-tab"),
+            Some((KeyModifiers::SHIFT, KeyCode::Tab))
+        );
+    }
+
+    #[test]
+    fn named_and_function_keys() {
+        assert_eq!(
+            parse_key("pageup"),
+            Some((KeyModifiers::NONE, KeyCode::PageUp))
+        );
+        assert_eq!(parse_key("F12"), Some((KeyModifiers::NONE, KeyCode::F(12))));
+        assert_eq!(
+            parse_key("f"),
+            Some((KeyModifiers::NONE, KeyCode::Char('f')))
+        );
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        assert_eq!(parse_key("hyper-x"), None);
+        assert_eq!(parse_key("fx"), None);
+        assert_eq!(parse_key("ctrl-"), None);
+        assert_eq!(parse_key("nokey"), None);
+    }
+
+    #[test]
+    fn actions() {
+        assert_eq!(
+            parse_action("clear-screen"),
+            Some(ReedlineEvent::ClearScreen)
+        );
+        assert_eq!(
+            parse_action("complete"),
+            Some(ReedlineEvent::Menu("completion_menu".to_string()))
+        );
+        assert_eq!(
+            parse_action("run: git status "),
+            Some(ReedlineEvent::ExecuteHostCommand("git status".to_string()))
+        );
+        assert_eq!(parse_action("fly"), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Detection of what the console can display, to degrade gracefully on legacy consoles
+
+/// How a Repl treats the console, see [Repl::with_console_mode](crate::Repl::with_console_mode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsoleMode {
+    /// Detect the capabilities of the console. On Unix the Unicode glyphs are only
+    /// dropped if the locale names a character set other than UTF-8, like
+    /// `LANG=de_DE.ISO-8859-1`. `C`, `POSIX` or no locale at all keep them, since such
+    /// environments mostly run in UTF-8 capable terminals anyway
+    #[default]
+    Auto,
+    /// Assume a console with Unicode glyphs and ANSI escape sequences
+    Modern,
+    /// Assume a legacy console: ASCII prompt indicators, no ANSI colors and CRLF line
+    /// endings
+    Legacy,
+}
+
+/// What the console can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ConsoleCapabilities {
+    pub(crate) unicode: bool,
+    pub(crate) ansi: bool,
+    pub(crate) crlf: bool,
+}
+
+const MODERN: ConsoleCapabilities = ConsoleCapabilities {
+    unicode: true,
+    ansi: true,
+    crlf: false,
+};
+
+const LEGACY: ConsoleCapabilities = ConsoleCapabilities {
+    unicode: false,
+    ansi: false,
+    crlf: true,
+};
+
+impl ConsoleCapabilities {
+    pub(crate) fn detect(mode: ConsoleMode) -> Self {
+        match mode {
+            ConsoleMode::Auto => detect(),
+            ConsoleMode::Modern => MODERN,
+            ConsoleMode::Legacy => LEGACY,
+        }
+    }
+}
+
+/// Consoles which can't process virtual terminal sequences are legacy consoles. Modern
+/// ones render the Unicode glyphs only when hosted by a terminal like Windows Terminal or
+/// ConEmu, the default conhost font lacks them
+#[cfg(windows)]
+fn detect() -> ConsoleCapabilities {
+    use winapi_util::console::Console;
+    let virtual_terminal = Console::stdout()
+        .and_then(|mut console| console.set_virtual_terminal_processing(true))
+        .is_ok();
+    windows_capabilities(virtual_terminal, |name| std::env::var_os(name).is_some())
+}
+
+#[cfg(any(windows, test))]
+fn windows_capabilities(
+    virtual_terminal: bool,
+    is_set: impl Fn(&str) -> bool,
+) -> ConsoleCapabilities {
+    if !virtual_terminal {
+        return LEGACY;
+    }
+    let terminal_host = ["WT_SESSION", "TERM_PROGRAM", "ConEmuANSI", "TERM"]
+        .iter()
+        .any(|name| is_set(name));
+    ConsoleCapabilities {
+        unicode: terminal_host,
+        ansi: true,
+        crlf: false,
+    }
+}
+
+/// Terminals process ANSI sequences, Unicode depends on the character set of the locale
+#[cfg(not(windows))]
+fn detect() -> ConsoleCapabilities {
+    unix_capabilities(|name| std::env::var(name).ok())
+}
+
+#[cfg(any(not(windows), test))]
+fn unix_capabilities(var: impl Fn(&str) -> Option<String>) -> ConsoleCapabilities {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty());
+    // the character set follows the dot, e.g. `en_US.UTF-8` or `de_DE.ISO-8859-1@euro`
+    let charset = locale
+        .as_deref()
+        .and_then(|locale| locale.split_once('.'))
+        .map(|(_, charset)| charset.split('@').next().unwrap_or_default().to_lowercase());
+    let unicode = match charset {
+        Some(charset) => charset == "utf-8" || charset == "utf8",
+        None => true,
+    };
+    ConsoleCapabilities { unicode, ..MODERN }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn utf8_locale_is_unicode() {
+        assert!(unix_capabilities(env(&[("LANG", "en_US.UTF-8")])).unicode);
+        assert!(unix_capabilities(env(&[("LC_ALL", "de_DE.utf8")])).unicode);
+    }
+
+    #[test]
+    fn other_charset_is_ascii() {
+        let capabilities = unix_capabilities(env(&[("LANG", "de_DE.ISO-8859-1@euro")]));
+        assert_eq!(
+            capabilities,
+            ConsoleCapabilities {
+                unicode: false,
+                ..MODERN
+            }
+        );
+    }
+
+    #[test]
+    fn missing_or_plain_locale_is_unicode() {
+        assert_eq!(unix_capabilities(env(&[])), MODERN);
+        assert_eq!(unix_capabilities(env(&[("LANG", "C")])), MODERN);
+        assert_eq!(unix_capabilities(env(&[("LANG", "POSIX")])), MODERN);
+        assert_eq!(unix_capabilities(env(&[("LANG", "")])), MODERN);
+    }
+
+    #[test]
+    fn lc_all_overrides_lang() {
+        let vars = [("LC_ALL", "en_US.ISO-8859-1"), ("LANG", "en_US.UTF-8")];
+        assert!(!unix_capabilities(env(&vars)).unicode);
+        let vars = [
+            ("LC_ALL", ""),
+            ("LC_CTYPE", "en_US.UTF-8"),
+            ("LANG", "C.ISO-8859-1"),
+        ];
+        assert!(unix_capabilities(env(&vars)).unicode);
+    }
+
+    #[test]
+    fn windows_without_virtual_terminal_is_legacy() {
+        assert_eq!(windows_capabilities(false, |_| true), LEGACY);
+    }
+
+    #[test]
+    fn windows_terminal_host_is_unicode() {
+        assert_eq!(
+            windows_capabilities(true, |name| name == "WT_SESSION"),
+            MODERN
+        );
+    }
+
+    #[test]
+    fn windows_conhost_is_ascii() {
+        assert_eq!(
+            windows_capabilities(true, |_| false),
+            ConsoleCapabilities {
+                unicode: false,
+                ..MODERN
+            }
+        );
+    }
+
+    #[test]
+    fn explicit_modes_skip_detection() {
+        assert_eq!(ConsoleCapabilities::detect(ConsoleMode::Modern), MODERN);
+        assert_eq!(ConsoleCapabilities::detect(ConsoleMode::Legacy), LEGACY);
+    }
+}
@@ -0,0 +1,133 @@
+//! Storage of the Context, owned by the Repl or shared with other threads
+
+#[cfg(feature = "persistence")]
+use crate::error::{Error, Result};
+#[cfg(feature = "persistence")]
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "persistence")]
+use std::fs;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "persistence")]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock, RwLockWriteGuard};
+
+pub(crate) enum ContextStore<Context> {
+    Owned(Context),
+    Shared(Arc<RwLock<Context>>),
+}
+
+/// Mutable access to the Context while a callback runs. A shared Context is write locked
+/// meanwhile, so other threads see the changes of a command as a whole
+pub(crate) enum ContextGuard<'a, Context> {
+    Owned(&'a mut Context),
+    Shared(RwLockWriteGuard<'a, Context>),
+}
+
+impl<Context> ContextStore<Context> {
+    /// Read the Context, e.g. for the prompt
+    pub(crate) fn with_ref<R>(&self, f: impl FnOnce(&Context) -> R) -> R {
+        match self {
+            ContextStore::Owned(context) => f(context),
+            ContextStore::Shared(context) => {
+                f(&context.read().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+
+    /// The Context shared behind a lock, moving an owned Context there
+    pub(crate) fn into_shared(self) -> Arc<RwLock<Context>> {
+        match self {
+            ContextStore::Owned(context) => Arc::new(RwLock::new(context)),
+            ContextStore::Shared(context) => context,
+        }
+    }
+
+    pub(crate) fn lock(&mut self) -> ContextGuard<'_, Context> {
+        match self {
+            ContextStore::Owned(context) => ContextGuard::Owned(context),
+            // a panic caught in a command poisons the lock, the Repl keeps running regardless
+            ContextStore::Shared(context) => {
+                ContextGuard::Shared(context.write().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+}
+
+impl<Context> Deref for ContextGuard<'_, Context> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        match self {
+            ContextGuard::Owned(context) => context,
+            ContextGuard::Shared(guard) => guard,
+        }
+    }
+}
+
+impl<Context> DerefMut for ContextGuard<'_, Context> {
+    fn deref_mut(&mut self) -> &mut Context {
+        match self {
+            ContextGuard::Owned(context) => context,
+            ContextGuard::Shared(guard) => guard,
+        }
+    }
+}
+
+/// Saving the Context to a JSON file on exit and restoring it on start, see
+/// [Repl::with_context_persistence](crate::Repl::with_context_persistence)
+#[cfg(feature = "persistence")]
+pub(crate) struct Persistence<Context> {
+    path: PathBuf,
+    // the serde bounds are only required where persistence is configured
+    save: fn(&Context, &Path) -> Result<()>,
+    restore: fn(&Path) -> Result<Option<Context>>,
+}
+
+#[cfg(feature = "persistence")]
+impl<Context> Persistence<Context> {
+    pub(crate) fn new(path: PathBuf) -> Self
+    where
+        Context: Serialize + DeserializeOwned,
+    {
+        Self {
+            path,
+            save: save_json::<Context>,
+            restore: restore_json::<Context>,
+        }
+    }
+
+    pub(crate) fn save(&self, context: &Context) -> Result<()> {
+        (self.save)(context, &self.path)
+    }
+
+    /// The saved Context, None if nothing has been saved yet
+    pub(crate) fn restore(&self) -> Result<Option<Context>> {
+        (self.restore)(&self.path)
+    }
+}
+
+#[cfg(feature = "persistence")]
+fn persistence_error(path: &Path, message: impl ToString) -> Error {
+    Error::PersistenceError(path.display().to_string(), message.to_string())
+}
+
+#[cfg(feature = "persistence")]
+fn save_json<Context: Serialize>(context: &Context, path: &Path) -> Result<()> {
+    let json = serde_json::to_string_pretty(context).map_err(|err| persistence_error(path, err))?;
+    // replace the file at once, so an interrupted save doesn't lose the previous state
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, json).map_err(|err| persistence_error(path, err))?;
+    fs::rename(&temporary, path).map_err(|err| persistence_error(path, err))
+}
+
+#[cfg(feature = "persistence")]
+fn restore_json<Context: DeserializeOwned>(path: &Path) -> Result<Option<Context>> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(persistence_error(path, err)),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|err| persistence_error(path, err))
+}
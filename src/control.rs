@@ -0,0 +1,130 @@
+//! Unix domain socket accepting commands while the interactive REPL runs
+//!
+//! Every line received on a connection is executed like an entered line and answered with
+//! its output, or the error message if it failed. Execution happens on the REPL's thread:
+//! the connection thread queues the line and wakes the Repl, see [signal](crate::signal).
+//! While a line is being edited, requests wait until it has been submitted.
+use crate::signal::Interrupts;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Line returned by `read_line` when control requests are waiting
+pub(crate) const CONTROL_COMMAND: &str = "\u{10}";
+
+/// A line received on the control socket, answered through `reply`
+pub(crate) struct ControlRequest {
+    pub(crate) line: String,
+    reply: Sender<String>,
+}
+
+impl ControlRequest {
+    pub(crate) fn reply(self, output: String) {
+        let _ = self.reply.send(output);
+    }
+}
+
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+    // device and inode of the socket file, so only the own socket is removed
+    identity: (u64, u64),
+    requests: Receiver<ControlRequest>,
+    next: Option<ControlRequest>,
+}
+
+impl ControlSocket {
+    /// Listen on the socket at `path`, replacing a stale socket file of a previous run.
+    /// Anything else at `path`, including a socket something listens on, is left alone
+    /// and gives an [AlreadyExists](io::ErrorKind::AlreadyExists) error
+    pub(crate) fn bind(path: &Path, interrupts: &Interrupts) -> io::Result<Self> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() && is_stale(path) => {
+                fs::remove_file(path)?;
+            }
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", path.display()),
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let listener = UnixListener::bind(path)?;
+        let metadata = fs::symlink_metadata(path)?;
+        let (sender, requests) = mpsc::channel();
+        let interrupts = interrupts.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                let interrupts = interrupts.clone();
+                thread::spawn(move || serve(stream, sender, &interrupts));
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            identity: (metadata.dev(), metadata.ino()),
+            requests,
+            next: None,
+        })
+    }
+
+    /// Returns true if a request is waiting
+    pub(crate) fn pending(&mut self) -> bool {
+        if self.next.is_none() {
+            self.next = self.requests.try_recv().ok();
+        }
+        self.next.is_some()
+    }
+
+    /// The next waiting request, if any
+    pub(crate) fn next_request(&mut self) -> Option<ControlRequest> {
+        self.next.take().or_else(|| self.requests.try_recv().ok())
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        // the socket may have been replaced by another process since
+        if let Ok(metadata) = fs::symlink_metadata(&self.path) {
+            if (metadata.dev(), metadata.ino()) == self.identity {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+/// Returns true if nothing listens on the socket at `path` anymore
+fn is_stale(path: &Path) -> bool {
+    matches!(UnixStream::connect(path), Err(err) if err.kind() == io::ErrorKind::ConnectionRefused)
+}
+
+fn serve(stream: UnixStream, requests: Sender<ControlRequest>, interrupts: &Interrupts) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let (reply, response) = mpsc::channel();
+        if requests.send(ControlRequest { line, reply }).is_err() {
+            return;
+        }
+        interrupts.wake();
+        let output = match response.recv() {
+            Ok(output) => output,
+            Err(_) => return,
+        };
+        if writeln!(writer, "{}", output).is_err() {
+            return;
+        }
+    }
+}
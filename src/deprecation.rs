@@ -0,0 +1,74 @@
+use crate::messages::{self, Messages};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use std::collections::{HashMap, HashSet};
+
+/// Deprecated commands and arguments with their replacements, see
+/// [Repl::with_deprecated_command](crate::Repl::with_deprecated_command)
+#[derive(Default)]
+pub(crate) struct Deprecations {
+    commands: HashMap<String, String>,
+    // arguments by command and argument id
+    args: HashMap<String, HashMap<String, String>>,
+    // warned about in this session, commands as name and arguments as `name --id`
+    warned: HashSet<String>,
+}
+
+impl Deprecations {
+    pub(crate) fn deprecate_command(&mut self, command: &str, replacement: &str) {
+        self.commands
+            .insert(command.to_string(), replacement.to_string());
+    }
+
+    pub(crate) fn deprecate_arg(&mut self, command: &str, arg: &str, replacement: &str) {
+        self.args
+            .entry(command.to_string())
+            .or_default()
+            .insert(arg.to_string(), replacement.to_string());
+    }
+
+    /// The replacement of a deprecated command, empty if there is none
+    pub(crate) fn command(&self, command: &str) -> Option<&str> {
+        self.commands.get(command).map(String::as_str)
+    }
+
+    /// The deprecated arguments of a command by id with their replacements
+    pub(crate) fn args(&self, command: &str) -> impl Iterator<Item = (&str, &str)> {
+        self.args
+            .get(command)
+            .into_iter()
+            .flatten()
+            .map(|(arg, replacement)| (arg.as_str(), replacement.as_str()))
+    }
+
+    /// The deprecated command and arguments given on the command line which haven't been
+    /// warned about yet, as name and replacement. Arguments are named `command --id`
+    pub(crate) fn take_used(
+        &mut self,
+        command: &str,
+        matches: &ArgMatches,
+    ) -> Vec<(String, String)> {
+        let mut used = vec![];
+        if let Some(replacement) = self.commands.get(command) {
+            used.push((command.to_string(), replacement.clone()));
+        }
+        for (arg, replacement) in self.args(command) {
+            // unknown ids are skipped, they may have been registered for another definition
+            let given = matches.try_contains_id(arg).unwrap_or(false)
+                && matches.value_source(arg) == Some(ValueSource::CommandLine);
+            if given {
+                used.push((format!("{} --{}", command, arg), replacement.to_string()));
+            }
+        }
+        used.retain(|(name, _)| self.warned.insert(name.clone()));
+        used
+    }
+}
+
+/// The warning about using the deprecated command or argument `name`
+pub(crate) fn warning(messages: &Messages, name: &str, replacement: &str) -> String {
+    match replacement {
+        "" => messages::fill(&messages.deprecated, &[name]),
+        replacement => messages::fill(&messages.deprecated_use, &[name, replacement]),
+    }
+}
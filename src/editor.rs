@@ -0,0 +1,65 @@
+//! Editing the line in an external editor, see
+//! [Repl::with_external_editor](crate::Repl::with_external_editor)
+//!
+//! reedline keybindings are single keys, so [ExternalEditorEditMode] waits for the key
+//! after Ctrl-X itself and opens the editor on Ctrl-E, like bash does.
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use reedline::{EditMode, PromptEditMode, ReedlineEvent};
+
+/// Extension of the temporary file the line is edited in
+pub(crate) const EXTENSION: &str = "txt";
+
+/// The editor set in `VISUAL` or `EDITOR`, if any
+pub(crate) fn from_env() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+}
+
+/// Edit mode opening the external editor on Ctrl-X Ctrl-E
+pub(crate) struct ExternalEditorEditMode {
+    inner: Box<dyn EditMode>,
+    submit: bool,
+    // Ctrl-X has been pressed, the next key completes the sequence
+    pending: bool,
+}
+
+impl ExternalEditorEditMode {
+    pub(crate) fn new(inner: Box<dyn EditMode>, submit: bool) -> Self {
+        Self {
+            inner,
+            submit,
+            pending: false,
+        }
+    }
+}
+
+impl EditMode for ExternalEditorEditMode {
+    fn parse_event(&mut self, event: Event) -> ReedlineEvent {
+        let key = match &event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::CONTROL,
+            }) => Some(c.to_ascii_lowercase()),
+            _ => None,
+        };
+        match (std::mem::take(&mut self.pending), key) {
+            (true, Some('e')) if self.submit => {
+                // Enter keeps the line open if it is incomplete
+                ReedlineEvent::Multiple(vec![ReedlineEvent::OpenEditor, ReedlineEvent::Enter])
+            }
+            (true, Some('e')) => ReedlineEvent::OpenEditor,
+            (false, Some('x')) => {
+                self.pending = true;
+                ReedlineEvent::None
+            }
+            _ => self.inner.parse_event(event),
+        }
+    }
+
+    fn edit_mode(&self) -> PromptEditMode {
+        self.inner.edit_mode()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::theme::Theme;
 use std::convert::From;
 use std::fmt;
 use std::num;
@@ -17,6 +18,18 @@ pub enum Error {
     /// A required argument is missing
     MissingRequiredArgument(String, String),
 
+    /// No completion script can be generated for the shell
+    UnknownShell(String),
+
+    /// The arguments of a command couldn't be parsed (command, message)
+    InvalidArguments(String, String),
+
+    /// An argument has no value or isn't defined by the command
+    MissingArgument(String),
+
+    /// An argument value couldn't be parsed (argument, value, message)
+    InvalidArgument(String, String, String),
+
     /// Too many arguments were provided
     TooManyArguments(String, usize),
 
@@ -29,8 +42,92 @@ pub enum Error {
     /// Error parsing a float value
     ParseFloatError(num::ParseFloatError),
 
-    /// Command not found
-    UnknownCommand(String),
+    /// Command not found (command, message)
+    UnknownCommand(String, String),
+
+    /// Command not found, but a similar one exists (command, suggestion, message)
+    UnknownCommandDidYouMean(String, String, String),
+
+    /// A command from a script file failed (file, line number, message)
+    ScriptError(String, usize, String),
+
+    /// Reading or writing a file failed (path, message)
+    IoError(String, String),
+
+    /// A file sources itself, directly or through other files
+    RecursiveSource(String),
+
+    /// Alias not found
+    UnknownAlias(String),
+
+    /// There is no setting with this name
+    UnknownSetting(String),
+
+    /// The value isn't valid for the setting (setting, value)
+    InvalidSetting(String, String),
+
+    /// Evaluating code with the embedded scripting engine failed
+    EvalError(String),
+
+    /// A command callback panicked (command, panic message)
+    CommandPanicked(String, String),
+
+    /// Reading a line from the terminal failed
+    ReadlineError(String),
+
+    /// The history file couldn't be loaded (path, message)
+    HistoryError(String, String),
+
+    /// Rendering the help failed
+    HelpError(String),
+
+    /// The matches of a derived command couldn't be turned into its type (command, message)
+    DerivedCommandError(String, String),
+
+    /// A plugin library couldn't be loaded (path, message)
+    PluginError(String, String),
+
+    /// A command of a WASM plugin failed (command, message)
+    PluginCommandError(String, String),
+
+    /// The configuration file couldn't be read or is invalid (path, message)
+    ConfigError(String, String),
+
+    /// The Context couldn't be saved or restored (path, message)
+    PersistenceError(String, String),
+
+    /// There is no checkpoint with this name
+    UnknownCheckpoint(String),
+
+    /// No command has been executed since the Repl started or the last undo
+    NothingToUndo,
+
+    /// The session lacks a capability the command requires (command, capability)
+    PermissionDenied(String, String),
+
+    /// The command has been executed too often recently (command, time until it may run)
+    RateLimited(String, std::time::Duration),
+
+    /// The system clipboard couldn't be read or written
+    ClipboardError(String),
+
+    /// More than one command has been registered with this name
+    DuplicateCommand(String),
+
+    /// A command has been registered with the name of a built-in command
+    ReservedCommandName(String),
+
+    /// The clap definition of a command is invalid (command, message)
+    InvalidCommandDefinition(String, String),
+
+    /// An init command failed (position starting at 1, command, message)
+    InitCommandError(usize, String, String),
+
+    /// There is no session with this name
+    UnknownSession(String),
+
+    /// A session with this name already exists
+    DuplicateSession(String),
 }
 
 impl std::error::Error for Error {}
@@ -49,6 +146,20 @@ impl fmt::Display for Error {
                 "Error: Missing required argument '{}' for command '{}'",
                 parameter, command
             ),
+            Error::UnknownShell(shell) => write!(
+                f,
+                "Error: Unknown shell '{}', expected bash, elvish, fish, powershell or zsh",
+                shell
+            ),
+            Error::InvalidArguments(_, message) => write!(f, "Error: {}", message),
+            Error::MissingArgument(parameter) => {
+                write!(f, "Error: Missing value for argument '{}'", parameter)
+            }
+            Error::InvalidArgument(parameter, value, message) => write!(
+                f,
+                "Error: Invalid value '{}' for argument '{}': {}",
+                value, parameter, message
+            ),
             Error::TooManyArguments(command, nargs) => write!(
                 f,
                 "Error: Command '{}' can have no more than {} arguments",
@@ -57,11 +168,120 @@ impl fmt::Display for Error {
             Error::ParseBoolError(error) => write!(f, "Error: {}", error,),
             Error::ParseFloatError(error) => write!(f, "Error: {}", error,),
             Error::ParseIntError(error) => write!(f, "Error: {}", error,),
-            Error::UnknownCommand(command) => write!(f, "Error: Unknown command '{}'", command),
+            Error::UnknownCommand(_, message) => write!(f, "Error: {}", message),
+            Error::UnknownCommandDidYouMean(_, _, message) => write!(f, "Error: {}", message),
+            Error::ScriptError(file, line, message) => {
+                write!(f, "{}:{}: {}", file, line, message)
+            }
+            Error::IoError(path, message) => write!(f, "Error: '{}': {}", path, message),
+            Error::RecursiveSource(path) => {
+                write!(f, "Error: '{}' is already being sourced", path)
+            }
+            Error::UnknownAlias(name) => write!(f, "Error: Unknown alias '{}'", name),
+            Error::UnknownSetting(name) => write!(f, "Error: Unknown setting '{}'", name),
+            Error::InvalidSetting(name, value) => {
+                write!(f, "Error: Invalid value '{}' for setting '{}'", value, name)
+            }
+            Error::EvalError(message) => write!(f, "Error: {}", message),
+            Error::CommandPanicked(command, message) => {
+                write!(f, "Error: Command '{}' panicked: {}", command, message)
+            }
+            Error::ReadlineError(message) => write!(f, "Error: Failed to read line: {}", message),
+            Error::HistoryError(path, message) => {
+                write!(f, "Error: Failed to load history '{}': {}", path, message)
+            }
+            Error::HelpError(message) => write!(f, "Error: Failed to render help: {}", message),
+            Error::PluginError(path, message) => {
+                write!(f, "Error: Failed to load plugin '{}': {}", path, message)
+            }
+            Error::PluginCommandError(command, message) => {
+                write!(f, "Error: Plugin command '{}' failed: {}", command, message)
+            }
+            Error::ConfigError(path, message) => {
+                write!(f, "Error: Invalid configuration '{}': {}", path, message)
+            }
+            Error::UnknownCheckpoint(name) => write!(f, "Error: Unknown checkpoint '{}'", name),
+            Error::UnknownSession(name) => write!(f, "Error: Unknown session '{}'", name),
+            Error::DuplicateSession(name) => {
+                write!(f, "Error: Session '{}' already exists", name)
+            }
+            Error::NothingToUndo => write!(f, "Error: Nothing to undo"),
+            Error::PermissionDenied(command, capability) => write!(
+                f,
+                "Error: Permission denied, command '{}' requires '{}'",
+                command, capability
+            ),
+            Error::RateLimited(command, wait) => write!(
+                f,
+                "Error: Command '{}' is rate limited, try again in {}s",
+                command,
+                // round up, waiting 0s wouldn't help
+                wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+            ),
+            Error::ClipboardError(message) => write!(f, "Error: Clipboard: {}", message),
+            Error::DuplicateCommand(name) => {
+                write!(f, "Error: Command '{}' is registered more than once", name)
+            }
+            Error::ReservedCommandName(name) => {
+                write!(
+                    f,
+                    "Error: Command name '{}' is reserved for a built-in",
+                    name
+                )
+            }
+            Error::InvalidCommandDefinition(name, message) => {
+                write!(
+                    f,
+                    "Error: Invalid definition of command '{}': {}",
+                    name, message
+                )
+            }
+            Error::PersistenceError(path, message) => {
+                write!(
+                    f,
+                    "Error: Persisting the context in '{}' failed: {}",
+                    path, message
+                )
+            }
+            Error::DerivedCommandError(command, message) => {
+                write!(f, "Error: Invalid arguments for '{}': {}", command, message)
+            }
+            Error::InitCommandError(index, command, message) => {
+                write!(
+                    f,
+                    "Error: Init command #{} '{}' failed: {}",
+                    index, command, message
+                )
+            }
         }
     }
 }
 
+/// Renders an error followed by its chain of `source()` errors, each cause indented one
+/// level deeper than the error it caused. The prefixes are colored by the theme unless
+/// yansi painting has been disabled with `Paint::disable()`
+pub(crate) fn render_chain(error: &dyn std::error::Error, theme: &Theme) -> String {
+    let message = error.to_string();
+    let mut rendered = format!(
+        "{} {}",
+        theme.error.paint("error:"),
+        message.strip_prefix("Error: ").unwrap_or(&message)
+    );
+    let mut source = error.source();
+    let mut depth = 1;
+    while let Some(cause) = source {
+        rendered.push_str(&format!(
+            "\n{}{} {}",
+            "  ".repeat(depth),
+            theme.error_cause.paint("caused by:"),
+            cause
+        ));
+        source = cause.source();
+        depth += 1;
+    }
+    rendered
+}
+
 impl From<num::ParseIntError> for Error {
     fn from(error: num::ParseIntError) -> Self {
         Error::ParseIntError(error)
@@ -0,0 +1,126 @@
+use crate::error::Error;
+use clap::Command;
+use std::collections::HashSet;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+/// Restores the terminal when dropped, including while unwinding from a panic, so it
+/// isn't left in raw mode with a hidden cursor
+pub(crate) struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::cursor::Show);
+        crate::repl::disable_virtual_terminal_processing();
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Checks a command definition for arguments clashing with each other, including the
+/// global arguments and the `--help` and `--version` flags clap adds: an id, long flag or
+/// short flag defined twice. In debug builds the assertions of clap run as well, they
+/// catch more mistakes but are compiled out of release builds
+pub(crate) fn check_definition(command: &Command) -> Result<(), String> {
+    let mut command = command.clone();
+    // building runs the assertions of clap in debug builds
+    catch_silently(|| command.build()).map_err(|payload| panic_message(payload.as_ref()))?;
+    check_arguments(&command)
+}
+
+fn check_arguments(command: &Command) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    let mut longs = HashSet::new();
+    let mut shorts = HashSet::new();
+    for arg in command.get_arguments() {
+        if !ids.insert(arg.get_id().as_str()) {
+            return Err(format!("argument '{}' is defined twice", arg.get_id()));
+        }
+        let aliases = arg.get_all_aliases().unwrap_or_default();
+        for long in arg.get_long().into_iter().chain(aliases) {
+            if !longs.insert(long) {
+                return Err(format!("flag '--{}' is defined twice", long));
+            }
+        }
+        let short_aliases = arg.get_all_short_aliases().unwrap_or_default();
+        for short in arg.get_short().into_iter().chain(short_aliases) {
+            if !shorts.insert(short) {
+                return Err(format!("flag '-{}' is defined twice", short));
+            }
+        }
+    }
+    command.get_subcommands().try_for_each(check_arguments)
+}
+
+/// Runs `f` with a panic hook printing nothing, for panics which are expected and reported
+/// otherwise. The hook is process wide, so panics of other threads meanwhile are silent too
+fn catch_silently<T>(f: impl FnOnce() -> T) -> std::thread::Result<T> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result
+}
+
+fn panic_error<E: From<Error>>(command: &str, payload: &(dyn std::any::Any + Send)) -> E {
+    Error::CommandPanicked(command.to_string(), panic_message(payload)).into()
+}
+
+/// Calls a command callback, turning a panic into an error if enabled
+pub(crate) fn catch_panic<T, E: From<Error>>(
+    enabled: bool,
+    command: &str,
+    callback: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    if !enabled {
+        return callback();
+    }
+    panic::catch_unwind(AssertUnwindSafe(callback))
+        .unwrap_or_else(|payload| Err(panic_error(command, payload.as_ref())))
+}
+
+/// Awaits an async command callback, turning a panic into an error if enabled
+#[cfg(feature = "async")]
+pub(crate) async fn catch_panic_async<T, E: From<Error>>(
+    enabled: bool,
+    command: &str,
+    future: Pin<Box<dyn Future<Output = Result<T, E>> + '_>>,
+) -> Result<T, E> {
+    if !enabled {
+        return future.await;
+    }
+    CatchUnwind(future)
+        .await
+        .unwrap_or_else(|payload| Err(panic_error(command, payload.as_ref())))
+}
+
+#[cfg(feature = "async")]
+struct CatchUnwind<'a, T>(Pin<Box<dyn Future<Output = T> + 'a>>);
+
+#[cfg(feature = "async")]
+impl<'a, T> Future for CatchUnwind<'a, T> {
+    type Output = std::thread::Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::console::ConsoleMode;
+use crate::error::Error;
+use crate::notify::{Notifier, NotifyLevel};
+use crate::signal::Interrupts;
+use crate::theme::Theme;
+use crate::Repl;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// Gives command callbacks registered with
+/// [with_handle_command](crate::Repl::with_handle_command) control over the running Repl
+#[derive(Debug, Default)]
+pub struct ReplHandle {
+    quit: bool,
+    exit_code: i32,
+    prompt: Option<String>,
+    settings: Vec<(String, bool)>,
+    queue: VecDeque<String>,
+    parent: Option<ParentRepl>,
+    notifier: Notifier,
+}
+
+/// What a nested Repl inherits from the Repl running the command, see
+/// [ReplHandle::nested]
+#[derive(Debug, Clone)]
+pub(crate) struct ParentRepl {
+    pub(crate) name: String,
+    pub(crate) history: Option<(PathBuf, usize)>,
+    pub(crate) theme: Theme,
+    pub(crate) console_mode: ConsoleMode,
+}
+
+impl ReplHandle {
+    /// Stop the Repl once the current command has finished. Remaining commands of a
+    /// script or piped input are not executed anymore
+    pub fn quit(&mut self) {
+        self.quit = true;
+    }
+
+    /// Stop the Repl like [quit](ReplHandle::quit) and set the exit code returned by
+    /// [run_with_exit_code](crate::Repl::run_with_exit_code)
+    pub fn exit(&mut self, exit_code: i32) {
+        self.exit_code = exit_code;
+        self.quit();
+    }
+
+    /// Set the exit code without stopping the Repl
+    pub fn set_exit_code(&mut self, exit_code: i32) {
+        self.exit_code = exit_code;
+    }
+
+    /// Returns true if [quit](ReplHandle::quit) has been called
+    pub fn is_quitting(&self) -> bool {
+        self.quit
+    }
+
+    /// The exit code set so far, 0 per default
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// Replace the prompt, starting with the next one shown after the current command
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = Some(prompt.to_string());
+    }
+
+    /// Turn a setting of the session on or off once the current command has finished,
+    /// like the `set` built-in command does. The settings are listed by `show options`,
+    /// e.g. `timing`, `hints` or `quick-completions`
+    pub fn set(&mut self, setting: &str, enabled: bool) {
+        self.settings.push((setting.to_string(), enabled));
+    }
+
+    /// Execute `line` right after the current command, e.g. `handle.enqueue("refresh")`.
+    /// Lines run in the order they were enqueued, the remaining ones are dropped when one
+    /// of them fails or a command quits the Repl
+    pub fn enqueue(&mut self, line: &str) {
+        self.queue.push_back(line.to_string());
+    }
+
+    /// Print `text` as a colored one-line notification above the prompt, see
+    /// [Notifier::notify]
+    pub fn notify(&self, level: NotifyLevel, text: &str) {
+        self.notifier.notify(level, text);
+    }
+
+    /// A [Notifier] which can be moved to background tasks, e.g. a thread watching a
+    /// long-running job, to notify the Repl while it waits for input
+    pub fn notifier(&self) -> Notifier {
+        self.notifier.clone()
+    }
+
+    /// Create a Repl to [run](Repl::run) from within the current command, e.g. a `debug`
+    /// command opening a sub-console with a command set of its own. The nested Repl is
+    /// named `<parent>/<name>`, uses the theme and console mode of the running Repl and
+    /// keeps its history next to the parent's, in a file with `.<name>` appended. Once it
+    /// quits, `run` returns and the command finishes, so the parent Repl prompts again
+    pub fn nested<Context, E>(&self, name: &str, context: Context) -> Repl<Context, E>
+    where
+        E: Display + From<Error> + std::fmt::Debug,
+    {
+        let repl = Repl::new(context);
+        let parent = match &self.parent {
+            Some(parent) => parent,
+            None => return repl.with_name(name),
+        };
+        let repl = repl
+            .with_name(&format!("{}/{}", parent.name, name))
+            .with_theme(parent.theme.clone())
+            .with_console_mode(parent.console_mode);
+        match &parent.history {
+            Some((path, capacity)) => {
+                let mut file = path.clone().into_os_string();
+                file.push(format!(".{}", name));
+                repl.with_history(PathBuf::from(file), *capacity)
+            }
+            None => repl,
+        }
+    }
+
+    pub(crate) fn set_parent(&mut self, parent: ParentRepl) {
+        self.parent = Some(parent);
+    }
+
+    pub(crate) fn dequeue(&mut self) -> Option<String> {
+        self.queue.pop_front()
+    }
+
+    pub(crate) fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    pub(crate) fn interrupts(&self) -> &Interrupts {
+        self.notifier.interrupts()
+    }
+
+    pub(crate) fn take_notifications(&self) -> Vec<(NotifyLevel, String)> {
+        self.notifier.take()
+    }
+
+    pub(crate) fn take_prompt(&mut self) -> Option<String> {
+        self.prompt.take()
+    }
+
+    pub(crate) fn take_settings(&mut self) -> Vec<(String, bool)> {
+        std::mem::take(&mut self.settings)
+    }
+
+    pub(crate) fn cancel_quit(&mut self) {
+        self.quit = false;
+    }
+
+    /// Marks the session as failed unless an exit code has been set explicitly
+    pub(crate) fn fail(&mut self) {
+        if self.exit_code == 0 {
+            self.exit_code = 1;
+        }
+    }
+}
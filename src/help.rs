@@ -0,0 +1,286 @@
+//! Rendering of the `help` overview and command help
+
+use crate::messages::Messages;
+use crate::theme::Theme;
+use crate::width::{display_width, pad};
+use clap::Command;
+use std::collections::BTreeMap;
+use yansi::Style;
+
+/// Narrowest column for command descriptions, even on very small terminals
+const MIN_ABOUT_WIDTH: usize = 20;
+
+/// Sections of the help overview, rendered in the order given to
+/// [HelpTemplate::with_sections]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpSection {
+    /// Name, version and description of the Repl
+    Header,
+    /// The commands added to the Repl. Built-in commands are listed here as well unless
+    /// the template contains [HelpSection::Builtins]
+    Commands,
+    /// The enabled built-in commands
+    Builtins,
+    /// The help topics added with [Repl::with_help_topic](crate::Repl::with_help_topic),
+    /// omitted if there are none
+    Topics,
+}
+
+/// What the help overview lists
+pub(crate) struct Overview<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) version: &'a str,
+    pub(crate) description: &'a str,
+    pub(crate) commands: &'a [&'a Command],
+    pub(crate) builtins: &'a [&'a Command],
+    pub(crate) topics: &'a BTreeMap<String, String>,
+    /// Names of the listed commands which are deprecated
+    pub(crate) deprecated: &'a [&'a str],
+}
+
+/// Layout and colors of the help output
+#[derive(Debug, Clone)]
+pub struct HelpTemplate {
+    sections: Vec<HelpSection>,
+    name_style: Style,
+    heading_style: Style,
+    command_style: Style,
+    deprecated_style: Style,
+    column_width: Option<usize>,
+}
+
+impl Default for HelpTemplate {
+    fn default() -> Self {
+        let theme = Theme::default();
+        Self {
+            sections: vec![
+                HelpSection::Header,
+                HelpSection::Commands,
+                HelpSection::Topics,
+            ],
+            name_style: theme.help_name,
+            heading_style: theme.help_heading,
+            command_style: theme.help_command,
+            deprecated_style: theme.help_deprecated,
+            column_width: None,
+        }
+    }
+}
+
+impl HelpTemplate {
+    /// Set the sections of the help overview and their order
+    pub fn with_sections(mut self, sections: &[HelpSection]) -> Self {
+        self.sections = sections.to_vec();
+        self
+    }
+
+    /// Set the style of the Repl name in the header
+    pub fn with_name_style(mut self, style: Style) -> Self {
+        self.name_style = style;
+        self
+    }
+
+    /// Set the style of section headings like `COMMANDS:` and `EXAMPLES:`
+    pub fn with_heading_style(mut self, style: Style) -> Self {
+        self.heading_style = style;
+        self
+    }
+
+    /// Set the style of command names in the overview
+    pub fn with_command_style(mut self, style: Style) -> Self {
+        self.command_style = style;
+        self
+    }
+
+    /// Set the style of deprecated commands in the overview
+    pub fn with_deprecated_style(mut self, style: Style) -> Self {
+        self.deprecated_style = style;
+        self
+    }
+
+    /// Set the width of the command name column, per default the longest name is used
+    pub fn with_column_width(mut self, width: usize) -> Self {
+        self.column_width = Some(width);
+        self
+    }
+
+    pub(crate) fn render_overview(
+        &self,
+        overview: &Overview,
+        messages: &Messages,
+        term_width: Option<usize>,
+    ) -> String {
+        let Overview {
+            name,
+            version,
+            description,
+            commands,
+            builtins,
+            topics,
+            deprecated,
+        } = *overview;
+        let separate_builtins = self.sections.contains(&HelpSection::Builtins);
+        let help = Command::new("help").about(messages.help_about.clone());
+        let mut listed: Vec<&Command> = commands.to_vec();
+        let mut builtins: Vec<&Command> = builtins.to_vec();
+        builtins.push(&help);
+        if !separate_builtins {
+            listed.append(&mut builtins);
+        }
+        listed.sort_by_key(|command| command.get_name());
+        builtins.sort_by_key(|command| command.get_name());
+        // topics are listed like commands, with the first line of their text as summary
+        let topics: Vec<Command> = topics
+            .iter()
+            .map(|(topic, text)| {
+                Command::new(topic.clone())
+                    .about(text.lines().next().unwrap_or_default().to_string())
+            })
+            .collect();
+        let topics: Vec<&Command> = topics.iter().collect();
+        let width = self.column_width.unwrap_or_else(|| {
+            listed
+                .iter()
+                .chain(builtins.iter())
+                .chain(topics.iter())
+                .map(|command| display_width(command.get_name()))
+                .max()
+                .unwrap_or_default()
+        });
+
+        let mut sections = vec![];
+        for section in &self.sections {
+            match section {
+                HelpSection::Header => sections.push(format!(
+                    "{} {}\n{}\n",
+                    self.name_style.paint(name),
+                    version,
+                    description
+                )),
+                HelpSection::Commands => sections.push(self.render_list(
+                    &messages.commands_heading,
+                    &listed,
+                    deprecated,
+                    messages,
+                    width,
+                    term_width,
+                )),
+                HelpSection::Builtins => sections.push(self.render_list(
+                    &messages.builtins_heading,
+                    &builtins,
+                    deprecated,
+                    messages,
+                    width,
+                    term_width,
+                )),
+                HelpSection::Topics if !topics.is_empty() => sections.push(self.render_list(
+                    &messages.topics_heading,
+                    &topics,
+                    &[],
+                    messages,
+                    width,
+                    term_width,
+                )),
+                HelpSection::Topics => {}
+            }
+        }
+        sections.join("\n")
+    }
+
+    fn render_list(
+        &self,
+        heading: &str,
+        commands: &[&Command],
+        deprecated: &[&str],
+        messages: &Messages,
+        width: usize,
+        term_width: Option<usize>,
+    ) -> String {
+        let mut list = format!("{}\n", self.heading_style.paint(heading));
+        // names are indented by 2 and followed by 2 spaces
+        let indent = " ".repeat(width + 4);
+        let about_width = term_width
+            .map(|term_width| term_width.saturating_sub(width + 4).max(MIN_ABOUT_WIDTH))
+            .unwrap_or(usize::MAX);
+        for command in commands {
+            // deprecated commands are dimmed and tagged
+            let deprecated = deprecated.contains(&command.get_name());
+            let (style, about) = match (command.get_about(), deprecated) {
+                (about, true) => (
+                    self.deprecated_style,
+                    Some(match about {
+                        Some(about) => format!("{} {}", about, messages.deprecated_tag),
+                        None => messages.deprecated_tag.clone(),
+                    }),
+                ),
+                (about, false) => (self.command_style, about.map(|about| about.to_string())),
+            };
+            match about {
+                Some(about) => list.push_str(&format!(
+                    "  {}  {}\n",
+                    style.paint(pad(command.get_name(), width)),
+                    wrap(&about, about_width)
+                        .iter()
+                        .map(|line| match deprecated {
+                            true => style.paint(line).to_string(),
+                            false => line.clone(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(&format!("\n{}", indent))
+                )),
+                None => list.push_str(&format!("  {}\n", style.paint(command.get_name()))),
+            }
+        }
+        list
+    }
+
+    /// Render the clap help of a command, with its `after_help` shown as EXAMPLES section
+    pub(crate) fn render_command(
+        &self,
+        command: &Command,
+        messages: &Messages,
+        term_width: Option<usize>,
+    ) -> std::io::Result<String> {
+        let examples = command
+            .get_after_help()
+            .map(|examples| examples.to_string());
+        let mut command = command.clone().after_help(None::<&'static str>);
+        if let Some(term_width) = term_width {
+            command = command.term_width(term_width);
+        }
+        let mut help_bytes: Vec<u8> = Vec::new();
+        command.write_help(&mut help_bytes)?;
+        let mut help = String::from_utf8_lossy(&help_bytes).into_owned();
+        if let Some(examples) = examples {
+            help = format!(
+                "{}\n{}\n{}\n",
+                help.trim_end(),
+                self.heading_style.paint(&messages.examples_heading),
+                examples
+                    .lines()
+                    .map(|line| format!("  {}", line).trim_end().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        Ok(help)
+    }
+}
+
+/// Wrap text at whitespace into lines of at most `width` characters, longer words are
+/// kept on a line of their own
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && display_width(&line) + 1 + display_width(word) > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
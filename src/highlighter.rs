@@ -0,0 +1,169 @@
+//! Syntax highlighting of the input line
+
+use crate::command::CommandIndex;
+use clap::Command;
+use nu_ansi_term::{Color, Style};
+use reedline::{Highlighter, StyledText};
+use std::sync::{Arc, Mutex};
+
+/// Colors of the built-in highlighter, part of the [Theme](crate::Theme) and set with
+/// [Repl::with_highlighter_colors](crate::Repl::with_highlighter_colors)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighlightColors {
+    /// A known command
+    pub command: Color,
+    /// A first word which isn't a command
+    pub unknown_command: Color,
+    /// Flags and options of the command like `-v` and `--verbose`
+    pub flag: Color,
+    /// Flags the command doesn't have
+    pub invalid_flag: Color,
+    /// Values which are possible values of an argument of the command
+    pub value: Color,
+    /// Quoted strings
+    pub string: Color,
+    /// Other arguments
+    pub argument: Color,
+}
+
+impl Default for HighlightColors {
+    fn default() -> Self {
+        Self {
+            command: Color::Green,
+            unknown_command: Color::Red,
+            flag: Color::Cyan,
+            invalid_flag: Color::Red,
+            value: Color::Purple,
+            string: Color::Yellow,
+            argument: Color::White,
+        }
+    }
+}
+
+/// Highlighter coloring the command and its arguments according to the clap definition:
+/// flags the command has and values from its `possible_values`, or invalid flags
+pub(crate) struct ReplHighlighter {
+    commands: CommandIndex,
+    colors: HighlightColors,
+}
+
+impl ReplHighlighter {
+    pub(crate) fn new(commands: CommandIndex, colors: HighlightColors) -> Self {
+        Self { commands, colors }
+    }
+
+    fn argument_color(&self, command: Option<&Command>, word: &str) -> Color {
+        if word.starts_with('"') || word.starts_with('\'') {
+            return self.colors.string;
+        }
+        let command = match command {
+            Some(command) => command,
+            None => return self.colors.argument,
+        };
+        if word == "-" || word == "--" || !word.starts_with('-') {
+            let possible = command.get_arguments().any(|arg| {
+                arg.get_possible_values()
+                    .iter()
+                    .any(|value| value.matches(word, false))
+            });
+            return if possible {
+                self.colors.value
+            } else {
+                self.colors.argument
+            };
+        }
+        let known = match word.strip_prefix("--") {
+            Some(long) => {
+                let long = long.split('=').next().unwrap_or_default();
+                long == "help"
+                    || command
+                        .get_arguments()
+                        .any(|arg| arg.get_long() == Some(long))
+            }
+            // combined short flags like -la are known if every flag is
+            None => word[1..].chars().all(|short| {
+                short == 'h'
+                    || command
+                        .get_arguments()
+                        .any(|arg| arg.get_short() == Some(short))
+            }),
+        };
+        if known {
+            self.colors.flag
+        } else {
+            self.colors.invalid_flag
+        }
+    }
+}
+
+impl Highlighter for ReplHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+        let mut styled_text = StyledText::new();
+        let commands = self.commands.read();
+        let mut command: Option<Option<&Command>> = None;
+        for (word, is_space) in split_words(line) {
+            let color = match command {
+                _ if is_space => {
+                    styled_text.push((Style::new(), word.to_string()));
+                    continue;
+                }
+                None => {
+                    let found = commands.get(word).map(Arc::as_ref);
+                    command = Some(found);
+                    if found.is_some() || word == "help" {
+                        self.colors.command
+                    } else {
+                        self.colors.unknown_command
+                    }
+                }
+                Some(command) => self.argument_color(command, word),
+            };
+            styled_text.push((Style::new().fg(color), word.to_string()));
+        }
+        styled_text
+    }
+}
+
+/// Split a line into words and the whitespace between them, keeping quoted strings with
+/// whitespace in one word. Unterminated quotes extend to the end of the line
+pub(crate) fn split_words(line: &str) -> Vec<(&str, bool)> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quote = None;
+    let mut in_space = None;
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => continue,
+            None => {}
+        }
+        let is_space = c.is_whitespace() && quote.is_none();
+        if in_space != Some(is_space) {
+            if index > start {
+                parts.push((&line[start..index], in_space.unwrap_or(false)));
+            }
+            start = index;
+            in_space = Some(is_space);
+        }
+        if !is_space && (c == '"' || c == '\'') && (index == start) {
+            quote = Some(c);
+        }
+    }
+    if start < line.len() {
+        parts.push((&line[start..], in_space.unwrap_or(false)));
+    }
+    parts
+}
+
+/// A highlighter shared by the line editors of all sessions of a Repl
+#[derive(Clone)]
+pub(crate) struct SharedHighlighter(pub(crate) Arc<Mutex<Box<dyn Highlighter>>>);
+
+impl Highlighter for SharedHighlighter {
+    fn highlight(&self, line: &str, cursor: usize) -> StyledText {
+        self.0
+            .lock()
+            .expect("highlighter lock poisoned")
+            .highlight(line, cursor)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::command::CommandIndex;
+use clap::Command;
+use nu_ansi_term::Style;
+use reedline::{DefaultHinter, Hinter, History};
+
+/// Hints from the history like the default hinter, otherwise the rest of a command example
+/// the line is the start of, otherwise a placeholder for the next argument of the command
+pub(crate) struct ReplHinter {
+    commands: CommandIndex,
+    examples: Vec<String>,
+    style: Style,
+    history: DefaultHinter,
+    current_hint: Option<String>,
+}
+
+impl ReplHinter {
+    pub(crate) fn new(commands: CommandIndex, examples: Vec<String>, style: Style) -> Self {
+        Self {
+            commands,
+            examples,
+            style,
+            history: DefaultHinter::default().with_style(style),
+            current_hint: None,
+        }
+    }
+
+    /// Placeholder for the next positional argument after a command name and a space,
+    /// e.g. `<who>`, or `[who]` if it is optional
+    fn placeholder(&self, line: &str) -> Option<String> {
+        if !line.ends_with(' ') {
+            return None;
+        }
+        let mut words = line.split_whitespace();
+        let commands = self.commands.read();
+        let command = commands.get(words.next()?)?;
+        let given = count_positionals(command, words);
+        let arg = command
+            .get_positionals()
+            .filter(|arg| !arg.is_hide_set())
+            .nth(given)?;
+        let name = arg
+            .get_value_names()
+            .and_then(|names| names.first())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| arg.get_id().to_string());
+        Some(match arg.is_required_set() {
+            true => format!("<{}>", name),
+            false => format!("[{}]", name),
+        })
+    }
+}
+
+/// Number of positional arguments among `words`, skipping flags and their values
+fn count_positionals<'a>(command: &Command, words: impl Iterator<Item = &'a str>) -> usize {
+    let mut count = 0;
+    let mut skip_value = false;
+    for word in words {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        if word.starts_with('-') && word.len() > 1 {
+            // a value of its own follows flags like `--port 80` and `-p 80`
+            let takes_value = command
+                .get_arguments()
+                .filter(|arg| arg.get_action().takes_values())
+                .any(|arg| {
+                    word.strip_prefix("--") == arg.get_long()
+                        || (word.len() == 2 && word.chars().nth(1) == arg.get_short())
+                });
+            skip_value = takes_value;
+            continue;
+        }
+        count += 1;
+    }
+    count
+}
+
+impl Hinter for ReplHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        pos: usize,
+        history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        let history_hint = self.history.handle(line, pos, history, use_ansi_coloring);
+        self.current_hint = None;
+        if !history_hint.is_empty() || line.trim().is_empty() {
+            return history_hint;
+        }
+        self.current_hint = self
+            .examples
+            .iter()
+            .find(|example| example.len() > line.len() && example.starts_with(line))
+            .map(|example| example[line.len()..].to_string());
+        match &self.current_hint {
+            Some(hint) if use_ansi_coloring => self.style.paint(hint).to_string(),
+            Some(hint) => hint.clone(),
+            // placeholders are only shown, accepting the hint doesn't insert them
+            None => match self.placeholder(line) {
+                Some(placeholder) if use_ansi_coloring => {
+                    self.style.dimmed().paint(placeholder).to_string()
+                }
+                Some(placeholder) => placeholder,
+                None => history_hint,
+            },
+        }
+    }
+
+    fn complete_hint(&self) -> String {
+        match &self.current_hint {
+            Some(hint) => hint.clone(),
+            None => self.history.complete_hint(),
+        }
+    }
+
+    fn next_hint_token(&self) -> String {
+        let hint = match &self.current_hint {
+            Some(hint) => hint,
+            None => return self.history.next_hint_token(),
+        };
+        // leading whitespace and the first word, like the default hinter
+        let start = hint.len() - hint.trim_start().len();
+        let end = hint[start..]
+            .find(char::is_whitespace)
+            .map_or(hint.len(), |end| start + end);
+        hint[..end].to_string()
+    }
+}
@@ -0,0 +1,141 @@
+//! History loaded in the background, so huge history files don't delay the first prompt
+
+use reedline::{FileBackedHistory, History, HistoryNavigationQuery};
+use std::collections::vec_deque::Iter;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// Where a [LazyHistory] leaves the error of reading the file, for the Repl to report it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoadFailure(Arc<Mutex<Option<std::io::Error>>>);
+
+impl LoadFailure {
+    pub(crate) fn take(&self) -> Option<std::io::Error> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+
+    fn set(&self, err: std::io::Error) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(err);
+    }
+}
+
+/// A [FileBackedHistory] read by a background thread. Until it has been read, lines are
+/// kept in memory and added to the file history once it is available. Browsing or saving
+/// the history waits for the file to be read. If reading fails, the error is left in the
+/// [LoadFailure] and the history stays in memory
+pub(crate) struct LazyHistory {
+    history: FileBackedHistory,
+    loading: Option<Receiver<std::io::Result<FileBackedHistory>>>,
+    // lines entered before the file history is available
+    pending: Vec<String>,
+    failure: LoadFailure,
+}
+
+impl LazyHistory {
+    pub(crate) fn new(capacity: usize, path: PathBuf, failure: LoadFailure) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(FileBackedHistory::with_file(capacity, path));
+        });
+        Self {
+            history: FileBackedHistory::new(capacity),
+            loading: Some(receiver),
+            pending: vec![],
+            failure,
+        }
+    }
+
+    /// Switch to the file history if it has been read, waiting for it if `wait` is true
+    fn finish_loading(&mut self, wait: bool) {
+        let receiver = match &self.loading {
+            Some(receiver) => receiver,
+            None => return,
+        };
+        let loaded = if wait {
+            receiver.recv().ok()
+        } else {
+            match receiver.try_recv() {
+                Ok(loaded) => Some(loaded),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => None,
+            }
+        };
+        self.loading = None;
+        match loaded {
+            Some(Ok(mut history)) => {
+                for entry in self.pending.drain(..) {
+                    history.append(&entry);
+                }
+                history.set_navigation(self.history.get_navigation());
+                self.history = history;
+            }
+            Some(Err(err)) => self.failure.set(err),
+            None => {}
+        }
+        self.pending.clear();
+    }
+}
+
+impl History for LazyHistory {
+    fn append(&mut self, entry: &str) {
+        self.finish_loading(false);
+        if self.loading.is_some() {
+            self.pending.push(entry.to_string());
+        }
+        self.history.append(entry);
+    }
+
+    fn iter_chronologic(&self) -> Iter<'_, String> {
+        self.history.iter_chronologic()
+    }
+
+    fn back(&mut self) {
+        self.finish_loading(true);
+        self.history.back();
+    }
+
+    fn forward(&mut self) {
+        self.finish_loading(true);
+        self.history.forward();
+    }
+
+    fn string_at_cursor(&self) -> Option<String> {
+        self.history.string_at_cursor()
+    }
+
+    fn set_navigation(&mut self, navigation: HistoryNavigationQuery) {
+        self.finish_loading(true);
+        self.history.set_navigation(navigation);
+    }
+
+    fn get_navigation(&self) -> HistoryNavigationQuery {
+        self.history.get_navigation()
+    }
+
+    fn query_entries(&self, search: &str) -> Vec<String> {
+        self.history.query_entries(search)
+    }
+
+    fn max_values(&self) -> usize {
+        self.history.max_values()
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.finish_loading(true);
+        self.history.sync()
+    }
+
+    fn reset_cursor(&mut self) {
+        self.finish_loading(false);
+        self.history.reset_cursor();
+    }
+}
+
+impl Drop for LazyHistory {
+    fn drop(&mut self) {
+        // the file history saves the lines entered meanwhile when it is dropped
+        self.finish_loading(true);
+    }
+}
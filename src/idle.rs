@@ -0,0 +1,81 @@
+use crate::signal::{Alarm, Interrupts};
+use crate::LifecycleCallback;
+use std::time::{Duration, Instant};
+
+/// What happens once a REPL has been idle for the duration given to
+/// [with_idle_timeout](crate::Repl::with_idle_timeout)
+pub enum IdleAction<Context, E> {
+    /// End the REPL as if Ctrl-D was pressed
+    Exit,
+    /// Call the callback, e.g. to lock the session until a password has been entered.
+    /// The REPL keeps running, a returned String is printed
+    Callback(LifecycleCallback<Context, E>),
+}
+
+impl<Context, E> Clone for IdleAction<Context, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Context, E> Copy for IdleAction<Context, E> {}
+
+pub(crate) enum IdleEvent {
+    /// The timeout expires after the given duration
+    Warning(Duration),
+    Timeout,
+}
+
+/// Tracks the time since the last input while waiting for a line
+pub(crate) struct IdleTimer {
+    timeout: Duration,
+    warning: Duration,
+    last_activity: Instant,
+    warned: bool,
+}
+
+impl IdleTimer {
+    pub(crate) fn new(timeout: Duration, warning: Duration) -> Self {
+        Self {
+            timeout,
+            warning: warning.min(timeout),
+            last_activity: Instant::now(),
+            warned: false,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.last_activity = Instant::now();
+        self.warned = false;
+    }
+
+    fn warns(&self) -> bool {
+        !self.warned && !self.warning.is_zero()
+    }
+
+    /// Alarm going off at the next warning or timeout
+    pub(crate) fn alarm(&self, interrupts: &Interrupts) -> Alarm {
+        let deadline = if self.warns() {
+            self.last_activity + (self.timeout - self.warning)
+        } else {
+            self.last_activity + self.timeout
+        };
+        Alarm::set(
+            deadline.saturating_duration_since(Instant::now()),
+            interrupts,
+        )
+    }
+
+    pub(crate) fn poll(&mut self) -> Option<IdleEvent> {
+        let elapsed = self.last_activity.elapsed();
+        if elapsed >= self.timeout {
+            self.reset();
+            Some(IdleEvent::Timeout)
+        } else if self.warns() && elapsed >= self.timeout - self.warning {
+            self.warned = true;
+            Some(IdleEvent::Warning(self.timeout - elapsed))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Commands executed before the first prompt, see
+//! [Repl::with_init_commands](crate::Repl::with_init_commands)
+
+use clap::{Arg, ArgAction, ArgMatches};
+
+/// What happens when an init command fails, see
+/// [Repl::with_init_failure_policy](crate::Repl::with_init_failure_policy)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitFailurePolicy {
+    /// Stop, `run()` returns the error
+    Abort,
+    /// Report the error and execute the next init command
+    #[default]
+    Continue,
+    /// Ask the user whether to go on, stopping like [Abort](InitFailurePolicy::Abort) if
+    /// not or if stdin is no terminal
+    PromptUser,
+}
+
+/// The `-e/--eval <COMMAND>` option for the command line of your application, which
+/// [Repl::with_eval_commands](crate::Repl::with_eval_commands) forwards to the init
+/// commands. It may be given several times
+pub fn eval_arg() -> Arg {
+    Arg::new(EVAL_ARG)
+        .short('e')
+        .long("eval")
+        .value_name("COMMAND")
+        .action(ArgAction::Append)
+        .help("Execute a REPL command before the first prompt")
+}
+
+pub(crate) const EVAL_ARG: &str = "eval";
+
+/// The values of [eval_arg] in `matches`
+pub(crate) fn eval_commands(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .try_get_many::<String>(EVAL_ARG)
+        .ok()
+        .flatten()
+        .map_or_else(Vec::new, |values| values.cloned().collect())
+}
+
+/// The commands in the environment variable `name`, separated by `separator`
+pub(crate) fn env_commands(name: &str, separator: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|value| {
+            value
+                .split(separator)
+                .map(str::trim)
+                .filter(|command| !command.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,49 @@
+//! Line delimited JSON requests and responses, to drive a Repl from other programs
+//!
+//! A request names a command and its arguments, the id is optional and returned as is:
+//!
+//! ```text
+//! {"id": 1, "cmd": "add", "args": ["1", 2]}
+//! {"id":1,"ok":true,"output":"3"}
+//! {"id": 2, "cmd": "divide", "args": ["1", "0"]}
+//! {"id":2,"ok":false,"error":"Error: division by zero"}
+//! ```
+use serde_json::{json, Value};
+
+pub(crate) struct Request {
+    pub(crate) id: Value,
+    pub(crate) command: String,
+    pub(crate) args: Vec<String>,
+}
+
+/// Parse a request, on failure returns the id (if any) with the error message
+pub(crate) fn parse_request(line: &str) -> Result<Request, (Value, String)> {
+    let request: Value = serde_json::from_str(line)
+        .map_err(|err| (Value::Null, format!("Error: Invalid request: {}", err)))?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let command = match request.get("cmd").and_then(Value::as_str) {
+        Some(command) => command.to_string(),
+        None => return Err((id, "Error: Missing \"cmd\"".to_string())),
+    };
+    let args = match request.get("args") {
+        None | Some(Value::Null) => vec![],
+        // numbers and booleans are passed the way they're written
+        Some(Value::Array(args)) => args
+            .iter()
+            .map(|arg| match arg {
+                Value::String(arg) => arg.clone(),
+                arg => arg.to_string(),
+            })
+            .collect(),
+        Some(_) => return Err((id, "Error: \"args\" must be an array".to_string())),
+    };
+    Ok(Request { id, command, args })
+}
+
+pub(crate) fn success(id: Value, output: Option<String>) -> String {
+    json!({ "id": id, "ok": true, "output": output }).to_string()
+}
+
+pub(crate) fn failure(id: Value, error: &str) -> String {
+    json!({ "id": id, "ok": false, "error": error }).to_string()
+}
@@ -38,6 +38,7 @@
 //! [Repl::new()](struct.Repl.html#method.new)
 //! - the context is passed to your command callback functions as a mutable reference
 //! - the prompt can be changed after each executed commmand using with_on_after_command as shown
+//! - setup and teardown code can be run with with_on_start and with_on_exit
 //!
 //! # Async Support
 //!
@@ -48,6 +49,32 @@
 //! A few things to note:
 //! - The ugly Pin::Box workaround is required because of unstable rust async Fn's  
 //!
+//! # Derive
+//!
+//! With the `derive` feature the commands can be declared as a clap [Subcommand](clap::Subcommand)
+//! enum, and one function dispatches the parsed values:
+//! ```rust,no_run
+#![cfg_attr(feature = "derive", doc = include_str!("../examples/derive.rs"))]
+//! ```
+//!
+//! # Attribute Macro
+//!
+//! With the `macro` feature the `#[repl_command]` attribute generates the clap Command and
+//! the glue unpacking ArgMatches from a function with typed parameters:
+//! ```rust,no_run
+#![cfg_attr(feature = "macro", doc = include_str!("../examples/repl_command.rs"))]
+//! ```
+//!
+//! # Plugins
+//!
+//! A [ReplPlugin] bundles commands and their completions, so third parties can extend an
+//! application's REPL. Plugins are added with [Repl::with_plugin], or with the `plugins`
+//! feature loaded from dynamic libraries which export them with [declare_plugin].
+//!
+//! With the `wasm` feature, commands can be implemented by WebAssembly modules loaded with
+//! [Repl::load_wasm_plugin]. They run sandboxed and only access the Context through the
+//! values it grants by implementing `WasmCapabilities`.
+//!
 //! # Keybindings
 //!
 //! Per default Emacs-style keybindings are used
@@ -58,7 +85,7 @@
 //! - The ugly Pin::Box workaround is required because of unstable rust async Fn's  
 //!
 //! # Help
-//! reedline-repl-rs automatically builds help commands for your REPL using clap [print_help](https://docs.rs/clap/latest/clap/struct.App.html#method.print_help):
+//! reedline-repl-rs automatically builds help commands for your REPL using clap [print_help](https://docs.rs/clap/latest/clap/struct.Command.html#method.print_help):
 //!
 //! ```bash
 //! % myapp
@@ -66,25 +93,27 @@
 //! MyApp v0.1.0: My very cool app
 //!
 //! COMMANDS:
-//!     append     Append name to end of list
-//!     help       Print this message or the help of the given subcommand(s)
-//!     prepend    Prepend name to front of list
+//!   append   Append name to end of list
+//!   help     Print this message or the help of the given command(s)
+//!   prepend  Prepend name to front of list
 //!
 //! MyApp> 〉help append
-//! append
 //! Append name to end of list
 //!
-//! USAGE:
-//!     append <name>
+//! Usage: append <name>
 //!
-//! ARGS:
-//!     <name>
+//! Arguments:
+//!   <name>
 //!
-//! OPTIONS:
-//!     -h, --help    Print help information
+//! Options:
+//!   -h, --help  Print help
 //! MyApp> 〉
 //! ```
 //!
+//! Sections, colors and column widths of the help can be changed with
+//! [Repl::with_help_template]. The `after_help` of a command is shown as EXAMPLES section.
+//! Prose pages can be added with [Repl::with_help_topic], they're shown by `help <topic>`.
+//!
 //! # Errors
 //!
 //! Your command functions don't need to return `reedline_repl_rs::Error`; you can return any error from
@@ -96,23 +125,126 @@
 //! ```rust,no_run
 #![doc = include_str!("../examples/custom_error.rs")]
 //! ```
+//!
+//! If your error type implements `std::error::Error`, [chain_error_handler] prints its
+//! `source()` chain as well, with each cause indented below the error it caused.
 
+mod abbreviation;
+mod accessible;
+mod alias;
+mod args;
+mod ask;
+#[cfg(feature = "audit")]
+mod audit;
+#[cfg(feature = "figlet")]
+mod banner;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+mod builtin;
+#[cfg(feature = "capture")]
+mod capture;
+#[cfg(feature = "clipboard")]
+mod clipboard;
 mod command;
 mod completer;
+#[cfg(feature = "completions")]
+mod completions;
+#[cfg(feature = "config")]
+mod config;
+mod console;
+mod context;
+#[cfg(unix)]
+mod control;
+mod deprecation;
+mod editor;
 mod error;
+mod guard;
+mod handle;
+mod help;
+mod highlighter;
+mod hinter;
+mod history;
+mod idle;
+mod init;
+#[cfg(feature = "json")]
+mod json;
+mod login;
+mod messages;
+mod notify;
+mod output;
+mod pager;
+mod paste;
+mod plugin;
 mod prompt;
+mod rate_limit;
+mod record;
 mod repl;
+mod results;
+mod script;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "session")]
+mod session;
+mod settings;
+mod signal;
+mod stash;
+mod stats;
+mod suggest;
+pub mod testing;
+mod theme;
+#[cfg(feature = "tui")]
+mod tui;
+mod undo;
+mod validator;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod width;
+mod workspace;
 
+pub use args::ArgMatchesExt;
+#[cfg(feature = "figlet")]
+pub use banner::BannerStyle;
 pub use clap;
 use clap::ArgMatches;
+#[cfg(feature = "clipboard")]
+pub use clipboard::paste_event;
+pub use command::CommandErrorAction;
+pub use console::ConsoleMode;
 pub use crossterm;
 pub use error::{Error, Result};
+pub use handle::ReplHandle;
+pub use help::{HelpSection, HelpTemplate};
+pub use highlighter::HighlightColors;
+pub use idle::IdleAction;
+pub use init::{eval_arg, InitFailurePolicy};
+pub use messages::Messages;
+#[cfg(feature = "lua")]
+pub use mlua;
+pub use notify::{Notifier, NotifyLevel};
 pub use nu_ansi_term;
+pub use plugin::{PluginCommand, ReplPlugin};
 pub use reedline;
+#[cfg(feature = "macro")]
+pub use reedline_repl_rs_macros::repl_command;
 #[doc(inline)]
-pub use repl::Repl;
+pub use repl::{chain_error_handler, Repl};
+#[cfg(feature = "serve")]
+pub use serve::{Server, SessionInfo};
+#[cfg(feature = "session")]
+pub use session::{ReplSession, ResizeHandle};
+pub use stats::CommandStats;
 #[cfg(feature = "async")]
 use std::{future::Future, pin::Pin};
+pub use theme::Theme;
+#[cfg(feature = "tui")]
+pub use tui::ReplConsole;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmCapabilities;
+#[cfg(feature = "websocket")]
+pub use websocket::run_websocket;
 pub use yansi;
 use yansi::Paint;
 
@@ -128,6 +260,57 @@ pub type AsyncCallback<Context, Error> =
         &'_ mut Context,
     ) -> Pin<Box<dyn Future<Output = std::result::Result<Option<String>, Error>> + '_>>;
 
+/// Command callback function signature for commands which control the Repl
+pub type HandleCallback<Context, Error> =
+    fn(ArgMatches, &mut Context, &mut ReplHandle) -> std::result::Result<Option<String>, Error>;
+
+/// Async Command callback function signature for commands which control the Repl
+#[cfg(feature = "async")]
+pub type AsyncHandleCallback<Context, Error> = for<'a> fn(
+    ArgMatches,
+    &'a mut Context,
+    &'a mut ReplHandle,
+) -> Pin<
+    Box<dyn Future<Output = std::result::Result<Option<String>, Error>> + 'a>,
+>;
+
+/// Dispatch function signature for commands of a clap derive [Subcommand](clap::Subcommand)
+pub type DerivedCallback<Command, Context, Error> =
+    fn(Command, &mut Context) -> std::result::Result<Option<String>, Error>;
+
+/// Callback function signature receiving the ArgMatches of a command, including the
+/// values of the global arguments, before the command is executed
+pub type GlobalArgsCallback<Context, Error> =
+    fn(&ArgMatches, &mut Context) -> std::result::Result<(), Error>;
+
+/// Matches inspector function signature, receiving the name of a command and its parsed
+/// ArgMatches before its callback is executed. Returning an error aborts the command
+pub type MatchesInspector<Context, Error> =
+    fn(&str, &ArgMatches, &mut Context) -> std::result::Result<(), Error>;
+
+/// Completion function signature, receiving the arguments before the cursor and the
+/// beginning of the argument being completed and returning the candidate values
+pub type CompletionCallback = fn(&[&str], &str) -> Vec<String>;
+
+/// Completion function signature like [CompletionCallback], additionally reading the
+/// Context, e.g. to complete names of the objects the application currently knows
+pub type ContextCompletionCallback<Context> = fn(&Context, &[&str], &str) -> Vec<String>;
+
+/// Prompt function signature, computing the prompt from the Context before it is shown
+pub type PromptCallback<Context> = fn(&Context) -> String;
+
+/// Capability resolver function signature, returning the capabilities the current
+/// session has been granted, e.g. `admin` for the commands added with
+/// [Repl::with_command_restricted] requiring it
+pub type CapabilityResolver<Context> = fn(&Context) -> Vec<String>;
+
+/// Banner function signature, computing text shown when the Repl starts from the Context
+pub type BannerCallback<Context> = fn(&Context) -> String;
+
+/// Resize callback function signature, receiving the new number of columns and rows of
+/// the terminal
+pub type ResizeCallback<Context> = fn(&mut Context, u16, u16);
+
 /// AfterCommand callback function signature
 pub type AfterCommandCallback<Context, Error> =
     fn(&mut Context) -> std::result::Result<Option<String>, Error>;
@@ -139,6 +322,49 @@ pub type AsyncAfterCommandCallback<Context, Error> =
         &'_ mut Context,
     ) -> Pin<Box<dyn Future<Output = std::result::Result<Option<String>, Error>> + '_>>;
 
+/// Start and exit callback function signature, a returned String is printed
+pub type LifecycleCallback<Context, Error> =
+    fn(&mut Context) -> std::result::Result<Option<String>, Error>;
+
+/// Async start and exit callback function signature
+#[cfg(feature = "async")]
+pub type AsyncLifecycleCallback<Context, Error> =
+    fn(
+        &'_ mut Context,
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<Option<String>, Error>> + '_>>;
+
+/// Error handler function signature for handlers which need access to the Context, called
+/// with the error before the Repl prints it
+pub type ContextErrorHandler<Context, Error> = fn(&Error, &mut Context) -> Result<()>;
+
+/// Error handler function signature for a single command, the returned action decides
+/// whether the command is executed again
+pub type CommandErrorHandler<Context, Error> =
+    fn(&Error, &mut Context) -> Result<CommandErrorAction>;
+
+/// Creates the Context of a new session of the `session` built-in command, see
+/// [Repl::with_sessions](crate::Repl::with_sessions)
+pub type ContextFactory<Context> = fn() -> Context;
+
+/// Login callback deciding whether the user may use the REPL, called with the entered
+/// password and the number of the attempt starting at 1
+pub type LoginCallback<Context, Error> =
+    fn(&str, u32, &mut Context) -> std::result::Result<bool, Error>;
+
+/// Connection error function signature of a [Server], receiving the session and the error
+/// which ended it, e.g. to log it
+#[cfg(feature = "serve")]
+pub type ConnectionErrorCallback = fn(&SessionInfo, &Error);
+
+/// Callback asked before the REPL exits on Ctrl-D or [ReplHandle::quit], returning a yes/no
+/// question the user has to confirm, or None to exit right away
+pub type ExitConfirmationCallback<Context> = fn(&mut Context) -> Option<String>;
+
+/// Callback which is invoked before code is evaluated with the `eval` built-in command,
+/// e.g. to expose Context values as Lua globals
+#[cfg(feature = "lua")]
+pub type LuaInitCallback<Context> = fn(&mlua::Lua, &mut Context) -> mlua::Result<()>;
+
 /// Utility to format prompt strings as green and bold. Use yansi directly instead for custom colors.
 pub fn paint_green_bold(input: &str) -> String {
     Box::new(Paint::green(input).bold()).to_string()
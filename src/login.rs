@@ -0,0 +1,33 @@
+//! Reading the password for the login gate, see [Repl::with_login](crate::Repl::with_login)
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal;
+use std::io;
+
+/// Read a password on the terminal without showing what is typed. Returns None on Ctrl-C
+/// and Ctrl-D
+pub(crate) fn read_hidden() -> io::Result<Option<String>> {
+    terminal::enable_raw_mode()?;
+    let result = read_keys();
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn read_keys() -> io::Result<Option<String>> {
+    let mut password = String::new();
+    loop {
+        if let Event::Key(KeyEvent { code, modifiers }) = event::read()? {
+            match code {
+                KeyCode::Enter => return Ok(Some(password)),
+                KeyCode::Backspace => {
+                    password.pop();
+                }
+                KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None);
+                }
+                KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => password.push(c),
+                _ => {}
+            }
+        }
+    }
+}
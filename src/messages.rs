@@ -0,0 +1,120 @@
+/// Texts the Repl shows by itself, so applications can translate them, see
+/// [Repl::with_messages](crate::Repl::with_messages). `{}` placeholders are replaced by
+/// the values named in the field documentation, in that order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Messages {
+    /// Heading of the commands in the help overview
+    pub commands_heading: String,
+    /// Heading of the built-in commands in the help overview
+    pub builtins_heading: String,
+    /// Heading of the help topics in the help overview
+    pub topics_heading: String,
+    /// Heading of the examples in the help of a command
+    pub examples_heading: String,
+    /// Description of the `help` command
+    pub help_about: String,
+    /// Description of `help` when it is suggested as completion
+    pub help_completion: String,
+    /// `help` was asked for something unknown: the name
+    pub help_not_found: String,
+    /// `examples` was asked for a command without examples: the command
+    pub no_examples: String,
+    /// Tag of deprecated commands in the help
+    pub deprecated_tag: String,
+    /// A deprecated command or argument was used: its name
+    pub deprecated: String,
+    /// A deprecated command or argument was used: its name and the replacement
+    pub deprecated_use: String,
+    /// An unknown command was entered: the command
+    pub unknown_command: String,
+    /// An unknown command was entered: the command and the most similar one
+    pub unknown_command_did_you_mean: String,
+    /// Question whether to run the most similar command: the command and the suggestion
+    pub run_suggestion: String,
+    /// Question whether to run a paste of several lines: the number of lines
+    pub paste_confirm: String,
+    /// Question whether to run, edit or drop a paste of several lines: the number of lines
+    pub paste_confirm_edit: String,
+    /// Question whether to go on after an init command failed: the error
+    pub init_continue: String,
+    /// Prompt asking for the password of the login gate
+    pub login_prompt: String,
+    /// A JSON request other than `login` was sent before logging in
+    pub login_required: String,
+    /// A login attempt was rejected
+    pub login_denied: String,
+    /// The session ended after the last login attempt was rejected
+    pub login_failed: String,
+    /// Time a command took, when timing is on: the duration
+    pub elapsed: String,
+    /// The idle timeout is near: the remaining seconds
+    pub idle_warning: String,
+    /// The session ended after the idle timeout
+    pub idle_timeout: String,
+    /// The `reload-config` command read the configuration file again
+    pub config_reloaded: String,
+    /// Column headings of the `stats` table: command, number of calls, number of failures
+    /// and average duration
+    pub stats_columns: [String; 4],
+    /// The `record` command started writing the session to a file: the path
+    pub recording_started: String,
+    /// The `record stop` command closed the file: the path
+    pub recording_stopped: String,
+    /// `record stop` was used without recording
+    pub not_recording: String,
+    /// The after command callback failed: the error
+    pub after_command_failed: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            commands_heading: "COMMANDS:".to_string(),
+            builtins_heading: "BUILT-IN COMMANDS:".to_string(),
+            topics_heading: "TOPICS:".to_string(),
+            examples_heading: "EXAMPLES:".to_string(),
+            help_about: "Print this message or the help of the given command(s)".to_string(),
+            help_completion: "show help".to_string(),
+            help_not_found: "Help not found for command '{}'".to_string(),
+            no_examples: "No examples for command '{}'".to_string(),
+            deprecated_tag: "(deprecated)".to_string(),
+            deprecated: "'{}' is deprecated".to_string(),
+            deprecated_use: "'{}' is deprecated, use '{}' instead".to_string(),
+            unknown_command: "Unknown command '{}'".to_string(),
+            unknown_command_did_you_mean: "Unknown command '{}', did you mean '{}'?".to_string(),
+            run_suggestion: "Unknown command '{}', run '{}' instead?".to_string(),
+            paste_confirm: "Run the {} pasted lines? (y/N)".to_string(),
+            paste_confirm_edit: "Run the {} pasted lines? (y)es, (e)dit, (N)o".to_string(),
+            init_continue: "{}\nContinue anyway?".to_string(),
+            login_prompt: "Password: ".to_string(),
+            login_required: "Login required".to_string(),
+            login_denied: "Access denied".to_string(),
+            login_failed: "Too many failed login attempts".to_string(),
+            elapsed: "Elapsed: {}".to_string(),
+            idle_warning: "Idle session times out in {} seconds".to_string(),
+            idle_timeout: "Idle session timed out".to_string(),
+            config_reloaded: "Configuration reloaded".to_string(),
+            stats_columns: [
+                "COMMAND".to_string(),
+                "CALLS".to_string(),
+                "FAILURES".to_string(),
+                "AVERAGE".to_string(),
+            ],
+            recording_started: "Recording to '{}'".to_string(),
+            recording_stopped: "Stopped recording to '{}'".to_string(),
+            not_recording: "Not recording".to_string(),
+            after_command_failed: "failed to execute after_command_callback {}".to_string(),
+        }
+    }
+}
+
+/// Replace the `{}` placeholders of `template` by `values` in order
+pub(crate) fn fill(template: &str, values: &[&str]) -> String {
+    let mut parts = template.split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for (index, part) in parts.enumerate() {
+        text.push_str(values.get(index).copied().unwrap_or_default());
+        text.push_str(part);
+    }
+    text
+}
@@ -0,0 +1,54 @@
+//! Notifications printed above the prompt, e.g. for alerts arriving from background tasks
+//!
+//! Notifications are queued and the Repl is woken, see [signal](crate::signal), so it
+//! prints them on its own thread before it prompts again.
+use crate::signal::Interrupts;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Importance of a notification, which selects its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NotifyLevel {
+    /// Neutral information
+    Info,
+    /// Something finished successfully
+    Success,
+    /// Something needs attention
+    Warning,
+    /// Something failed
+    Error,
+}
+
+/// Sends notifications to a running Repl from any thread, see
+/// [ReplHandle::notifier](crate::ReplHandle::notifier)
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    queue: Arc<Mutex<VecDeque<(NotifyLevel, String)>>>,
+    interrupts: Interrupts,
+}
+
+impl Notifier {
+    /// Print `text` as a one-line notification above the prompt. While a line is being
+    /// entered, the notification is printed once it has been submitted
+    pub fn notify(&self, level: NotifyLevel, text: &str) {
+        // a single line keeps the prompt in place
+        let text = text.lines().collect::<Vec<_>>().join(" ");
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back((level, text));
+        self.interrupts.wake();
+    }
+
+    pub(crate) fn interrupts(&self) -> &Interrupts {
+        &self.interrupts
+    }
+
+    pub(crate) fn take(&self) -> Vec<(NotifyLevel, String)> {
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain(..)
+            .collect()
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::{Error, Result};
+use crossterm::tty::IsTty;
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+type Writer = RefCell<Box<dyn Write + Send>>;
+
+/// Destinations of everything a Repl prints: command output, help and messages go to
+/// stdout, errors to stderr. Both can be replaced, e.g. to capture output in tests.
+pub(crate) struct Output {
+    stdout: Writer,
+    stderr: Writer,
+    terminal: bool,
+    plain: bool,
+    crlf: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            stdout: RefCell::new(Box::new(std::io::stdout())),
+            stderr: RefCell::new(Box::new(std::io::stderr())),
+            terminal: true,
+            plain: false,
+            crlf: false,
+        }
+    }
+}
+
+/// Writer removing ANSI escape sequences, for destinations which can't display them
+#[cfg(feature = "serve")]
+pub(crate) struct StripAnsi<W> {
+    inner: W,
+}
+
+#[cfg(feature = "serve")]
+impl<W: Write> StripAnsi<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "serve")]
+impl<W: Write> Write for StripAnsi<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner
+            .write_all(crate::width::strip_ansi(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Cloneable in-memory writer, e.g. for [Repl::with_output_writer](crate::Repl::with_output_writer)
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, invalid UTF-8 is replaced
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().expect("buffer lock poisoned")).into_owned()
+    }
+
+    /// Everything written so far, leaving the buffer empty
+    pub fn take(&self) -> String {
+        let contents = std::mem::take(&mut *self.0.lock().expect("buffer lock poisoned"));
+        String::from_utf8_lossy(&contents).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .expect("buffer lock poisoned")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    /// Replace the stdout writer, returning the previous one
+    pub(crate) fn set_stdout(&mut self, writer: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        self.terminal = false;
+        std::mem::replace(self.stdout.get_mut(), writer)
+    }
+
+    /// Replace the stderr writer, returning the previous one
+    pub(crate) fn set_stderr(&mut self, writer: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        std::mem::replace(self.stderr.get_mut(), writer)
+    }
+
+    /// Adapt the output to a legacy console, removing ANSI escape sequences if it can't
+    /// process them and ending lines with CRLF
+    pub(crate) fn set_console(&mut self, plain: bool, crlf: bool) {
+        self.plain = plain;
+        self.crlf = crlf;
+    }
+
+    /// Returns true if stdout hasn't been replaced and is a terminal
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.terminal && std::io::stdout().is_tty()
+    }
+
+    pub(crate) fn println(&self, text: &str) -> Result<()> {
+        self.write(&self.stdout, &format!("{}\n", text))
+    }
+
+    pub(crate) fn eprintln(&self, text: &str) -> Result<()> {
+        self.write(&self.stderr, &format!("{}\n", text))
+    }
+
+    /// Print text without a newline, e.g. a prompt
+    pub(crate) fn print(&self, text: &str) -> Result<()> {
+        self.write(&self.stdout, text)
+    }
+
+    fn write(&self, writer: &Writer, text: &str) -> Result<()> {
+        let mut text = text.to_string();
+        if self.plain {
+            text = crate::width::strip_ansi(&text);
+        }
+        if self.crlf {
+            text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+        }
+        let mut writer = writer.borrow_mut();
+        write!(writer, "{}", text)
+            .and_then(|_| writer.flush())
+            .map_err(|err| Error::IoError("<output>".to_string(), err.to_string()))
+    }
+}
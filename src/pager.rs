@@ -0,0 +1,87 @@
+//! Interactive paging of output which is longer than the terminal
+
+use crate::error::{Error, Result};
+use crate::output::Output;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{self, disable_raw_mode, enable_raw_mode};
+
+const MORE: &str = "-- More -- (space: next page, enter: next line, q: quit)";
+
+enum Advance {
+    Page,
+    Line,
+    Quit,
+    /// The terminal has been resized to this number of rows
+    Resize(usize),
+}
+
+/// The size of the terminal in columns and rows, None if it can't be determined
+pub(crate) fn terminal_size() -> Option<(usize, usize)> {
+    match terminal::size() {
+        Ok((columns, rows)) if columns > 0 && rows > 0 => Some((columns as usize, rows as usize)),
+        _ => None,
+    }
+}
+
+/// Print text a screen at a time, waiting for a key press after each screen. Text which
+/// fits on the screen is printed at once. Resizing the terminal changes the size of the
+/// following screens
+pub(crate) fn page(output: &Output, text: &str, rows: usize) -> Result<()> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut page_size = rows.saturating_sub(1).max(1);
+    if lines.len() <= page_size {
+        return output.println(text);
+    }
+    let mut shown = 0;
+    let mut next = page_size;
+    while shown < lines.len() {
+        let end = next.min(lines.len());
+        for line in &lines[shown..end] {
+            output.println(line)?;
+        }
+        shown = end;
+        if shown == lines.len() {
+            break;
+        }
+        let advance = loop {
+            output.print(MORE)?;
+            let advance = read_advance();
+            output.print(&format!("\r{}\r", " ".repeat(MORE.len())))?;
+            match advance? {
+                Advance::Resize(rows) => page_size = rows.saturating_sub(1).max(1),
+                advance => break advance,
+            }
+        };
+        next = match advance {
+            Advance::Page => shown + page_size,
+            Advance::Line => shown + 1,
+            _ => break,
+        };
+    }
+    Ok(())
+}
+
+fn read_advance() -> Result<Advance> {
+    let io_error = |err: std::io::Error| Error::IoError("<stdin>".to_string(), err.to_string());
+    enable_raw_mode().map_err(io_error)?;
+    let advance = loop {
+        match event::read() {
+            Ok(Event::Key(KeyEvent {
+                code, modifiers, ..
+            })) => match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Ok(Advance::Quit)
+                }
+                KeyCode::Char(' ') | KeyCode::PageDown => break Ok(Advance::Page),
+                KeyCode::Enter | KeyCode::Down | KeyCode::Char('j') => break Ok(Advance::Line),
+                KeyCode::Char('q') | KeyCode::Esc => break Ok(Advance::Quit),
+                _ => {}
+            },
+            Ok(Event::Resize(_, rows)) if rows > 0 => break Ok(Advance::Resize(rows as usize)),
+            Ok(_) => {}
+            Err(err) => break Err(io_error(err)),
+        }
+    };
+    disable_raw_mode().map_err(io_error)?;
+    advance
+}
@@ -0,0 +1,161 @@
+//! Confirming multi-line pastes, see
+//! [Repl::with_paste_confirmation](crate::Repl::with_paste_confirmation)
+//!
+//! The terminal doesn't mark pastes, crossterm 0.23 which reedline uses can't enable
+//! bracketed paste. reedline returns the first line of a paste once its Enter is handled
+//! and leaves the keys of the other lines queued, so keys still waiting right after a line
+//! was read are taken as the rest of a paste if they contain another Enter. Keys after the
+//! last Enter, or all of them without one, were typed ahead and are replayed to the line
+//! editor by [TypedAheadEditMode].
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use reedline::{EditMode, PromptEditMode, ReedlineEvent};
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// pasted keys arrive together, typed ones are far apart
+const PASTE_WAIT: Duration = Duration::from_millis(10);
+
+/// Keys read while looking for a paste which belong to the next line
+#[derive(Clone, Default)]
+pub(crate) struct TypedAhead(Arc<Mutex<Vec<Event>>>);
+
+impl TypedAhead {
+    fn set(&self, events: Vec<Event>) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = events;
+    }
+
+    fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+/// Edit mode handling the keys typed ahead before the next key, as reedline can't be
+/// given the start of a line. They show up once the next key is pressed
+pub(crate) struct TypedAheadEditMode {
+    inner: Box<dyn EditMode>,
+    typed_ahead: TypedAhead,
+}
+
+impl TypedAheadEditMode {
+    pub(crate) fn new(inner: Box<dyn EditMode>, typed_ahead: TypedAhead) -> Self {
+        Self { inner, typed_ahead }
+    }
+}
+
+impl EditMode for TypedAheadEditMode {
+    fn parse_event(&mut self, event: Event) -> ReedlineEvent {
+        let mut events: Vec<ReedlineEvent> = self
+            .typed_ahead
+            .take()
+            .into_iter()
+            .map(|event| self.inner.parse_event(event))
+            .collect();
+        if events.is_empty() {
+            return self.inner.parse_event(event);
+        }
+        events.push(self.inner.parse_event(event));
+        ReedlineEvent::Multiple(events)
+    }
+
+    fn edit_mode(&self) -> PromptEditMode {
+        self.inner.edit_mode()
+    }
+}
+
+/// The lines of keys already waiting to be read, empty if nothing was pasted. The keys
+/// after the last line are kept in `typed_ahead`
+pub(crate) fn pending_lines(typed_ahead: &TypedAhead) -> io::Result<Vec<String>> {
+    crossterm::terminal::enable_raw_mode()?;
+    let result = read_pending();
+    crossterm::terminal::disable_raw_mode()?;
+    let mut events = result?;
+    let rest = events
+        .iter()
+        .rposition(is_enter)
+        .map_or(0, |index| index + 1);
+    typed_ahead.set(events.split_off(rest));
+    Ok(to_lines(&events))
+}
+
+fn read_pending() -> io::Result<Vec<Event>> {
+    let mut events = vec![];
+    while event::poll(PASTE_WAIT)? {
+        events.push(event::read()?);
+    }
+    Ok(events)
+}
+
+fn is_enter(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key(KeyEvent {
+            code: KeyCode::Enter,
+            ..
+        })
+    )
+}
+
+/// The text of the lines ended by Enter, with Backspace removing the character before it
+fn to_lines(events: &[Event]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for event in events {
+        if let Event::Key(KeyEvent { code, modifiers }) = event {
+            match code {
+                KeyCode::Enter => lines.push(std::mem::take(&mut line)),
+                KeyCode::Tab => line.push('\t'),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Char(c) if *modifiers - KeyModifiers::SHIFT == KeyModifiers::NONE => {
+                    line.push(*c)
+                }
+                _ => {}
+            }
+        }
+    }
+    lines
+}
+
+/// Let the user change `lines` in `editor`, returns the lines saved or None if the editor
+/// failed, e.g. was quit with `:cq` to cancel
+pub(crate) fn edit(editor: &str, lines: &[String]) -> io::Result<Option<Vec<String>>> {
+    let (path, mut file) = create_temp_file()?;
+    let written = file.write_all(lines.join("\n").as_bytes());
+    drop(file);
+    let result = written.and_then(|_| run_editor(editor, &path));
+    let _ = std::fs::remove_file(&path);
+    Ok(result?.map(|text| text.lines().map(str::to_string).collect()))
+}
+
+/// Creates a file with an unpredictable name in the temp directory, which is neither
+/// an existing file nor a link planted there by someone else
+fn create_temp_file() -> io::Result<(PathBuf, File)> {
+    loop {
+        let random = RandomState::new().build_hasher().finish();
+        let path = std::env::temp_dir().join(format!("repl_paste_{:016x}.txt", random));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn run_editor(editor: &str, path: &Path) -> io::Result<Option<String>> {
+    if !Command::new(editor).arg(path).status()?.success() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path).map(Some)
+}
@@ -0,0 +1,80 @@
+//! Plugins adding commands to a Repl, linked in or loaded from dynamic libraries
+
+use crate::{Callback, CompletionCallback};
+use clap::Command;
+
+/// A command contributed by a [ReplPlugin]
+pub struct PluginCommand<Context, E> {
+    pub(crate) command: Command,
+    pub(crate) callback: Callback<Context, E>,
+    pub(crate) completion: Option<CompletionCallback>,
+}
+
+impl<Context, E> PluginCommand<Context, E> {
+    /// A command executing `callback`, like [Repl::with_command](crate::Repl::with_command)
+    pub fn new(command: Command, callback: Callback<Context, E>) -> Self {
+        Self {
+            command,
+            callback,
+            completion: None,
+        }
+    }
+
+    /// Complete the arguments of the command, see
+    /// [Repl::with_completion](crate::Repl::with_completion)
+    pub fn with_completion(mut self, completion: CompletionCallback) -> Self {
+        self.completion = Some(completion);
+        self
+    }
+}
+
+/// Extension of a Repl by a third party, added with
+/// [Repl::with_plugin](crate::Repl::with_plugin) or, with the `plugins` feature, loaded
+/// from a dynamic library with [Repl::load_plugin](crate::Repl::load_plugin).
+///
+/// Commands of a plugin replace commands of the application with the same name.
+pub trait ReplPlugin<Context, E> {
+    /// Name of the plugin, used in error messages
+    fn name(&self) -> &str;
+
+    /// The commands added to the Repl
+    fn commands(&self) -> Vec<PluginCommand<Context, E>>;
+}
+
+/// Name of the function exported by plugin libraries, see [declare_plugin](crate::declare_plugin)
+#[cfg(feature = "plugins")]
+pub(crate) const PLUGIN_CONSTRUCTOR: &[u8] = b"_reedline_repl_plugin";
+
+/// Signature of the function exported by plugin libraries
+#[cfg(feature = "plugins")]
+pub(crate) type PluginConstructor<Context, E> = fn() -> Box<dyn ReplPlugin<Context, E>>;
+
+/// Export a [ReplPlugin] from a `cdylib` crate, so applications can load it with
+/// [Repl::load_plugin](crate::Repl::load_plugin). The Context and error types have to be
+/// the same types the application uses, and both have to be built with the same compiler
+/// and versions of this crate.
+///
+/// ```rust,ignore
+/// struct Greeter;
+///
+/// impl ReplPlugin<AppContext, reedline_repl_rs::Error> for Greeter {
+///     fn name(&self) -> &str {
+///         "greeter"
+///     }
+///
+///     fn commands(&self) -> Vec<PluginCommand<AppContext, reedline_repl_rs::Error>> {
+///         vec![PluginCommand::new(Command::new("greet"), greet)]
+///     }
+/// }
+///
+/// declare_plugin!(AppContext, reedline_repl_rs::Error, Greeter);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($context:ty, $error:ty, $plugin:expr) => {
+        #[no_mangle]
+        pub fn _reedline_repl_plugin() -> Box<dyn $crate::ReplPlugin<$context, $error>> {
+            Box::new($plugin)
+        }
+    };
+}
@@ -1,56 +1,122 @@
-use reedline::{DefaultPrompt, Prompt, PromptEditMode, PromptHistorySearch};
-use std::borrow::Cow;
-
-#[derive(Clone)]
-pub struct ReplPrompt {
-    default: DefaultPrompt,
-    prefix: String,
-}
-
-impl Prompt for ReplPrompt {
-    /// Use prefix as render prompt
-    fn render_prompt_left(&self) -> Cow<str> {
-        {
-            Cow::Borrowed(&self.prefix)
-        }
-    }
-
-    // call default impl
-    fn render_prompt_right(&self) -> Cow<str> {
-        self.default.render_prompt_right()
-    }
-    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<str> {
-        self.default.render_prompt_indicator(edit_mode)
-    }
-    fn render_prompt_multiline_indicator(&self) -> Cow<str> {
-        self.default.render_prompt_multiline_indicator()
-    }
-    fn render_prompt_history_search_indicator(
-        &self,
-        history_search: PromptHistorySearch,
-    ) -> Cow<str> {
-        self.default
-            .render_prompt_history_search_indicator(history_search)
-    }
-}
-
-impl Default for ReplPrompt {
-    fn default() -> Self {
-        ReplPrompt::new("repl")
-    }
-}
-
-impl ReplPrompt {
-    /// Constructor for the default prompt, which takes the amount of spaces required between the left and right-hand sides of the prompt
-    pub fn new(left_prompt: &str) -> ReplPrompt {
-        ReplPrompt {
-            prefix: left_prompt.to_string(),
-            default: DefaultPrompt::default(),
-        }
-    }
-
-    #[allow(dead_code)]
-    pub fn update_prefix(&mut self, prefix: &str) {
-        self.prefix = prefix.to_string();
-    }
-}
+use reedline::{DefaultPrompt, Prompt, PromptEditMode, PromptHistorySearch, PromptViMode};
+use std::borrow::Cow;
+
+/// Prompt indicator for consoles without Unicode glyphs
+const ASCII_INDICATOR: &str = "> ";
+const ASCII_VI_NORMAL_INDICATOR: &str = ": ";
+const ASCII_MULTILINE_INDICATOR: &str = "... ";
+
+#[derive(Clone)]
+pub struct ReplPrompt {
+    default: DefaultPrompt,
+    prefix: String,
+    ascii: bool,
+    plain: bool,
+    style: Option<yansi::Style>,
+    right: bool,
+}
+
+impl Prompt for ReplPrompt {
+    /// Use prefix as render prompt
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        match self.style {
+            Some(style) if !self.plain => Cow::Owned(
+                style
+                    .paint(crate::width::strip_ansi(&self.prefix))
+                    .to_string(),
+            ),
+            _ if self.plain => Cow::Owned(crate::width::strip_ansi(&self.prefix)),
+            _ => Cow::Borrowed(&self.prefix),
+        }
+    }
+
+    // call default impl
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        if self.right {
+            self.default.render_prompt_right()
+        } else {
+            Cow::Borrowed("")
+        }
+    }
+    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<'_, str> {
+        match edit_mode {
+            PromptEditMode::Vi(PromptViMode::Normal) if self.ascii => {
+                Cow::Borrowed(ASCII_VI_NORMAL_INDICATOR)
+            }
+            PromptEditMode::Default | PromptEditMode::Emacs | PromptEditMode::Vi(_)
+                if self.ascii =>
+            {
+                Cow::Borrowed(ASCII_INDICATOR)
+            }
+            _ => self.default.render_prompt_indicator(edit_mode),
+        }
+    }
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        if self.ascii {
+            Cow::Borrowed(ASCII_MULTILINE_INDICATOR)
+        } else {
+            self.default.render_prompt_multiline_indicator()
+        }
+    }
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<'_, str> {
+        self.default
+            .render_prompt_history_search_indicator(history_search)
+    }
+}
+
+impl Default for ReplPrompt {
+    fn default() -> Self {
+        ReplPrompt::new("repl")
+    }
+}
+
+impl ReplPrompt {
+    /// Constructor for the default prompt, which takes the amount of spaces required between the left and right-hand sides of the prompt
+    pub fn new(left_prompt: &str) -> ReplPrompt {
+        ReplPrompt {
+            prefix: left_prompt.to_string(),
+            default: DefaultPrompt,
+            ascii: false,
+            plain: false,
+            style: None,
+            right: true,
+        }
+    }
+
+    /// Use ASCII indicators, for consoles which can't render the Unicode ones
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.ascii = ascii;
+    }
+
+    /// Paint the prefix with `style` instead of its own colors
+    pub fn set_style(&mut self, style: Option<yansi::Style>) {
+        self.style = style;
+    }
+
+    /// Remove ANSI escape sequences from the prefix, for consoles which can't process them
+    pub fn set_plain(&mut self, plain: bool) {
+        self.plain = plain;
+    }
+
+    /// Show the right prompt, the current time per default
+    pub fn set_right(&mut self, right: bool) {
+        self.right = right;
+    }
+
+    #[allow(dead_code)]
+    pub fn update_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+    }
+
+    /// The prompt as shown before the input in the default edit mode
+    pub fn render_plain(&self) -> String {
+        format!(
+            "{}{}",
+            self.render_prompt_left(),
+            self.render_prompt_indicator(PromptEditMode::Default)
+        )
+    }
+}
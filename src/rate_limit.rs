@@ -0,0 +1,37 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Limits how often a command may run, see [Repl::with_rate_limit](crate::Repl::with_rate_limit)
+pub(crate) struct RateLimit {
+    max_calls: usize,
+    period: Duration,
+    // start times of the calls within the last period, oldest first
+    calls: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    pub(crate) fn new(max_calls: usize, period: Duration) -> Self {
+        Self {
+            max_calls: max_calls.max(1),
+            period,
+            calls: VecDeque::new(),
+        }
+    }
+
+    /// Count a call starting now, returns the time to wait instead if the limit is reached
+    pub(crate) fn acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        while let Some(&oldest) = self.calls.front() {
+            if now.duration_since(oldest) < self.period {
+                break;
+            }
+            self.calls.pop_front();
+        }
+        if self.calls.len() < self.max_calls {
+            self.calls.push_back(now);
+            return Ok(());
+        }
+        let oldest = self.calls[0];
+        Err(self.period - now.duration_since(oldest))
+    }
+}
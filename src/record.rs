@@ -0,0 +1,48 @@
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Writes entered commands (and optionally their output as `#` comments) to a script file
+/// which can be re-run with [Repl::replay](crate::Repl::replay)
+pub(crate) struct Recorder {
+    path: String,
+    file: File,
+    with_output: bool,
+}
+
+impl Recorder {
+    /// Create (or truncate) the script file at `path`
+    pub(crate) fn start(path: &Path, with_output: bool) -> Result<Self> {
+        let display = path.display().to_string();
+        let file =
+            File::create(path).map_err(|err| Error::IoError(display.clone(), err.to_string()))?;
+        Ok(Self {
+            path: display,
+            file,
+            with_output,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn record_command(&mut self, line: &str) -> Result<()> {
+        self.write_line(line)
+    }
+
+    pub(crate) fn record_output(&mut self, output: &str) -> Result<()> {
+        if self.with_output {
+            for line in output.lines() {
+                self.write_line(&format!("# {}", line))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{}", line)
+            .map_err(|err| Error::IoError(self.path.clone(), err.to_string()))
+    }
+}
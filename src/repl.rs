@@ -1,52 +1,274 @@
-use crate::command::ReplCommand;
-use crate::completer::ReplCompleter;
+use crate::abbreviation::{AbbreviationEditMode, AbbreviationMenu};
+use crate::accessible::PlainMenu;
+use crate::alias::{self, Aliases};
+#[cfg(feature = "audit")]
+use crate::audit::AuditLog;
+#[cfg(feature = "figlet")]
+use crate::banner::{self, BannerStyle};
+use crate::builtin;
+#[cfg(feature = "capture")]
+use crate::capture::StdioCapture;
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+use crate::command::{CommandIndex, ReplCommand};
+use crate::completer::{CompletionProvider, ReplCompleter};
+#[cfg(feature = "completions")]
+use crate::completions;
+#[cfg(feature = "config")]
+use crate::config::Config;
+use crate::console::{ConsoleCapabilities, ConsoleMode};
+use crate::context::ContextStore;
+#[cfg(feature = "persistence")]
+use crate::context::Persistence;
+#[cfg(unix)]
+use crate::control::{self, ControlSocket};
+use crate::deprecation::{self, Deprecations};
+use crate::editor::{self, ExternalEditorEditMode};
 use crate::error::*;
+use crate::guard::{self, TerminalGuard};
+use crate::handle::ParentRepl;
+use crate::help::{HelpTemplate, Overview};
+use crate::highlighter::{HighlightColors, ReplHighlighter, SharedHighlighter};
+use crate::hinter::ReplHinter;
+use crate::history::{LazyHistory, LoadFailure};
+use crate::idle::{IdleEvent, IdleTimer};
+use crate::init;
+#[cfg(feature = "json")]
+use crate::json;
+use crate::login;
+use crate::messages::{self, Messages};
+use crate::notify::{Notifier, NotifyLevel};
+use crate::output::Output;
+use crate::pager;
+use crate::paste::{self, TypedAhead, TypedAheadEditMode};
 use crate::prompt::ReplPrompt;
-use crate::{paint_green_bold, paint_yellow_bold, AfterCommandCallback, Callback};
+use crate::rate_limit::RateLimit;
+use crate::record::Recorder;
+use crate::results::ResultHistory;
+use crate::script;
+use crate::settings::{format_switch, parse_switch, Setting};
+use crate::signal::{self, SignalHandlers};
+use crate::stash::{self, LineStash, StashMenu};
+use crate::stats::CommandStats;
+use crate::suggest;
+use crate::theme::Theme;
+use crate::undo::UndoHistory;
+use crate::validator::{self, BodyValidator, SharedValidator};
+use crate::width;
+use crate::workspace::Workspace;
+#[cfg(feature = "lua")]
+use crate::LuaInitCallback;
+use crate::{
+    paint_green_bold, AfterCommandCallback, BannerCallback, Callback, CapabilityResolver,
+    CommandErrorAction, CommandErrorHandler, CompletionCallback, ContextCompletionCallback,
+    ContextErrorHandler, ContextFactory, DerivedCallback, ExitConfirmationCallback,
+    GlobalArgsCallback, HandleCallback, IdleAction, InitFailurePolicy, LifecycleCallback,
+    LoginCallback, MatchesInspector, PromptCallback, ReplHandle, ReplPlugin, ResizeCallback,
+};
 #[cfg(feature = "async")]
-use crate::{AsyncAfterCommandCallback, AsyncCallback};
-use clap::Command;
+use crate::{
+    AsyncAfterCommandCallback, AsyncCallback, AsyncHandleCallback, AsyncLifecycleCallback,
+};
+use clap::{Arg, ArgMatches, Command, Subcommand};
 use crossterm::event::{KeyCode, KeyModifiers};
-use nu_ansi_term::{Color, Style};
+use crossterm::tty::IsTty;
+use nu_ansi_term::Style;
 use reedline::{
-    default_emacs_keybindings, ColumnarMenu, DefaultHinter, DefaultValidator, Emacs,
-    ExampleHighlighter, FileBackedHistory, Keybindings, Reedline, ReedlineEvent, ReedlineMenu,
-    Signal,
+    default_emacs_keybindings, ColumnarMenu, DefaultValidator, EditMode, Emacs, FileBackedHistory,
+    Highlighter, Keybindings, Menu, Reedline, ReedlineEvent, ReedlineMenu, Signal, Validator,
 };
+use std::borrow::Cow;
 use std::boxed::Box;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
 type ErrorHandler<Context, E> = fn(error: E, repl: &Repl<Context, E>) -> Result<()>;
 
-fn default_error_handler<Context, E: Display>(error: E, _repl: &Repl<Context, E>) -> Result<()> {
-    eprintln!("{}", error);
-    Ok(())
+fn default_error_handler<Context, E: Display>(error: E, repl: &Repl<Context, E>) -> Result<()> {
+    repl.output.eprintln(&error.to_string())
+}
+
+/// Error handler printing the error with its chain of `source()` errors, for error types
+/// implementing `std::error::Error`:
+///
+/// ```text
+/// error: failed to sync
+///   caused by: connection refused
+/// ```
+///
+/// Use it with `repl.with_error_handler(chain_error_handler)`
+pub fn chain_error_handler<Context, E: std::error::Error>(
+    error: E,
+    repl: &Repl<Context, E>,
+) -> Result<()> {
+    repl.output.eprintln(&render_chain(&error, &repl.theme))
+}
+
+/// How often a command is executed again in a row when its error handler asks for it
+const MAX_COMMAND_RETRIES: u32 = 5;
+
+/// A yes/no question asked in a session without line editing, answered by the next line
+enum Question {
+    Exit,
+    /// Execute the command with the arguments again
+    Retry(String, Vec<String>),
+}
+
+/// State of the interactive loop, kept between calls of [Repl::step]. The fields are
+/// dropped in order, so the history is saved before the terminal is restored.
+///
+/// On dumb terminals there is no line editor, lines are read from stdin as they are.
+struct Session {
+    line_editor: Option<Reedline>,
+    idle_timer: Option<IdleTimer>,
+    _signals: Option<SignalHandlers>,
+    _guard: Option<TerminalGuard>,
 }
 
 /// Main REPL struct
 pub struct Repl<Context, E: Display> {
     name: String,
     banner: Option<String>,
+    banner_callback: Option<BannerCallback<Context>>,
+    quiet_start: bool,
+    notification_bell: Option<NotifyLevel>,
+    resize_callback: Option<ResizeCallback<Context>>,
+    // size of the terminal when it was last checked, to notice resizes between commands
+    terminal_size: Option<(usize, usize)>,
     version: String,
+    build_info: Vec<(String, String)>,
     description: String,
     prompt: ReplPrompt,
     after_command_callback: Option<AfterCommandCallback<Context, E>>,
     #[cfg(feature = "async")]
     after_command_callback_async: Option<AsyncAfterCommandCallback<Context, E>>,
+    start_callback: Option<LifecycleCallback<Context, E>>,
+    exit_callback: Option<LifecycleCallback<Context, E>>,
+    exit_confirmation: Option<ExitConfirmationCallback<Context>>,
+    login: Option<(LoginCallback<Context, E>, u32)>,
+    login_attempts: u32,
+    logged_in: bool,
+    question: Option<Question>,
+    exit_confirmed: bool,
+    workspace: Option<Workspace<Context>>,
+    #[cfg(feature = "async")]
+    start_callback_async: Option<AsyncLifecycleCallback<Context, E>>,
+    #[cfg(feature = "async")]
+    exit_callback_async: Option<AsyncLifecycleCallback<Context, E>>,
     commands: HashMap<String, ReplCommand<Context, E>>,
+    completions: HashMap<String, CompletionProvider>,
+    prompt_callback: Option<PromptCallback<Context>>,
+    capability_resolver: Option<CapabilityResolver<Context>>,
+    // capabilities returned by the resolver when the prompt was last shown
+    granted_capabilities: HashSet<String>,
+    rate_limits: HashMap<String, RateLimit>,
+    examples: HashMap<String, Vec<String>>,
+    deprecations: Deprecations,
+    results: ResultHistory,
+    global_args: Vec<Arg>,
+    global_args_callback: Option<GlobalArgsCallback<Context, E>>,
+    matches_inspector: Option<MatchesInspector<Context, E>>,
+    help_template: HelpTemplate,
+    help_pager: bool,
+    help_width: Option<usize>,
+    help_topics: BTreeMap<String, String>,
+    // rendered help by command name (empty for the overview) and width, cleared whenever
+    // commands or the template change
+    help_cache: HashMap<(String, Option<usize>), String>,
+    messages: Arc<Messages>,
+    // names registered more than once, reported by validate
+    duplicate_commands: Vec<String>,
+    #[cfg(unix)]
+    control_socket: Option<PathBuf>,
+    #[cfg(unix)]
+    control: Option<ControlSocket>,
+    builtins: HashMap<String, Command>,
+    index: CommandIndex,
+    handle: ReplHandle,
+    session: Option<Session>,
+    recorder: Option<Recorder>,
+    source_stack: Vec<PathBuf>,
+    aliases: Aliases,
+    output: Output,
+    output_capture: Option<Vec<String>>,
+    #[cfg(feature = "lua")]
+    lua: Option<mlua::Lua>,
+    #[cfg(feature = "lua")]
+    lua_init_callback: Option<LuaInitCallback<Context>>,
     history: Option<PathBuf>,
     history_capacity: Option<usize>,
-    context: Context,
+    history_lazy: bool,
+    history_failure: LoadFailure,
+    rc_file: Option<PathBuf>,
+    rc_file_abort_on_error: bool,
+    init_commands: Vec<String>,
+    init_failure_policy: InitFailurePolicy,
+    echo_queued: bool,
+    context: ContextStore<Context>,
+    #[cfg(feature = "persistence")]
+    persistence: Option<Persistence<Context>>,
+    undo: Option<UndoHistory<Context>>,
     keybindings: Keybindings,
     hinter_style: Style,
     hinter_enabled: bool,
+    accessible: bool,
+    completion_details: bool,
+    abbreviations: BTreeMap<String, String>,
+    body_commands: HashSet<String>,
+    external_editor: Option<String>,
+    external_editor_submits: bool,
+    line_stash: Option<LineStash>,
+    highlighter: Option<SharedHighlighter>,
+    highlighter_enabled: bool,
+    validator: Option<SharedValidator>,
     quick_completions: bool,
     partial_completions: bool,
     stop_on_ctrl_c: bool,
+    confirm_suggestions: bool,
+    prompt_missing_args: bool,
+    paste_confirmation: bool,
+    // keys read while looking for a paste, replayed to the line editor
+    typed_ahead: TypedAhead,
+    idle_timeout: Option<(Duration, IdleAction<Context, E>)>,
+    idle_warning: Duration,
+    catch_panics: bool,
     stop_on_ctrl_d: bool,
+    console_mode: ConsoleMode,
+    theme: Theme,
+    timing: bool,
+    stats: BTreeMap<String, CommandStats>,
+    // output of the current command, or of the previous one while `copy-last` runs
+    #[cfg(feature = "clipboard")]
+    command_output: String,
+    #[cfg(feature = "audit")]
+    audit_log: Option<AuditLog>,
+    #[cfg(feature = "capture")]
+    capture_stdio: bool,
+    rebuild_line_editor: bool,
+    #[cfg(feature = "config")]
+    config_file: Option<PathBuf>,
+    #[cfg(feature = "config")]
+    config_watch: bool,
+    #[cfg(feature = "config")]
+    config_modified: Option<std::time::SystemTime>,
+    #[cfg(feature = "config")]
+    config_keybindings: Vec<(KeyModifiers, KeyCode, Option<ReedlineEvent>)>,
+    // settings made with the builder, which the configuration file doesn't override
+    #[cfg_attr(not(feature = "config"), allow(dead_code))]
+    explicit_settings: HashSet<&'static str>,
+    #[cfg_attr(not(feature = "config"), allow(dead_code))]
+    explicit_keybindings: HashSet<(KeyModifiers, KeyCode)>,
     error_handler: ErrorHandler<Context, E>,
+    context_error_handler: Option<ContextErrorHandler<Context, E>>,
+    #[cfg(feature = "wasm")]
+    wasm_fuel: u64,
+    // declared last, the callbacks of loaded plugins point into these libraries
+    #[cfg(feature = "plugins")]
+    plugin_libraries: Vec<libloading::Library>,
 }
 
 impl<Context, E> Repl<Context, E>
@@ -55,53 +277,306 @@ where
 {
     /// Create a new Repl with the given context's initial value.
     pub fn new(context: Context) -> Self {
+        Self::with_context_store(ContextStore::Owned(context))
+    }
+
+    /// Create a new Repl working on a Context shared with other threads, e.g. background
+    /// tasks updating the state the commands show. Callbacks still receive `&mut Context`:
+    /// the Context is write locked while a callback runs, so don't keep it locked elsewhere
+    /// for long
+    ///
+    /// ```rust,ignore
+    /// let state = Arc::new(RwLock::new(State::default()));
+    /// let background = Arc::clone(&state);
+    /// std::thread::spawn(move || loop {
+    ///     background.write().unwrap().ticks += 1;
+    ///     std::thread::sleep(Duration::from_secs(1));
+    /// });
+    /// let mut repl = Repl::new_shared(state).with_command(Command::new("ticks"), ticks);
+    /// ```
+    pub fn new_shared(context: Arc<RwLock<Context>>) -> Self {
+        Self::with_context_store(ContextStore::Shared(context))
+    }
+
+    fn with_context_store(context: ContextStore<Context>) -> Self {
         let name = String::from("repl");
-        let style = Style::new().italic().fg(Color::LightGray);
+        let theme = Theme::default();
         let mut keybindings = default_emacs_keybindings();
         keybindings.add_binding(
             KeyModifiers::NONE,
             KeyCode::Tab,
             ReedlineEvent::Menu("completion_menu".to_string()),
         );
+        #[cfg(unix)]
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('z'),
+            ReedlineEvent::ExecuteHostCommand(signal::SUSPEND_COMMAND.to_string()),
+        );
         let prompt = ReplPrompt::new(&paint_green_bold(&format!("{}> ", name)));
 
         Self {
             name,
             banner: None,
+            banner_callback: None,
+            quiet_start: false,
+            notification_bell: None,
+            resize_callback: None,
+            terminal_size: None,
             version: String::new(),
+            build_info: vec![],
             description: String::new(),
             commands: HashMap::new(),
+            completions: HashMap::new(),
+            prompt_callback: None,
+            capability_resolver: None,
+            granted_capabilities: HashSet::new(),
+            rate_limits: HashMap::new(),
+            examples: HashMap::new(),
+            deprecations: Deprecations::default(),
+            results: ResultHistory::default(),
+            global_args: Vec::new(),
+            global_args_callback: None,
+            matches_inspector: None,
+            help_template: HelpTemplate::default(),
+            help_pager: true,
+            help_width: None,
+            help_topics: BTreeMap::new(),
+            help_cache: HashMap::new(),
+            messages: Arc::new(Messages::default()),
+            duplicate_commands: vec![],
+            #[cfg(unix)]
+            control_socket: None,
+            #[cfg(unix)]
+            control: None,
+            builtins: HashMap::new(),
+            index: CommandIndex::default(),
+            handle: ReplHandle::default(),
+            session: None,
+            recorder: None,
+            source_stack: Vec::new(),
+            aliases: Aliases::default(),
+            output: Output::default(),
+            output_capture: None,
+            #[cfg(feature = "lua")]
+            lua: None,
+            #[cfg(feature = "lua")]
+            lua_init_callback: None,
             history: None,
             history_capacity: None,
+            history_lazy: false,
+            history_failure: LoadFailure::default(),
+            rc_file: None,
+            rc_file_abort_on_error: false,
+            init_commands: vec![],
+            init_failure_policy: InitFailurePolicy::Continue,
+            echo_queued: true,
             after_command_callback: None,
             #[cfg(feature = "async")]
             after_command_callback_async: None,
+            start_callback: None,
+            exit_callback: None,
+            exit_confirmation: None,
+            login: None,
+            login_attempts: 0,
+            logged_in: false,
+            question: None,
+            exit_confirmed: false,
+            workspace: None,
+            #[cfg(feature = "async")]
+            start_callback_async: None,
+            #[cfg(feature = "async")]
+            exit_callback_async: None,
             quick_completions: true,
             partial_completions: false,
             hinter_enabled: true,
-            hinter_style: style,
+            accessible: false,
+            completion_details: false,
+            abbreviations: BTreeMap::new(),
+            body_commands: HashSet::new(),
+            external_editor: editor::from_env(),
+            external_editor_submits: false,
+            line_stash: None,
+            highlighter: None,
+            highlighter_enabled: true,
+            validator: None,
+            hinter_style: theme.hinter,
+            theme,
             prompt,
             context,
+            #[cfg(feature = "persistence")]
+            persistence: None,
+            undo: None,
             keybindings,
             stop_on_ctrl_c: false,
+            confirm_suggestions: false,
+            prompt_missing_args: false,
+            paste_confirmation: false,
+            typed_ahead: TypedAhead::default(),
+            idle_timeout: None,
+            idle_warning: Duration::ZERO,
+            catch_panics: false,
             stop_on_ctrl_d: true,
+            console_mode: ConsoleMode::Auto,
+            timing: false,
+            stats: BTreeMap::new(),
+            #[cfg(feature = "clipboard")]
+            command_output: String::new(),
+            #[cfg(feature = "audit")]
+            audit_log: None,
+            #[cfg(feature = "capture")]
+            capture_stdio: false,
+            rebuild_line_editor: false,
+            #[cfg(feature = "config")]
+            config_file: None,
+            #[cfg(feature = "config")]
+            config_watch: false,
+            #[cfg(feature = "config")]
+            config_modified: None,
+            #[cfg(feature = "config")]
+            config_keybindings: vec![],
+            explicit_settings: HashSet::new(),
+            explicit_keybindings: HashSet::new(),
             error_handler: default_error_handler,
+            context_error_handler: None,
+            #[cfg(feature = "wasm")]
+            wasm_fuel: crate::wasm::DEFAULT_FUEL,
+            #[cfg(feature = "plugins")]
+            plugin_libraries: Vec::new(),
         }
+        .with_version_commands(true)
     }
 
     /// Give your Repl a name. This is used in the help summary for the Repl.
     pub fn with_name(mut self, name: &str) -> Self {
         self.name = name.to_string();
-        self.with_formatted_prompt(name)
+        // the name is only the default prompt, a configured prompt takes precedence
+        self.prompt.update_prefix(name);
+        self
     }
 
     /// Give your Repl a banner. This is printed at the start of running the Repl.
     pub fn with_banner(mut self, banner: &str) -> Self {
         self.banner = Some(banner.to_string());
+        self.banner_callback = None;
+
+        self
+    }
+
+    /// Give your Repl a banner computed from the Context when the Repl starts instead of
+    /// when it is built, so it can show values only known then, e.g. the logged in user or
+    /// enabled features. Replaces a banner set with [with_banner](Repl::with_banner)
+    pub fn with_banner_fn(mut self, callback: BannerCallback<Context>) -> Self {
+        self.banner = None;
+        self.banner_callback = Some(callback);
+
+        self
+    }
+
+    /// Give your Repl a banner showing `text` as ASCII art in the standard figlet font,
+    /// e.g. the name of the application
+    #[cfg(feature = "figlet")]
+    pub fn with_banner_figlet(mut self, text: &str, style: BannerStyle) -> Self {
+        self.banner = Some(banner::render_figlet(text, style));
+
+        self
+    }
+
+    /// Give your Repl a figlet banner like [with_banner_figlet](Repl::with_banner_figlet),
+    /// followed by the text `callback` returns when the Repl starts, e.g. the version or the
+    /// address of the server it is connected to
+    #[cfg(feature = "figlet")]
+    pub fn with_banner_figlet_fn(
+        mut self,
+        text: &str,
+        style: BannerStyle,
+        callback: BannerCallback<Context>,
+    ) -> Self {
+        self.banner = Some(banner::render_figlet(text, style));
+        self.banner_callback = Some(callback);
+
+        self
+    }
+
+    /// Start without printing the banner or the output of the start callback, e.g. when
+    /// the Repl is launched by a script or embedded in a tool showing its own header.
+    /// Errors are still reported (Default: false)
+    pub fn with_quiet_start(mut self, quiet: bool) -> Self {
+        self.quiet_start = quiet;
+
+        self
+    }
+
+    /// A [Notifier] to print notifications above the prompt from background tasks, e.g.
+    /// a thread started before [run](Repl::run) watching for alerts
+    pub fn notifier(&self) -> Notifier {
+        self.handle.notifier()
+    }
+
+    /// Ring the terminal bell for notifications of `level` and above, see
+    /// [ReplHandle::notify]
+    pub fn with_notification_bell(mut self, level: NotifyLevel) -> Self {
+        self.notification_bell = Some(level);
+
+        self
+    }
+
+    /// Print the notifications sent since the prompt was shown last
+    fn print_notifications(&mut self) -> Result<()> {
+        for (level, text) in self.handle.take_notifications() {
+            let style = match level {
+                NotifyLevel::Info => yansi::Style::default(),
+                NotifyLevel::Success => self.theme.success,
+                NotifyLevel::Warning => self.theme.warning,
+                NotifyLevel::Error => self.theme.error,
+            };
+            // start at the beginning of the line, replacing the prompt painted there
+            let clear = if yansi::Paint::is_enabled() && self.output.is_terminal() {
+                "\r\x1b[2K"
+            } else {
+                ""
+            };
+            let bell = match self.notification_bell {
+                Some(bell_level) if level >= bell_level => "\x07",
+                _ => "",
+            };
+            self.output
+                .println(&format!("{}{}{}", clear, style.paint(text), bell))?;
+        }
+        Ok(())
+    }
+
+    /// Give your Repl a callback which is invoked with the new number of columns and rows
+    /// when the terminal has been resized, e.g. to adapt the layout of tables. Resizes are
+    /// noticed before the next prompt is shown, in a `ReplSession` with the next input after
+    /// its `ResizeHandle` reported them. The help is rendered for the current width and the
+    /// pager adapts its page size while it waits for a key anyway
+    pub fn with_on_resize(mut self, callback: ResizeCallback<Context>) -> Self {
+        self.resize_callback = Some(callback);
 
         self
     }
 
+    /// Call the resize callback if the terminal size changed since the last check
+    fn check_resize(&mut self) {
+        let size = pager::terminal_size();
+        if size == self.terminal_size {
+            return;
+        }
+        // the first size isn't a resize
+        let previous = std::mem::replace(&mut self.terminal_size, size);
+        if let (Some(_), Some((columns, rows))) = (previous, size) {
+            self.resized(columns as u16, rows as u16);
+        }
+    }
+
+    /// Call the resize callback with the new size of the terminal
+    pub(crate) fn resized(&mut self, columns: u16, rows: u16) {
+        if let Some(callback) = self.resize_callback {
+            callback(&mut self.context.lock(), columns, rows);
+        }
+    }
+
     /// Give your Repl a version. This is used in the help summary for the Repl.
     pub fn with_version(mut self, version: &str) -> Self {
         self.version = version.to_string();
@@ -116,6 +591,44 @@ where
         self
     }
 
+    /// Add a line of build information shown by the `about` built-in command, e.g.
+    /// `with_build_info("commit", env!("GIT_HASH"))`
+    pub fn with_build_info(mut self, key: &str, value: &str) -> Self {
+        self.build_info.push((key.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// Turn on/off the `version` built-in command, which shows the name and version, and
+    /// `about`, which adds the description and build information (Default: true).
+    /// Commands named `version` or `about` take precedence over them
+    pub fn with_version_commands(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::version_command(), enabled);
+        self.set_builtin(builtin::about_command(), enabled);
+
+        self
+    }
+
+    /// The name of the Repl
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The version of the Repl
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The description of the Repl
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The build information as key and value, in the order it was added
+    pub fn build_info(&self) -> &[(String, String)] {
+        &self.build_info
+    }
+
     /// Give your REPL a callback which is called after every command and may update the prompt
     pub fn with_on_after_command(mut self, callback: AfterCommandCallback<Context, E>) -> Self {
         self.after_command_callback = Some(callback);
@@ -134,454 +647,4288 @@ where
         self
     }
 
-    /// Give your Repl a file based history saved at history_path
-    pub fn with_history(mut self, history_path: PathBuf, capacity: usize) -> Self {
-        self.history = Some(history_path);
-        self.history_capacity = Some(capacity);
+    /// Give your REPL a callback which is called once before the first command is read,
+    /// e.g. to open connections. A returned String is printed, errors are passed to the
+    /// error handler which may abort the REPL by returning an error itself
+    pub fn with_on_start(mut self, callback: LifecycleCallback<Context, E>) -> Self {
+        self.start_callback = Some(callback);
 
         self
     }
 
-    /// Give your Repl a custom prompt. The default prompt is the Repl name, followed by
-    /// a `>`, all in green and bold, followed by a space:
-    ///
-    /// &Paint::green(format!("{}> ", name)).bold().to_string()
-    pub fn with_prompt(mut self, prompt: &str) -> Self {
-        self.prompt.update_prefix(prompt);
+    /// Give your REPL a callback which is called once before the first command is read
+    #[cfg(feature = "async")]
+    pub fn with_on_start_async(mut self, callback: AsyncLifecycleCallback<Context, E>) -> Self {
+        self.start_callback_async = Some(callback);
 
         self
     }
 
-    /// Give your Repl a custom prompt while applying green/bold formatting automatically
-    ///
-    /// &Paint::green(format!("{}> ", name)).bold().to_string()
-    pub fn with_formatted_prompt(mut self, prompt: &str) -> Self {
-        self.prompt.update_prefix(prompt);
+    /// Give your REPL a callback which is called once when the REPL exits, e.g. to save
+    /// state or print a farewell message. It is called as well if the REPL is aborted
+    /// by an error
+    pub fn with_on_exit(mut self, callback: LifecycleCallback<Context, E>) -> Self {
+        self.exit_callback = Some(callback);
 
         self
     }
 
-    /// Pass in a custom error handler. This is really only for testing - the default
-    /// error handler simply prints the error to stderr and then returns
-    pub fn with_error_handler(mut self, handler: ErrorHandler<Context, E>) -> Self {
-        self.error_handler = handler;
+    /// Give your REPL a callback which is asked before it exits on Ctrl-D (or Ctrl-C if
+    /// [with_stop_on_ctrl_c](Repl::with_stop_on_ctrl_c) is set) or [ReplHandle::quit]. It
+    /// returns a question, e.g. whether to discard unsaved changes, which the REPL asks with
+    /// (y/N) on the terminal, sessions without line editing take the next line entered as
+    /// answer. The JSON machine mode exits without asking
+    pub fn with_exit_confirmation(mut self, callback: ExitConfirmationCallback<Context>) -> Self {
+        self.exit_confirmation = Some(callback);
 
         self
     }
 
-    /// Turn on/off if REPL run is stopped on CTRG+C (Default: false)
-    pub fn with_stop_on_ctrl_c(mut self, stop_on_ctrl_c: bool) -> Self {
-        self.stop_on_ctrl_c = stop_on_ctrl_c;
+    /// Give your REPL a login callback which has to return true before the banner is shown
+    /// and commands can be entered. The REPL asks for the password with the `login_prompt`
+    /// message where the session reads its input: hidden on the terminal, otherwise as
+    /// line read from a non-interactive stdin, a served connection, a stream session or a
+    /// console pane, or as `login` request in the JSON machine mode. It is asked up to
+    /// `max_attempts` times, after that the REPL ends without running with exit code 1.
+    /// Errors it returns are passed to the error handler and count as failed attempt
+    pub fn with_login(mut self, callback: LoginCallback<Context, E>, max_attempts: u32) -> Self {
+        self.login = Some((callback, max_attempts.max(1)));
 
         self
     }
 
-    /// Turn on/off if REPL run is stopped on CTRG+D (Default: true)
-    pub fn with_stop_on_ctrl_d(mut self, stop_on_ctrl_d: bool) -> Self {
-        self.stop_on_ctrl_d = stop_on_ctrl_d;
+    /// Give your REPL a callback which is called once when the REPL exits
+    #[cfg(feature = "async")]
+    pub fn with_on_exit_async(mut self, callback: AsyncLifecycleCallback<Context, E>) -> Self {
+        self.exit_callback_async = Some(callback);
 
         self
     }
 
-    /// Turn on quick completions. These completions will auto-select if the completer
-    /// ever narrows down to a single entry.
-    pub fn with_quick_completions(mut self, quick_completions: bool) -> Self {
-        self.quick_completions = quick_completions;
+    /// Save the Context as JSON to the file at `path` when the Repl exits and restore it
+    /// from there when it starts, so variables, connection settings and preferences survive
+    /// restarts. The initial Context is used as long as nothing has been saved. Fields which
+    /// shouldn't be persisted can be marked `#[serde(skip)]`
+    #[cfg(feature = "persistence")]
+    pub fn with_context_persistence(mut self, path: PathBuf) -> Self
+    where
+        Context: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.persistence = Some(Persistence::new(path));
 
         self
     }
 
-    /// Turn on partial completions. These completions will fill the buffer with the
-    /// smallest common string from all the options
-    pub fn with_partial_completions(mut self, partial_completions: bool) -> Self {
-        self.partial_completions = partial_completions;
+    #[cfg(feature = "persistence")]
+    fn restore_context(&mut self) -> Result<()> {
+        let restored = match &self.persistence {
+            Some(persistence) => persistence.restore()?,
+            None => None,
+        };
+        if let Some(context) = restored {
+            *self.context.lock() = context;
+        }
+        Ok(())
+    }
 
-        self
+    #[cfg(feature = "persistence")]
+    fn save_context(&mut self) -> Result<()> {
+        if let Some(persistence) = &self.persistence {
+            persistence.save(&self.context.lock())?;
+        }
+        Ok(())
     }
 
-    /// Sets the style for reedline's fish-style history autosuggestions
-    ///
-    /// Default: `nu_ansi_term::Style::new().italic().fg(nu_ansi_term::Color::LightGray)`
-    ///
-    pub fn with_hinter_style(mut self, style: Style) -> Self {
-        self.hinter_style = style;
+    /// Turn on/off the `checkpoint [name]` and `undo [checkpoint]` built-in commands. The
+    /// Context is cloned before every command, so `undo` reverts the changes of the last
+    /// command (up to 100 commands back), and `undo <name>` returns to the state saved by
+    /// `checkpoint <name>` (Default: false)
+    pub fn with_undo_commands(mut self, enabled: bool) -> Self
+    where
+        Context: Clone,
+    {
+        self.undo = if enabled {
+            Some(UndoHistory::new())
+        } else {
+            None
+        };
+        self.set_builtin(builtin::checkpoint_command(), enabled);
+        self.set_builtin(builtin::undo_command(), enabled);
 
         self
     }
 
-    /// Disables reedline's fish-style history autosuggestions
-    pub fn with_hinter_disabled(mut self) -> Self {
-        self.hinter_enabled = false;
+    /// Turn on the `session new <name>`, `session list` and `session switch <name>`
+    /// built-in commands, which run several named sessions in one terminal. Each session
+    /// has its own Context, `factory` creates the Context of a new one. The Repl starts in
+    /// session `main` with the Context it was created with
+    pub fn with_sessions(mut self, factory: ContextFactory<Context>) -> Self {
+        self.workspace = Some(Workspace::new(factory));
+        self.set_builtin(builtin::session_command(), true);
 
         self
     }
 
-    /// Adds a reedline keybinding
-    ///
-    /// # Panics
-    ///
-    /// If `comamnd` is an empty [`ReedlineEvent::UntilFound`]
-    pub fn with_keybinding(
-        mut self,
-        modifier: KeyModifiers,
-        key_code: KeyCode,
-        command: ReedlineEvent,
-    ) -> Self {
-        self.keybindings.add_binding(modifier, key_code, command);
-
-        self
+    /// The name of the current session, see [with_sessions](Repl::with_sessions)
+    pub fn current_session(&self) -> Option<&str> {
+        self.workspace.as_ref().map(Workspace::current)
     }
 
-    /// Find a keybinding based on the modifier and keycode
-    pub fn find_keybinding(
-        &self,
-        modifier: KeyModifiers,
-        key_code: KeyCode,
-    ) -> Option<ReedlineEvent> {
-        self.keybindings.find_binding(modifier, key_code)
+    /// Save the current Context as checkpoint `name`, which [rollback](Repl::rollback) and
+    /// the `undo <name>` built-in command return to. Requires
+    /// [with_undo_commands](Repl::with_undo_commands)
+    pub fn snapshot(&mut self, name: &str) {
+        if let Some(undo) = &mut self.undo {
+            undo.checkpoint(name, &self.context.lock());
+        }
     }
 
-    /// Get assigned keybindings
-    pub fn get_keybindings(&self) -> HashMap<(KeyModifiers, KeyCode), ReedlineEvent> {
-        // keybindings.get_keybindings() cannot be returned directly because KeyCombination is not visible
-        self.keybindings
-            .get_keybindings()
-            .iter()
-            .map(|(key, value)| ((key.modifier, key.key_code), value.clone()))
-            .collect()
+    /// Return the Context to the state saved as checkpoint `name`
+    pub fn rollback(&mut self, name: &str) -> Result<()> {
+        let undo = self
+            .undo
+            .as_mut()
+            .ok_or_else(|| Error::UnknownCheckpoint(name.to_string()))?;
+        *self.context.lock() = undo.rollback(name)?;
+        Ok(())
     }
 
-    /// Remove a keybinding
+    /// Load settings from a TOML file when the Repl starts, so end users can customize a
+    /// shipped binary: theme, prompt text and color, history, completion options,
+    /// keybindings and aliases. Settings made with the builder take precedence, a missing
+    /// file is ignored. See [with_reload_config_command](Repl::with_reload_config_command)
+    /// and [with_config_watch](Repl::with_config_watch) to apply changes while running.
     ///
-    /// Returns `Some(ReedlineEvent)` if the keycombination was previously bound to a particular [`ReedlineEvent`]
-    pub fn without_keybinding(mut self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
-        self.keybindings.remove_binding(modifier, key_code);
+    /// ```toml
+    /// theme = "light"  # dark, light, monochrome or solarized
+    ///
+    /// [prompt]
+    /// text = "myapp> "
+    /// color = "cyan"   # black, red, green, yellow, blue, magenta, cyan, white or 0-255
+    /// bold = true
+    ///
+    /// [history]
+    /// file = "/home/me/.myapp_history"
+    /// capacity = 1000
+    ///
+    /// [completion]
+    /// quick = false
+    /// partial = true
+    /// hints = false
+    ///
+    /// [keybindings]
+    /// "ctrl-l" = "clear-screen"   # complete, submit, history-previous, history-next,
+    /// "alt-s" = "run:status"      # history-search, hint-complete, editor, none
+    ///
+    /// [aliases]
+    /// ll = "list --long"
+    ///
+    /// [settings]                  # see `show options`, written by `set --save`
+    /// timing = "on"
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn with_config_file(mut self, config_path: PathBuf) -> Self {
+        self.config_file = Some(config_path);
 
         self
     }
 
-    /// Add a command to your REPL
-    pub fn with_command(
-        mut self,
-        command: Command<'static>,
-        callback: Callback<Context, E>,
-    ) -> Self {
-        let name = command.get_name().to_string();
-        self.commands
-            .insert(name.clone(), ReplCommand::new(&name, command, callback));
+    /// Turn on/off the `reload-config` built-in command, which reads the
+    /// [configuration file](Repl::with_config_file) again and applies the changes to the
+    /// running session (Default: false)
+    #[cfg(feature = "config")]
+    pub fn with_reload_config_command(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::reload_config_command(), enabled);
+
         self
     }
 
-    /// Add a command to your REPL
-    #[cfg(feature = "async")]
-    pub fn with_command_async(
-        mut self,
-        command: Command<'static>,
-        callback: AsyncCallback<Context, E>,
-    ) -> Self {
-        let name = command.get_name().to_string();
-        self.commands.insert(
-            name.clone(),
-            ReplCommand::new_async(&name, command, callback),
-        );
+    /// Turn on/off reloading the [configuration file](Repl::with_config_file) whenever it
+    /// changed, checked before each prompt (Default: false)
+    #[cfg(feature = "config")]
+    pub fn with_config_watch(mut self, enabled: bool) -> Self {
+        self.config_watch = enabled;
+
         self
     }
 
-    fn show_help(&self, args: &[&str]) -> Result<()> {
-        if args.is_empty() {
-            let mut app = Command::new("app");
-
-            for (_, com) in self.commands.iter() {
-                app = app.subcommand(com.command.clone());
-            }
-            let mut help_bytes: Vec<u8> = Vec::new();
-            app.write_help(&mut help_bytes)
-                .expect("failed to print help");
-            let mut help_string =
-                String::from_utf8(help_bytes).expect("Help message was invalid UTF8");
-            let marker = "SUBCOMMANDS:";
-            if let Some(marker_pos) = help_string.find(marker) {
-                help_string = paint_yellow_bold("COMMANDS:")
-                    + &help_string[(marker_pos + marker.len())..help_string.len()];
-            }
-            let header = format!(
-                "{} {}\n{}\n",
-                paint_green_bold(&self.name),
-                self.version,
-                self.description
-            );
-            println!("{}", header);
-            println!("{}", help_string);
-        } else if let Some((_, subcommand)) = self
-            .commands
-            .iter()
-            .find(|(name, _)| name.as_str() == args[0])
-        {
-            subcommand
-                .command
-                .clone()
-                .print_help()
-                .expect("failed to print help");
-            println!();
-        } else {
-            eprintln!("Help not found for command '{}'", args[0]);
+    #[cfg(feature = "config")]
+    fn apply_config_file(&mut self) -> Result<()> {
+        let path = match &self.config_file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.config_modified = config_modified(path);
+        let config = match Config::load(path)? {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        // undo the keybindings and aliases of a previous load, the file may have dropped some
+        for (modifier, key_code, previous) in std::mem::take(&mut self.config_keybindings) {
+            match previous {
+                Some(event) => self.keybindings.add_binding(modifier, key_code, event),
+                None => {
+                    self.keybindings.remove_binding(modifier, key_code);
+                }
+            }
         }
-        Ok(())
-    }
+        self.aliases.clear_defaults();
+        let key_events = config.key_events();
+        let settings = config.settings();
+        let explicit = |setting: &str| self.explicit_settings.contains(setting);
+        let theme = config.theme().filter(|_| !explicit("theme"));
+        let prompt = config.prompt.as_ref().and_then(|prompt| prompt.render());
+        let prompt = prompt.filter(|_| !explicit("prompt"));
+        let history = config.history.filter(|_| !explicit("history"));
+        let quick = config
+            .completion
+            .quick
+            .filter(|_| !explicit("quick_completions"));
+        let partial = config
+            .completion
+            .partial
+            .filter(|_| !explicit("partial_completions"));
+        let hints = config.completion.hints.filter(|_| !explicit("hints"));
 
-    fn handle_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
-        match self.commands.get(command) {
-            Some(definition) => {
-                let mut argv: Vec<&str> = vec![command];
-                argv.extend(args);
-                match definition.command.clone().try_get_matches_from_mut(argv) {
-                    Ok(matches) => match (definition
-                        .callback
-                        .expect("Must be filled for sync commands"))(
-                        matches, &mut self.context
-                    ) {
-                        Ok(Some(value)) => println!("{}", value),
-                        Ok(None) => (),
-                        Err(error) => return Err(error),
-                    },
-                    Err(err) => {
-                        err.print().expect("failed to print");
-                    }
-                };
-                self.execute_after_command_callback()?;
-            }
-            None => {
-                if command == "help" {
-                    self.show_help(args)?;
-                } else {
-                    return Err(Error::UnknownCommand(command.to_string()).into());
-                }
+        if let Some(theme) = theme {
+            self.set_theme(theme);
+        }
+        if let Some(prompt) = prompt {
+            self.prompt.update_prefix(&prompt);
+        }
+        if let Some(history) = history {
+            self.history = Some(history.file);
+            self.history_capacity = Some(history.capacity);
+        }
+        if let Some(quick) = quick {
+            self.quick_completions = quick;
+        }
+        if let Some(partial) = partial {
+            self.partial_completions = partial;
+        }
+        if let Some(hints) = hints {
+            self.hinter_enabled = hints;
+        }
+        for (modifier, key_code, event) in key_events {
+            if !self.explicit_keybindings.contains(&(modifier, key_code)) {
+                let previous = self.keybindings.find_binding(modifier, key_code);
+                self.config_keybindings.push((modifier, key_code, previous));
+                self.keybindings.add_binding(modifier, key_code, event);
             }
         }
+        for (name, expansion) in config.aliases {
+            self.aliases.define_default(name, expansion);
+        }
+        for (name, value) in settings {
+            self.apply_setting(&name, &value)?;
+        }
+        Ok(())
+    }
 
+    /// Apply the configuration file again and rebuild the line editor with the changes
+    #[cfg(feature = "config")]
+    fn reload_config(&mut self) -> Result<()> {
+        self.apply_config_file()?;
+        self.rebuild_line_editor = true;
         Ok(())
     }
 
-    fn execute_after_command_callback(&mut self) -> core::result::Result<(), E> {
-        if let Some(callback) = self.after_command_callback {
-            match callback(&mut self.context) {
-                Ok(Some(new_prompt)) => {
-                    self.prompt.update_prefix(&new_prompt);
-                }
-                Ok(None) => {}
-                Err(err) => {
-                    eprintln!("failed to execute after_command_callback {:?}", err);
-                }
+    /// Reload the configuration file if it changed since it was applied and watching is
+    /// turned on. Errors in the file are reported without ending the session
+    #[cfg(feature = "config")]
+    fn reload_changed_config(&mut self, session: &mut Session) -> Result<()> {
+        let changed = match &self.config_file {
+            Some(path) if self.config_watch => config_modified(path) != self.config_modified,
+            _ => false,
+        };
+        if changed {
+            match self.reload_config() {
+                Ok(()) => self.output.println(&self.messages.config_reloaded)?,
+                Err(err) => self.output.eprintln(&render_chain(&err, &self.theme))?,
             }
+            self.refresh_line_editor(session)?;
         }
-
         Ok(())
     }
 
-    #[cfg(feature = "async")]
-    async fn execute_after_command_callback_async(&mut self) -> core::result::Result<(), E> {
-        self.execute_after_command_callback()?;
-        if let Some(callback) = self.after_command_callback_async {
-            match callback(&mut self.context).await {
-                Ok(new_prompt) => {
-                    if let Some(new_prompt) = new_prompt {
-                        self.prompt.update_prefix(&new_prompt);
-                    }
-                }
-                Err(err) => {
-                    eprintln!("failed to execute after_command_callback {:?}", err);
-                }
-            }
-        }
+    /// Give your Repl a file based history saved at history_path
+    pub fn with_history(mut self, history_path: PathBuf, capacity: usize) -> Self {
+        self.history = Some(history_path);
+        self.history_capacity = Some(capacity);
+        self.explicit_settings.insert("history");
 
-        Ok(())
+        self
     }
 
-    #[cfg(feature = "async")]
-    async fn handle_command_async(
-        &mut self,
-        command: &str,
-        args: &[&str],
-    ) -> core::result::Result<(), E> {
-        match self.commands.get(command) {
-            Some(definition) => {
-                let mut argv: Vec<&str> = vec![command];
-                argv.extend(args);
-                match definition.command.clone().try_get_matches_from_mut(argv) {
-                    Ok(matches) => match if let Some(async_callback) = definition.async_callback {
-                        async_callback(matches, &mut self.context).await
-                    } else {
-                        definition
-                            .callback
-                            .expect("Either async or sync callback must be set")(
-                            matches,
-                            &mut self.context,
-                        )
-                    } {
-                        Ok(Some(value)) => println!("{}", value),
-                        Ok(None) => (),
-                        Err(error) => return Err(error),
-                    },
-                    Err(err) => {
-                        err.print().expect("failed to print");
-                    }
-                };
-                self.execute_after_command_callback_async().await?;
+    /// Read the history file in the background instead of before the first prompt, so
+    /// huge history files don't delay startup. Until it has been read, browsing the history
+    /// waits for it and hints only come from the lines entered meanwhile. If reading fails,
+    /// the error is passed to the error handler before the next prompt and the history
+    /// stays in memory (Default: false)
+    pub fn with_history_lazy_loading(mut self, enabled: bool) -> Self {
+        self.history_lazy = enabled;
+
+        self
+    }
+
+    /// Give your Repl a startup file of commands (e.g. `~/.myapprc`) which are executed
+    /// before the first prompt. Empty lines and lines starting with `#` are skipped, a
+    /// missing file is silently ignored. Like all scripts it may use `let`, `if`, `for`
+    /// and `repeat` blocks, see [with_source_command](Repl::with_source_command).
+    pub fn with_rc_file(mut self, rc_path: &str) -> Self {
+        self.rc_file = Some(script::expand_tilde(rc_path));
+
+        self
+    }
+
+    /// Turn on/off if a failing command in the rc file aborts `run()` (Default: false).
+    /// When turned off, failures are reported with their line number and startup continues.
+    pub fn with_rc_file_abort_on_error(mut self, abort_on_error: bool) -> Self {
+        self.rc_file_abort_on_error = abort_on_error;
+
+        self
+    }
+
+    /// Add commands which are executed before the first prompt, after the
+    /// [rc file](Repl::with_rc_file), e.g. to connect to a server given on the command
+    /// line. A failing command is handled as set by
+    /// [with_init_failure_policy](Repl::with_init_failure_policy)
+    pub fn with_init_commands(mut self, commands: &[&str]) -> Self {
+        self.init_commands
+            .extend(commands.iter().map(|command| command.to_string()));
+
+        self
+    }
+
+    /// Add the commands in the environment variable `name` to the
+    /// [init commands](Repl::with_init_commands), e.g. with
+    /// `with_init_commands_from_env("MYAPP_INIT_CMDS", ";")` and
+    /// `MYAPP_INIT_CMDS="connect db1; set timing on"`. Nothing is added if it isn't set
+    pub fn with_init_commands_from_env(mut self, name: &str, separator: &str) -> Self {
+        self.init_commands
+            .extend(init::env_commands(name, separator));
+
+        self
+    }
+
+    /// Add the commands given with [eval_arg](crate::eval_arg) on the command line of your
+    /// application to the [init commands](Repl::with_init_commands), in their order
+    pub fn with_eval_commands(mut self, matches: &ArgMatches) -> Self {
+        self.init_commands.extend(init::eval_commands(matches));
+
+        self
+    }
+
+    /// Decide what happens when an init command fails (Default:
+    /// [InitFailurePolicy::Continue]). The error names the command and its position
+    pub fn with_init_failure_policy(mut self, policy: InitFailurePolicy) -> Self {
+        self.init_failure_policy = policy;
+
+        self
+    }
+
+    /// Turn on/off if init commands and commands enqueued with
+    /// [ReplHandle::enqueue](crate::ReplHandle::enqueue) are printed after the prompt
+    /// before they are executed, as if they had been typed, so transcripts of automated
+    /// runs look like interactive sessions (Default: true)
+    pub fn with_queued_echo(mut self, enabled: bool) -> Self {
+        self.echo_queued = enabled;
+
+        self
+    }
+
+    /// Turn on/off the `record start <file>` / `record stop` built-in commands, which
+    /// write every entered command (and with `--output` their output as `#` comments)
+    /// to a script that can be re-run with [replay](Repl::replay) (Default: false)
+    pub fn with_record_command(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::record_command(), enabled);
+
+        self
+    }
+
+    /// Turn on/off the `source <file>` built-in command, which executes the commands of a
+    /// file in the current session. Files may source other files, relative paths are
+    /// resolved against the directory of the sourcing file (Default: false)
+    ///
+    /// Scripts support a minimal control flow, blocks are opened at the end of a line and
+    /// closed by a line containing only `}`:
+    ///
+    /// ```plain
+    /// let target = staging
+    /// repeat 3 {
+    ///     ping $target
+    /// }
+    /// for name in Alice Bob "Carol D" {
+    ///     if $name != Bob {
+    ///         hello "$name"
+    ///     } else {
+    ///         hello stranger
+    ///     }
+    /// }
+    /// ```
+    pub fn with_source_command(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::source_command(), enabled);
+
+        self
+    }
+
+    /// Turn on/off the `alias` and `unalias` built-in commands. `alias st = status --all`
+    /// defines an alias which replaces the first word of entered lines, `alias` lists all
+    /// aliases. Aliases only live as long as the session, see
+    /// [with_alias_file](Repl::with_alias_file) to persist them (Default: false)
+    pub fn with_alias_commands(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::alias_command(), enabled);
+        self.set_builtin(builtin::unalias_command(), enabled);
+
+        self
+    }
+
+    /// Turn on the `alias` and `unalias` built-in commands and persist the aliases to
+    /// alias_path (e.g. next to your history file), so they are available in later sessions
+    pub fn with_alias_file(mut self, alias_path: PathBuf) -> Self {
+        self.aliases = Aliases::new(Some(alias_path));
+        self.with_alias_commands(true)
+    }
+
+    /// Turn on/off the `set <option> on|off` and `show options` built-in commands, which
+    /// change settings of the session like timing of commands, the help pager and completion
+    /// behavior. With a [configuration file](Repl::with_config_file), `set --save` also
+    /// stores the setting there (Default: false)
+    pub fn with_settings_commands(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::set_command(), enabled);
+        self.set_builtin(builtin::show_command(), enabled);
+
+        self
+    }
+
+    /// Turn on/off the `copy-last` built-in command, copying the output of the previous
+    /// command to the system clipboard, and `paste`, executing the lines of the clipboard.
+    /// Bind [paste_event](crate::paste_event) to a key to paste with it (Default: false)
+    #[cfg(feature = "clipboard")]
+    pub fn with_clipboard_commands(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::copy_last_command(), enabled);
+        self.set_builtin(builtin::paste_command(), enabled);
+
+        self
+    }
+
+    /// Capture what command callbacks print to stdout and stderr themselves, e.g. with
+    /// `println!`, and pass it through the output of the Repl like returned output, so it
+    /// is paged, recorded and redirected as well (Default: false). Output of other
+    /// threads printed while a command runs is captured too
+    #[cfg(feature = "capture")]
+    pub fn with_stdio_capture(mut self, enabled: bool) -> Self {
+        self.capture_stdio = enabled;
+
+        self
+    }
+
+    /// Print what a command callback printed to stdout and stderr while it was captured
+    #[cfg(feature = "capture")]
+    fn print_captured(&mut self, capture: Option<StdioCapture>) -> Result<()> {
+        let (stdout, stderr) = match capture {
+            Some(capture) => capture.finish(),
+            None => return Ok(()),
+        };
+        if !stdout.is_empty() {
+            self.print_output(stdout.trim_end_matches('\n'))?;
+        }
+        if !stderr.is_empty() {
+            self.output.eprintln(stderr.trim_end_matches('\n'))?;
+        }
+        Ok(())
+    }
+
+    /// Make the REPL usable with screen readers (Default: false). Completions are listed as
+    /// plain lines instead of a colored menu, history hints shown as ghost text, syntax
+    /// highlighting, colors, the clock on the right and the repainting it needs are turned
+    /// off, and prompts use ASCII indicators
+    pub fn with_accessible_mode(mut self, enabled: bool) -> Self {
+        self.accessible = enabled;
+
+        self
+    }
+
+    /// Keep the outputs of the last `capacity` commands, so later lines can pass them as
+    /// arguments: `$_` or `$1` is replaced by the most recent output, `$2` by the one before
+    /// and so on. The `results` built-in command lists them. A capacity of 0 turns this off
+    /// (Default: 0)
+    pub fn with_result_history(mut self, capacity: usize) -> Self {
+        self.results = ResultHistory::new(capacity);
+        self.set_builtin(builtin::results_command(), capacity > 0);
+
+        self
+    }
+
+    /// Turn on/off the `stats` built-in command, which shows the usage statistics of the
+    /// commands, see [stats](Repl::stats) (Default: false)
+    pub fn with_stats_command(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::stats_command(), enabled);
+
+        self
+    }
+
+    /// Record every executed command in the file at `path` as a line of JSON with
+    /// timestamp, user, command line, duration in milliseconds and whether it succeeded,
+    /// appending to the file if it exists. The user is taken from the `USER` or `USERNAME`
+    /// environment variable unless set with [with_audit_user](Repl::with_audit_user)
+    #[cfg(feature = "audit")]
+    pub fn with_audit_log(mut self, path: PathBuf) -> Self {
+        self.audit_log = Some(AuditLog::file(path));
+
+        self
+    }
+
+    /// Record every executed command to `writer`, see [with_audit_log](Repl::with_audit_log)
+    #[cfg(feature = "audit")]
+    pub fn with_audit_writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.audit_log = Some(AuditLog::writer(writer));
+
+        self
+    }
+
+    /// The user recorded in the audit log, e.g. the user of an SSH session. Call after
+    /// [with_audit_log](Repl::with_audit_log) or [with_audit_writer](Repl::with_audit_writer)
+    #[cfg(feature = "audit")]
+    pub fn with_audit_user(mut self, user: &str) -> Self {
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.set_user(user);
+        }
+
+        self
+    }
+
+    /// Print how long each command took (Default: false)
+    pub fn with_timing(mut self, timing: bool) -> Self {
+        self.timing = timing;
+
+        self
+    }
+
+    /// Turn on/off the `eval <code>` built-in command, which evaluates Lua code. Registered
+    /// commands are available as `run("command args")`, returning their output or raising
+    /// an error. Globals persist between evaluations (Default: false)
+    #[cfg(feature = "lua")]
+    pub fn with_eval_command(mut self, enabled: bool) -> Self {
+        self.set_builtin(builtin::eval_command(), enabled);
+
+        self
+    }
+
+    /// Give your REPL a callback which is invoked before code is evaluated with the `eval`
+    /// built-in command, e.g. to expose values of your Context as Lua globals
+    #[cfg(feature = "lua")]
+    pub fn with_on_eval(mut self, callback: LuaInitCallback<Context>) -> Self {
+        self.lua_init_callback = Some(callback);
+
+        self
+    }
+
+    fn set_builtin(&mut self, command: Command, enabled: bool) {
+        let name = command.get_name().to_string();
+        self.help_cache.clear();
+        // commands take precedence over built-ins of the same name
+        if !self.commands.contains_key(&name) {
+            if enabled {
+                self.index.insert(&name, Arc::new(command.clone()));
+            } else {
+                self.index.remove(&name);
+            }
+        }
+        if enabled {
+            self.builtins.insert(name, command);
+        } else {
+            self.builtins.remove(&name);
+        }
+    }
+
+    /// Replace the texts the Repl shows by itself, e.g. help headings and the message for
+    /// unknown commands, to translate them
+    pub fn with_messages(mut self, messages: Messages) -> Self {
+        self.messages = Arc::new(messages);
+        self.help_cache.clear();
+
+        self
+    }
+
+    /// Give your Repl a custom prompt. The default prompt is the Repl name, followed by
+    /// a `>`, all in green and bold, followed by a space:
+    ///
+    /// &Paint::green(format!("{}> ", name)).bold().to_string()
+    pub fn with_prompt(mut self, prompt: &str) -> Self {
+        self.prompt.update_prefix(prompt);
+        self.explicit_settings.insert("prompt");
+
+        self
+    }
+
+    /// Give your Repl a custom prompt while applying green/bold formatting automatically
+    ///
+    /// &Paint::green(format!("{}> ", name)).bold().to_string()
+    pub fn with_formatted_prompt(mut self, prompt: &str) -> Self {
+        self.prompt.update_prefix(prompt);
+        self.explicit_settings.insert("prompt");
+
+        self
+    }
+
+    /// Set the colors of prompt, line editor, completion menu, errors and help, see [Theme]
+    /// for the presets. The help styles can be changed afterwards with
+    /// [with_help_template](Repl::with_help_template)
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.set_theme(theme);
+        self.explicit_settings.insert("theme");
+        self
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        self.help_cache.clear();
+        self.prompt.set_style(theme.prompt);
+        self.hinter_style = theme.hinter;
+        self.help_template = self
+            .help_template
+            .clone()
+            .with_name_style(theme.help_name)
+            .with_heading_style(theme.help_heading)
+            .with_command_style(theme.help_command)
+            .with_deprecated_style(theme.help_deprecated);
+        self.theme = theme;
+    }
+
+    /// Set how the console is treated. Per default legacy consoles are detected, e.g. the
+    /// Windows console before virtual terminal processing, and get ASCII prompt indicators,
+    /// output without ANSI colors and CRLF line endings
+    pub fn with_console_mode(mut self, mode: ConsoleMode) -> Self {
+        self.console_mode = mode;
+        self
+    }
+
+    /// Write command output, help and other messages to writer instead of stdout
+    pub fn with_output_writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.output.set_stdout(writer);
+
+        self
+    }
+
+    /// Write error messages to writer instead of stderr
+    pub fn with_error_writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.output.set_stderr(writer);
+
+        self
+    }
+
+    /// Pass in a custom error handler. This is really only for testing - the default
+    /// error handler simply prints the error to stderr and then returns
+    pub fn with_error_handler(mut self, handler: ErrorHandler<Context, E>) -> Self {
+        self.error_handler = handler;
+
+        self
+    }
+
+    /// Pass in an error handler which receives the Context, e.g. to record failures in it.
+    /// It is called before the error handler given to
+    /// [with_error_handler](Repl::with_error_handler) prints the error, so the error goes
+    /// to the Repl's error writer, e.g. the client of a served session
+    pub fn with_context_error_handler(mut self, handler: ContextErrorHandler<Context, E>) -> Self {
+        self.context_error_handler = Some(handler);
+
+        self
+    }
+
+    fn handle_error(&mut self, error: E) -> Result<()> {
+        if let Some(handler) = self.context_error_handler {
+            handler(&error, &mut self.context.lock())?;
+        }
+        (self.error_handler)(error, self)
+    }
+
+    /// Turn on/off if REPL run is stopped on CTRG+C (Default: false)
+    pub fn with_stop_on_ctrl_c(mut self, stop_on_ctrl_c: bool) -> Self {
+        self.stop_on_ctrl_c = stop_on_ctrl_c;
+
+        self
+    }
+
+    /// End the REPL, or call a callback e.g. to lock it, once no input has been entered for
+    /// the given duration. The timeout is noticed while the prompt waits for the first key,
+    /// a line being edited is left alone until it has been submitted
+    pub fn with_idle_timeout(mut self, timeout: Duration, action: IdleAction<Context, E>) -> Self {
+        self.idle_timeout = Some((timeout, action));
+
+        self
+    }
+
+    /// Print a warning the given duration before the idle timeout expires
+    pub fn with_idle_warning(mut self, warning: Duration) -> Self {
+        self.idle_warning = warning;
+
+        self
+    }
+
+    /// Turn on/off if the user is asked whether to run the suggested command instead of
+    /// a mistyped one (Default: false)
+    pub fn with_confirm_suggestions(mut self, confirm_suggestions: bool) -> Self {
+        self.confirm_suggestions = confirm_suggestions;
+
+        self
+    }
+
+    /// Turn on/off if the user is asked for the values of missing required arguments
+    /// instead of getting a usage error, listing the possible values of an argument as
+    /// numbered choices. Leaving an answer empty shows the usage error. Only applies when
+    /// stdin and the output are the local terminal, not e.g. to served connections, sessions
+    /// or the JSON machine mode (Default: false)
+    pub fn with_prompt_missing_args(mut self, prompt_missing_args: bool) -> Self {
+        self.prompt_missing_args = prompt_missing_args;
+
+        self
+    }
+
+    /// Turn on/off if pasting several lines at the prompt shows them and asks whether to
+    /// run them all, edit them in the [external editor](Repl::with_external_editor) first,
+    /// or drop them, instead of running each line as it arrives. Terminals don't mark
+    /// pastes, so lines already waiting when a line is entered are taken as pasted. If the
+    /// editor exits with an error the paste is dropped (Default: false)
+    pub fn with_paste_confirmation(mut self, paste_confirmation: bool) -> Self {
+        self.paste_confirmation = paste_confirmation;
+
+        self
+    }
+
+    /// Turn on/off if panics in command callbacks are caught and passed to the error
+    /// handler instead of ending the REPL (Default: false)
+    pub fn with_catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+
+        self
+    }
+
+    /// Turn on/off if REPL run is stopped on CTRG+D (Default: true)
+    pub fn with_stop_on_ctrl_d(mut self, stop_on_ctrl_d: bool) -> Self {
+        self.stop_on_ctrl_d = stop_on_ctrl_d;
+
+        self
+    }
+
+    /// Turn on quick completions. These completions will auto-select if the completer
+    /// ever narrows down to a single entry.
+    pub fn with_quick_completions(mut self, quick_completions: bool) -> Self {
+        self.quick_completions = quick_completions;
+        self.explicit_settings.insert("quick_completions");
+
+        self
+    }
+
+    /// Turn on partial completions. These completions will fill the buffer with the
+    /// smallest common string from all the options
+    pub fn with_partial_completions(mut self, partial_completions: bool) -> Self {
+        self.partial_completions = partial_completions;
+        self.explicit_settings.insert("partial_completions");
+
+        self
+    }
+
+    /// Show the extra information of flag suggestions in the completion menu after their
+    /// description: the type of their value or `flag`, their default and whether they are
+    /// required, e.g. `Port to connect to [<PORT>, default: 5432]` (Default: false).
+    /// Completers built by the Repl always fill it into the `extra` field of [Suggestion](reedline::Suggestion)
+    pub fn with_completion_details(mut self, enabled: bool) -> Self {
+        self.completion_details = enabled;
+
+        self
+    }
+
+    /// Expand `abbreviation` to `expansion` when it is typed as command followed by a
+    /// space, e.g. `with_abbreviation("gco", "git checkout")`. Unlike aliases, the
+    /// expansion is visible in the line, can be edited before it is executed and is stored
+    /// in the history
+    pub fn with_abbreviation(mut self, abbreviation: &str, expansion: &str) -> Self {
+        self.abbreviations
+            .insert(abbreviation.to_string(), expansion.to_string());
+
+        self
+    }
+
+    /// Let the command `name` take a multi-line body: a line like `script <<` keeps the
+    /// input open until a line with only `.`, and the lines in between are passed to the
+    /// command as one last argument, joined by newlines
+    pub fn with_command_body(mut self, name: &str) -> Self {
+        self.body_commands.insert(name.to_string());
+
+        self
+    }
+
+    /// Make Tab behave like in bash: the first Tab lists the candidates without changing
+    /// the line, further ones cycle through them and Shift-Tab cycles back, Enter inserts
+    /// the selected candidate. Turns off quick and partial completions, which would change
+    /// the line on the first Tab
+    pub fn with_completion_cycling(mut self) -> Self {
+        self.keybindings.add_binding(
+            KeyModifiers::NONE,
+            KeyCode::Tab,
+            ReedlineEvent::UntilFound(vec![
+                ReedlineEvent::Menu("completion_menu".to_string()),
+                ReedlineEvent::MenuNext,
+            ]),
+        );
+        self.keybindings.add_binding(
+            KeyModifiers::SHIFT,
+            KeyCode::BackTab,
+            ReedlineEvent::MenuPrevious,
+        );
+
+        self.with_quick_completions(false)
+            .with_partial_completions(false)
+    }
+
+    /// Sets the style for reedline's fish-style history autosuggestions
+    ///
+    /// Default: the hinter style of the [Theme]
+    ///
+    pub fn with_hinter_style(mut self, style: Style) -> Self {
+        self.hinter_style = style;
+
+        self
+    }
+
+    /// Replace the built-in highlighter of the input line
+    pub fn with_highlighter(mut self, highlighter: Box<dyn Highlighter>) -> Self {
+        self.highlighter = Some(SharedHighlighter(Arc::new(Mutex::new(highlighter))));
+
+        self
+    }
+
+    /// Set the colors of the built-in highlighter. It colors the input according to the
+    /// clap definitions as you type: known and unknown commands, flags and flags the command
+    /// doesn't have, possible values, quoted strings and other arguments
+    pub fn with_highlighter_colors(mut self, colors: HighlightColors) -> Self {
+        self.theme.highlight = colors;
+
+        self
+    }
+
+    /// Disables highlighting of the input line
+    pub fn with_highlighter_disabled(mut self) -> Self {
+        self.highlighter_enabled = false;
+
+        self
+    }
+
+    /// Decide when the input is complete, e.g. only once an SQL statement ends with `;`.
+    /// Enter on incomplete input starts a new line instead of executing it. Per default
+    /// input is incomplete while brackets or double quotes are unbalanced
+    pub fn with_validator(mut self, validator: Box<dyn Validator>) -> Self {
+        self.validator = Some(SharedValidator(Arc::new(Mutex::new(validator))));
+
+        self
+    }
+
+    /// Edit the line in `editor` on Ctrl-X Ctrl-E (and on Ctrl-O), the edited text
+    /// replaces the line. Per default the editor set in `VISUAL` or `EDITOR` is used, the
+    /// keys do nothing if neither is set. `editor` is run with the path of a temporary file
+    /// as only argument
+    pub fn with_external_editor(mut self, editor: &str) -> Self {
+        self.external_editor = Some(editor.to_string());
+
+        self
+    }
+
+    /// Execute the line right after it was edited in the external editor instead of
+    /// returning to the prompt to continue editing it (Default: false)
+    pub fn with_external_editor_submit(mut self, submit: bool) -> Self {
+        self.external_editor_submits = submit;
+
+        self
+    }
+
+    /// Disables editing the line in an external editor
+    pub fn with_external_editor_disabled(mut self) -> Self {
+        self.external_editor = None;
+
+        self
+    }
+
+    /// Save the line Ctrl-C discards instead of losing it, pressing `key_code` with
+    /// `modifier` brings it back, e.g.
+    /// `with_line_stash(KeyModifiers::ALT, KeyCode::Char('u'))`. Ctrl-C on an empty line
+    /// keeps the line saved before
+    pub fn with_line_stash(mut self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        self.keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('c'),
+            stash::stash_event(),
+        );
+        self.keybindings
+            .add_binding(modifier, key_code, stash::restore_event());
+        self.line_stash = Some(LineStash::default());
+
+        self
+    }
+
+    /// Disables the hints after the cursor: reedline's fish-style history autosuggestions,
+    /// the rest of command examples and the dimmed placeholder of the next argument of a
+    /// command, e.g. `hello <who>`
+    pub fn with_hinter_disabled(mut self) -> Self {
+        self.hinter_enabled = false;
+        self.explicit_settings.insert("hints");
+
+        self
+    }
+
+    /// Adds a reedline keybinding
+    ///
+    /// # Panics
+    ///
+    /// If `comamnd` is an empty [`ReedlineEvent::UntilFound`]
+    pub fn with_keybinding(
+        mut self,
+        modifier: KeyModifiers,
+        key_code: KeyCode,
+        command: ReedlineEvent,
+    ) -> Self {
+        self.keybindings.add_binding(modifier, key_code, command);
+        self.explicit_keybindings.insert((modifier, key_code));
+
+        self
+    }
+
+    /// Find a keybinding based on the modifier and keycode
+    pub fn find_keybinding(
+        &self,
+        modifier: KeyModifiers,
+        key_code: KeyCode,
+    ) -> Option<ReedlineEvent> {
+        self.keybindings.find_binding(modifier, key_code)
+    }
+
+    /// Get assigned keybindings
+    pub fn get_keybindings(&self) -> HashMap<(KeyModifiers, KeyCode), ReedlineEvent> {
+        // keybindings.get_keybindings() cannot be returned directly because KeyCombination is not visible
+        self.keybindings
+            .get_keybindings()
+            .iter()
+            .map(|(key, value)| ((key.modifier, key.key_code), value.clone()))
+            .collect()
+    }
+
+    /// Remove a keybinding
+    ///
+    /// Returns `Some(ReedlineEvent)` if the keycombination was previously bound to a particular [`ReedlineEvent`]
+    pub fn without_keybinding(mut self, modifier: KeyModifiers, key_code: KeyCode) -> Self {
+        self.keybindings.remove_binding(modifier, key_code);
+        self.explicit_keybindings.insert((modifier, key_code));
+
+        self
+    }
+
+    /// Set the layout and colors of the `help` output, see [HelpTemplate]
+    ///
+    /// The `after_help` of a command is shown in its help as EXAMPLES section
+    pub fn with_help_template(mut self, template: HelpTemplate) -> Self {
+        self.help_template = template;
+        self.help_cache.clear();
+        self
+    }
+
+    /// Page help output which is longer than the terminal, waiting for a key press after
+    /// each screen. Defaults to true, output which isn't written to a terminal is never paged
+    pub fn with_help_pager(mut self, enabled: bool) -> Self {
+        self.help_pager = enabled;
+        self
+    }
+
+    /// Wrap help output at the given width instead of the width of the terminal
+    pub fn with_help_width(mut self, width: usize) -> Self {
+        self.help_width = Some(width);
+        self
+    }
+
+    /// Add a prose help page, e.g. about concepts or the syntax of arguments, which is shown
+    /// by `help <topic>` and listed under TOPICS in the help overview. Commands with the same
+    /// name take precedence
+    pub fn with_help_topic(mut self, topic: &str, text: &str) -> Self {
+        self.help_topics
+            .insert(topic.to_string(), text.trim_end().to_string());
+        self
+    }
+
+    /// Add usage examples of the command `name`, e.g.
+    /// `with_examples("connect", &["connect prod --tls", "connect localhost:5432"])`.
+    /// They are shown in the EXAMPLES section of its help and by the `examples <command>`
+    /// built-in command, and hinted while typing a line they start with
+    pub fn with_examples(mut self, name: &str, examples: &[&str]) -> Self {
+        self.examples
+            .entry(name.to_string())
+            .or_default()
+            .extend(examples.iter().map(|example| example.to_string()));
+        self.set_builtin(builtin::examples_command(), true);
+        self.help_cache.clear();
+        self
+    }
+
+    /// Mark the command `name` as deprecated. Its first use in a session prints a warning
+    /// pointing to `replacement`, pass an empty replacement if there is none. The help
+    /// overview shows it dimmed and tagged as deprecated
+    pub fn with_deprecated_command(mut self, name: &str, replacement: &str) -> Self {
+        self.deprecations.deprecate_command(name, replacement);
+        self.help_cache.clear();
+        self
+    }
+
+    /// Mark the argument with the id `arg` of the command `name` as deprecated. Its first
+    /// use in a session prints a warning pointing to `replacement`, pass an empty
+    /// replacement if there is none. The help of the command tags it as deprecated
+    pub fn with_deprecated_arg(mut self, name: &str, arg: &str, replacement: &str) -> Self {
+        self.deprecations.deprecate_arg(name, arg, replacement);
+        self.help_cache.clear();
+        self
+    }
+
+    /// The definition of a command as shown in its help, with the examples added by
+    /// [with_examples](Self::with_examples) and deprecation tags
+    fn help_command<'a>(&self, name: &str, command: &'a Command) -> Cow<'a, Command> {
+        let mut command = Cow::Borrowed(command);
+        if self.examples.contains_key(name) {
+            let examples = self.command_examples(name, &command).join("\n");
+            command = Cow::Owned(command.into_owned().after_help(examples));
+        }
+        let tag = &self.messages.deprecated_tag;
+        if self.deprecations.command(name).is_some() {
+            let about = match command.get_about() {
+                Some(about) => format!("{} {}", about, tag),
+                None => tag.clone(),
+            };
+            command = Cow::Owned(command.into_owned().about(about));
+        }
+        for (arg, _) in self.deprecations.args(name) {
+            if command
+                .get_arguments()
+                .all(|existing| existing.get_id() != arg)
+            {
+                continue;
+            }
+            command = Cow::Owned(command.into_owned().mut_arg(arg, |existing| {
+                let help = match existing.get_help() {
+                    Some(help) => format!("{} {}", help, tag),
+                    None => tag.clone(),
+                };
+                existing.help(help)
+            }));
+        }
+        command
+    }
+
+    /// The examples of a command: the lines of its `after_help` followed by those added by
+    /// [with_examples](Self::with_examples)
+    fn command_examples(&self, name: &str, command: &Command) -> Vec<String> {
+        let mut examples: Vec<String> = command
+            .get_after_help()
+            .map(|after_help| {
+                after_help
+                    .to_string()
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        examples.extend(self.examples.get(name).into_iter().flatten().cloned());
+        examples
+    }
+
+    /// Additionally accept commands on a Unix domain socket at `path` while the interactive
+    /// REPL runs, so external tools can drive the same commands. Every line received is
+    /// executed like an entered line and answered with its output or error message:
+    ///
+    /// ```bash
+    /// echo "status" | nc -U /tmp/myapp.sock
+    /// ```
+    ///
+    /// A command arriving while a line is being typed runs once that line has been
+    /// submitted. On dumb terminals commands are executed after the next entered line.
+    /// A stale socket left by a previous run is replaced, anything else at `path` is
+    /// reported to the error handler and left alone
+    #[cfg(unix)]
+    pub fn with_control_socket(mut self, path: PathBuf) -> Self {
+        self.control_socket = Some(path);
+        self
+    }
+
+    /// Add a command to your REPL
+    pub fn with_command(mut self, command: Command, callback: Callback<Context, E>) -> Self {
+        let name = command.get_name().to_string();
+        self.add_command(ReplCommand::new(&name, command, callback));
+        self
+    }
+
+    /// Add a command to your REPL
+    #[cfg(feature = "async")]
+    pub fn with_command_async(
+        mut self,
+        command: Command,
+        callback: AsyncCallback<Context, E>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        self.add_command(ReplCommand::new_async(&name, command, callback));
+        self
+    }
+
+    /// Add a command to your REPL with an error handler which decides what happens after
+    /// the command failed, see [CommandErrorAction]. The command is executed again at most
+    /// 5 times in a row, after that the error is reported
+    pub fn with_command_and_error_handler(
+        mut self,
+        command: Command,
+        callback: Callback<Context, E>,
+        error_handler: CommandErrorHandler<Context, E>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        let mut definition = ReplCommand::new(&name, command, callback);
+        definition.error_handler = Some(error_handler);
+        self.add_command(definition);
+        self
+    }
+
+    /// Add an async command to your REPL with its own error handler, see
+    /// [with_command_and_error_handler](Repl::with_command_and_error_handler)
+    #[cfg(feature = "async")]
+    pub fn with_command_async_and_error_handler(
+        mut self,
+        command: Command,
+        callback: AsyncCallback<Context, E>,
+        error_handler: CommandErrorHandler<Context, E>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        let mut definition = ReplCommand::new_async(&name, command, callback);
+        definition.error_handler = Some(error_handler);
+        self.add_command(definition);
+        self
+    }
+
+    /// Add a command whose callback additionally receives a [ReplHandle] to control the
+    /// REPL, e.g. to [quit](ReplHandle::quit) it, [change the prompt](ReplHandle::set_prompt)
+    /// or [toggle settings](ReplHandle::set)
+    pub fn with_handle_command(
+        mut self,
+        command: Command,
+        callback: HandleCallback<Context, E>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        self.add_command(ReplCommand::new_with_handle(&name, command, callback));
+        self
+    }
+
+    /// Add an async command whose callback additionally receives a [ReplHandle]
+    #[cfg(feature = "async")]
+    pub fn with_handle_command_async(
+        mut self,
+        command: Command,
+        callback: AsyncHandleCallback<Context, E>,
+    ) -> Self {
+        let name = command.get_name().to_string();
+        self.add_command(ReplCommand::new_async_with_handle(&name, command, callback));
+        self
+    }
+
+    /// Add the commands of a clap derive [Subcommand] enum to your REPL. Names, arguments and
+    /// help are taken from the derive, the parsed enum value is passed to `dispatch`.
+    /// Requires the `derive` feature for the derive macro, see `examples/derive.rs`
+    pub fn with_derived_commands<S>(mut self, dispatch: DerivedCallback<S, Context, E>) -> Self
+    where
+        S: Subcommand + 'static,
+        Context: 'static,
+        E: 'static,
+    {
+        let app = S::augment_subcommands(Command::new(self.name.clone()));
+        for command in app.get_subcommands() {
+            let name = command.get_name().to_string();
+            let callback = Box::new(move |matches: ArgMatches, context: &mut Context| {
+                let command = S::from_arg_matches(&matches).map_err(|err| {
+                    E::from(Error::DerivedCommandError(
+                        matches.subcommand_name().unwrap_or_default().to_string(),
+                        err.to_string(),
+                    ))
+                })?;
+                dispatch(command, context)
+            });
+            self.add_command(ReplCommand::new_derived(&name, command.clone(), callback));
+        }
+        self
+    }
+
+    /// Complete the arguments of the command `name` with the values returned by `completion`,
+    /// in addition to the possible values and options of its clap definition
+    pub fn with_completion(mut self, name: &str, completion: CompletionCallback) -> Self {
+        self.completions
+            .insert(name.to_string(), Arc::new(completion));
+        self
+    }
+
+    /// Complete the arguments of command `name` like [with_completion](Repl::with_completion),
+    /// with the callback additionally reading the Context. Tab completion runs while the
+    /// line is edited, so an owned Context is moved behind a lock shared with the completer,
+    /// see [new_shared](Repl::new_shared)
+    pub fn with_context_completion(
+        mut self,
+        name: &str,
+        completion: ContextCompletionCallback<Context>,
+    ) -> Self
+    where
+        Context: Send + Sync + 'static,
+    {
+        let context = self.context.into_shared();
+        self.context = ContextStore::Shared(Arc::clone(&context));
+        let provider = move |args: &[&str], word: &str| {
+            let context = context.read().unwrap_or_else(PoisonError::into_inner);
+            completion(&context, args, word)
+        };
+        self.completions
+            .insert(name.to_string(), Arc::new(provider));
+        self
+    }
+
+    /// Compute the prompt from the Context before each prompt is shown, so it reflects
+    /// the current state of the application. Replaces the prompt set otherwise
+    pub fn with_prompt_callback(mut self, callback: PromptCallback<Context>) -> Self {
+        self.prompt_callback = Some(callback);
+        self.explicit_settings.insert("prompt");
+
+        self
+    }
+
+    /// Add a command which requires all of the given `capabilities`, see
+    /// [with_capability_resolver](Repl::with_capability_resolver). Without them it is hidden
+    /// from help and completion and rejected when entered
+    pub fn with_command_restricted(
+        mut self,
+        command: Command,
+        callback: Callback<Context, E>,
+        capabilities: &[&str],
+    ) -> Self {
+        let name = command.get_name().to_string();
+        let mut definition = ReplCommand::new(&name, command, callback);
+        definition.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+        self.add_command(definition);
+        self
+    }
+
+    /// Decide which capabilities the current session has. The resolver is asked before
+    /// each prompt and before a restricted command is executed, so capabilities can change
+    /// with the Context, e.g. after a `login` command. Without a resolver no capabilities
+    /// are granted
+    pub fn with_capability_resolver(mut self, resolver: CapabilityResolver<Context>) -> Self {
+        self.capability_resolver = Some(resolver);
+        self.refresh_capabilities();
+        self
+    }
+
+    /// Ask the capability resolver again, updating which restricted commands are shown in
+    /// help and completion
+    pub(crate) fn refresh_capabilities(&mut self) {
+        let granted: HashSet<String> = match self.capability_resolver {
+            Some(resolver) => self.context.with_ref(resolver).into_iter().collect(),
+            None => HashSet::new(),
+        };
+        if granted == self.granted_capabilities {
+            return;
+        }
+        self.granted_capabilities = granted;
+        self.help_cache.clear();
+        for definition in self.commands.values() {
+            if definition.capabilities.is_empty() {
+                continue;
+            }
+            if self.missing_capability(definition).is_none() {
+                self.index
+                    .insert(&definition.name, Arc::clone(&definition.command));
+            } else {
+                self.index.remove(&definition.name);
+            }
+        }
+    }
+
+    /// The first capability required by the command which hasn't been granted
+    fn missing_capability<'a>(&self, definition: &'a ReplCommand<Context, E>) -> Option<&'a str> {
+        definition
+            .capabilities
+            .iter()
+            .find(|capability| !self.granted_capabilities.contains(*capability))
+            .map(String::as_str)
+    }
+
+    /// Reject the command unless the session has all capabilities it requires
+    fn check_capabilities(&mut self, command: &str) -> Result<()> {
+        let restricted = self
+            .commands
+            .get(command)
+            .is_some_and(|definition| !definition.capabilities.is_empty());
+        if !restricted {
+            return Ok(());
+        }
+        self.refresh_capabilities();
+        let definition = &self.commands[command];
+        match self.missing_capability(definition) {
+            Some(capability) => Err(Error::PermissionDenied(
+                command.to_string(),
+                capability.to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Allow the command `name` to run at most `max_calls` times within `period`, e.g. when
+    /// it calls a rate limited API. Further calls fail with an error telling how long to
+    /// wait
+    pub fn with_rate_limit(mut self, name: &str, max_calls: usize, period: Duration) -> Self {
+        self.rate_limits
+            .insert(name.to_string(), RateLimit::new(max_calls, period));
+        self
+    }
+
+    /// Let the command `name` run again only after `cooldown` has passed since it last ran
+    pub fn with_cooldown(self, name: &str, cooldown: Duration) -> Self {
+        self.with_rate_limit(name, 1, cooldown)
+    }
+
+    /// Count a call of the command, failing if its rate limit is reached
+    fn check_rate_limit(&mut self, command: &str) -> Result<()> {
+        match self.rate_limits.get_mut(command) {
+            Some(rate_limit) => rate_limit
+                .acquire()
+                .map_err(|wait| Error::RateLimited(command.to_string(), wait)),
+            None => Ok(()),
+        }
+    }
+
+    /// Update the prompt from the prompt callback
+    pub(crate) fn refresh_prompt(&mut self) {
+        if let Some(callback) = self.prompt_callback {
+            let prompt = self.context.with_ref(callback);
+            self.prompt.update_prefix(&prompt);
+        }
+    }
+
+    /// Add the commands of a [ReplPlugin] to your REPL
+    pub fn with_plugin<P: ReplPlugin<Context, E>>(mut self, plugin: P) -> Self {
+        self.add_plugin(&plugin);
+        self
+    }
+
+    /// Load a [ReplPlugin] from a dynamic library exporting it with
+    /// [declare_plugin](crate::declare_plugin) and add its commands. The library stays
+    /// loaded as long as the Repl exists.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialization code, and the plugin has to be built for
+    /// the same Context and error types with the same compiler and version of this crate.
+    /// Neither can be checked.
+    #[cfg(feature = "plugins")]
+    pub unsafe fn load_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        use crate::plugin::{PluginConstructor, PLUGIN_CONSTRUCTOR};
+        let path = path.as_ref();
+        let plugin_error = |err: libloading::Error| {
+            Error::PluginError(path.display().to_string(), err.to_string())
+        };
+        let library = libloading::Library::new(path).map_err(plugin_error)?;
+        let plugin = {
+            let constructor = library
+                .get::<PluginConstructor<Context, E>>(PLUGIN_CONSTRUCTOR)
+                .map_err(plugin_error)?;
+            constructor()
+        };
+        self.add_plugin(plugin.as_ref());
+        drop(plugin);
+        self.plugin_libraries.push(library);
+        Ok(())
+    }
+
+    /// Limit the work a command of a WebAssembly plugin may do to `fuel`, roughly the number
+    /// of instructions it executes. Applies to modules loaded afterwards
+    /// (Default: 1 000 000 000)
+    #[cfg(feature = "wasm")]
+    pub fn with_wasm_fuel(mut self, fuel: u64) -> Self {
+        self.wasm_fuel = fuel;
+
+        self
+    }
+
+    /// Load a WebAssembly plugin module and add its commands. The module runs sandboxed and
+    /// only reaches the Context through its [WasmCapabilities](crate::WasmCapabilities),
+    /// which also describes the interface a module implements. All arguments of its
+    /// commands are passed to the module as they are. A command running out of the fuel set
+    /// with [with_wasm_fuel](Repl::with_wasm_fuel) fails instead of hanging the REPL
+    #[cfg(feature = "wasm")]
+    pub fn load_wasm_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<()>
+    where
+        Context: crate::WasmCapabilities + 'static,
+        E: 'static,
+    {
+        let (plugin, commands) = crate::wasm::WasmPlugin::load(path.as_ref(), self.wasm_fuel)?;
+        for (name, about) in commands {
+            let command = Command::new(name.clone()).about(about).arg(
+                Arg::new("args")
+                    .num_args(0..)
+                    .trailing_var_arg(true)
+                    .allow_hyphen_values(true),
+            );
+            let plugin = std::rc::Rc::clone(&plugin);
+            let command_name = name.clone();
+            let callback = Box::new(move |matches: ArgMatches, context: &mut Context| {
+                let mut line = vec![command_name.clone()];
+                line.extend(
+                    matches
+                        .subcommand()
+                        .and_then(|(_, args)| args.get_many::<String>("args"))
+                        .into_iter()
+                        .flatten()
+                        .cloned(),
+                );
+                let mut plugin = plugin.borrow_mut();
+                plugin.run(&line, context).map_err(|message| {
+                    E::from(Error::PluginCommandError(
+                        format!("{} ({})", command_name, plugin.name()),
+                        message,
+                    ))
+                })
+            });
+            self.add_command(ReplCommand::new_derived(&name, command, callback));
+        }
+        Ok(())
+    }
+
+    fn add_plugin(&mut self, plugin: &dyn ReplPlugin<Context, E>) {
+        for plugin_command in plugin.commands() {
+            let name = plugin_command.command.get_name().to_string();
+            if let Some(completion) = plugin_command.completion {
+                self.completions.insert(name.clone(), Arc::new(completion));
+            }
+            self.add_command(ReplCommand::new(
+                &name,
+                plugin_command.command,
+                plugin_command.callback,
+            ));
+        }
+    }
+
+    /// Add an argument to every command of your REPL, including commands added later.
+    /// Commands which already define an argument with the same id keep their own.
+    /// The values are part of each command's ArgMatches and are passed to the callback
+    /// registered with [with_on_global_args](Repl::with_on_global_args)
+    pub fn with_global_arg(mut self, arg: Arg) -> Self {
+        for definition in self.commands.values_mut() {
+            Self::add_global_arg(definition.command_mut(), &arg);
+            self.index
+                .insert(&definition.name, Arc::clone(&definition.command));
+        }
+        self.global_args.push(arg);
+        self
+    }
+
+    /// Callback invoked with the ArgMatches of every command before the command itself,
+    /// e.g. to apply a global `--verbose` flag to the Context
+    pub fn with_on_global_args(mut self, callback: GlobalArgsCallback<Context, E>) -> Self {
+        self.global_args_callback = Some(callback);
+        self
+    }
+
+    /// Callback invoked with the name and ArgMatches of every command after its arguments
+    /// were parsed successfully and before its callback, e.g. to convert units, canonicalize
+    /// paths or redact values for all commands in one place. Returning an error aborts the
+    /// command and is handled like an error of the command
+    pub fn with_matches_inspector(mut self, inspector: MatchesInspector<Context, E>) -> Self {
+        self.matches_inspector = Some(inspector);
+        self
+    }
+
+    fn add_global_arg(command: &mut Command, arg: &Arg) {
+        if command
+            .get_arguments()
+            .all(|existing| existing.get_id() != arg.get_id())
+        {
+            *command = std::mem::take(command).arg(arg.clone());
+        }
+    }
+
+    fn add_command(&mut self, mut definition: ReplCommand<Context, E>) {
+        for arg in &self.global_args {
+            Self::add_global_arg(definition.command_mut(), arg);
+        }
+        if self.missing_capability(&definition).is_none() {
+            self.index
+                .insert(&definition.name, Arc::clone(&definition.command));
+        }
+        self.help_cache.clear();
+        if let Some(replaced) = self.commands.insert(definition.name.clone(), definition) {
+            self.duplicate_commands.push(replaced.name);
+        }
+    }
+
+    /// Validate the Repl after configuring it, returning it ready to [run](Repl::run) or
+    /// the first problem found, see [validate](Repl::validate)
+    pub fn build(self) -> Result<Self> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Check the registered commands for problems that otherwise go unnoticed: a name
+    /// registered twice (the last command replaces the others), a command named `help` or
+    /// like an enabled built-in, which it shadows, and arguments clashing with each other,
+    /// the global arguments or the `--help` and `--version` flags, e.g. the same short flag
+    /// used twice. In debug builds the assertions clap runs when a command is executed
+    /// are checked as well, release builds skip them
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = self.duplicate_commands.first() {
+            return Err(Error::DuplicateCommand(name.clone()));
+        }
+        let mut names: Vec<&String> = self.commands.keys().collect();
+        names.sort();
+        for name in names {
+            if name == "help" || self.builtins.contains_key(name) {
+                return Err(Error::ReservedCommandName(name.clone()));
+            }
+            guard::check_definition(&self.commands[name].command)
+                .map_err(|message| Error::InvalidCommandDefinition(name.clone(), message))?;
+        }
+        Ok(())
+    }
+
+    fn show_help(&mut self, args: &[&str]) -> Result<()> {
+        let width = self.help_width();
+        let key = (args.first().copied().unwrap_or_default().to_string(), width);
+        let help_string = match self.help_cache.get(&key) {
+            Some(help_string) => help_string.clone(),
+            None => match self.render_help(args.first().copied(), width)? {
+                Some(help_string) => {
+                    self.help_cache.insert(key, help_string.clone());
+                    help_string
+                }
+                None => {
+                    return self
+                        .output
+                        .eprintln(&messages::fill(&self.messages.help_not_found, &[args[0]]))
+                }
+            },
+        };
+        self.print_help(&help_string)
+    }
+
+    /// The help overview, or the help of a command or topic, None if there is no such
+    /// command or topic
+    pub(crate) fn render_help(
+        &self,
+        name: Option<&str>,
+        width: Option<usize>,
+    ) -> Result<Option<String>> {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                let builtins: Vec<&Command> = self
+                    .builtins
+                    .iter()
+                    .filter(|(name, _)| !self.commands.contains_key(*name))
+                    .map(|(_, builtin)| builtin)
+                    .collect();
+                let commands: Vec<&Command> = self
+                    .commands
+                    .values()
+                    .filter(|definition| self.missing_capability(definition).is_none())
+                    .map(|definition| definition.command.as_ref())
+                    .collect();
+                let deprecated: Vec<&str> = commands
+                    .iter()
+                    .map(|command| command.get_name())
+                    .filter(|name| self.deprecations.command(name).is_some())
+                    .collect();
+                let overview = Overview {
+                    name: &self.name,
+                    version: &self.version,
+                    description: &self.description,
+                    commands: &commands,
+                    builtins: &builtins,
+                    topics: &self.help_topics,
+                    deprecated: &deprecated,
+                };
+                return Ok(Some(self.help_template.render_overview(
+                    &overview,
+                    &self.messages,
+                    width,
+                )));
+            }
+        };
+        let command = match self.commands.get(name) {
+            Some(definition) if self.missing_capability(definition).is_some() => None,
+            Some(definition) => Some(definition.command.as_ref()),
+            None => self.builtins.get(name),
+        };
+        match command {
+            Some(command) => self
+                .help_template
+                .render_command(&self.help_command(name, command), &self.messages, width)
+                .map(Some)
+                .map_err(|err| Error::HelpError(err.to_string())),
+            None => Ok(self.help_topics.get(name).cloned()),
+        }
+    }
+
+    fn help_width(&self) -> Option<usize> {
+        self.help_width.or_else(|| {
+            if self.output.is_terminal() {
+                pager::terminal_size().map(|(columns, _)| columns)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Print help through the pager if it doesn't fit on the terminal
+    fn print_help(&mut self, help: &str) -> Result<()> {
+        let rows = match pager::terminal_size() {
+            Some((_, rows))
+                if self.help_pager
+                    && self.output_capture.is_none()
+                    && self.output.is_terminal()
+                    && std::io::stdin().is_tty() =>
+            {
+                rows
+            }
+            _ => return self.print_output(help),
+        };
+        pager::page(&self.output, help, rows)?;
+        self.record_output(help)
+    }
+
+    /// Print the help and version output of clap, parse errors are returned as
+    /// [Error::InvalidArguments] for the error handlers
+    fn handle_clap_error(&mut self, command: &str, err: clap::Error) -> Result<()> {
+        let rendered = err.render().to_string();
+        let rendered = rendered.trim_end();
+        if err.use_stderr() {
+            Err(Error::InvalidArguments(
+                command.to_string(),
+                rendered
+                    .strip_prefix("error: ")
+                    .unwrap_or(rendered)
+                    .to_string(),
+            ))
+        } else {
+            self.print_help(rendered)
+        }
+    }
+
+    fn command_error_handler(&self, command: &str) -> Option<CommandErrorHandler<Context, E>> {
+        self.commands
+            .get(command)
+            .and_then(|definition| definition.error_handler)
+    }
+
+    /// Lets the error handler of the command decide what happens after it failed. Returns
+    /// true to execute the command again and the error if it is to be reported
+    fn retry_command(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        error: E,
+        retries: &mut u32,
+    ) -> core::result::Result<bool, E> {
+        let handler = match self.command_error_handler(command) {
+            Some(handler) if *retries < MAX_COMMAND_RETRIES => handler,
+            _ => return Err(error),
+        };
+        let action = handler(&error, &mut self.context.lock())?;
+        let retry = match action {
+            CommandErrorAction::Report => return Err(error),
+            CommandErrorAction::Ignore => false,
+            CommandErrorAction::Retry => true,
+            CommandErrorAction::AskRetry(question) if self.is_local_terminal() => {
+                self.confirm(&question)
+            }
+            // the next line answers it, the prompt of sessions shows (y/N) until then
+            CommandErrorAction::AskRetry(question) => {
+                self.output.println(&question)?;
+                let args = args.iter().map(|arg| arg.to_string()).collect();
+                self.question = Some(Question::Retry(command.to_string(), args));
+                false
+            }
+        };
+        *retries += 1;
+        Ok(retry)
+    }
+
+    /// Returns the command to execute for the given name, which is the suggested command
+    /// if the user confirmed to run it instead of a mistyped one
+    fn resolve_command(&self, command: &str) -> Result<String> {
+        if command == "help"
+            || self.commands.contains_key(command)
+            || self.builtins.contains_key(command)
+        {
+            return Ok(command.to_string());
+        }
+        let candidates = self
+            .commands
+            .values()
+            .filter(|definition| self.missing_capability(definition).is_none())
+            .map(|definition| &definition.name)
+            .chain(self.builtins.keys())
+            .map(String::as_str)
+            .chain(std::iter::once("help"));
+        match suggest::did_you_mean(command, candidates) {
+            Some(suggestion)
+                if self.confirm_suggestions
+                    && self.is_local_terminal()
+                    && self.confirm(&messages::fill(
+                        &self.messages.run_suggestion,
+                        &[command, suggestion],
+                    )) =>
+            {
+                Ok(suggestion.to_string())
+            }
+            Some(suggestion) => Err(Error::UnknownCommandDidYouMean(
+                command.to_string(),
+                suggestion.to_string(),
+                messages::fill(
+                    &self.messages.unknown_command_did_you_mean,
+                    &[command, suggestion],
+                ),
+            )),
+            None => Err(self.unknown_command(command)),
+        }
+    }
+
+    /// The error for an unknown command, with the text from the messages of the Repl
+    fn unknown_command(&self, command: &str) -> Error {
+        Error::UnknownCommand(
+            command.to_string(),
+            messages::fill(&self.messages.unknown_command, &[command]),
+        )
+    }
+
+    fn handle_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        let started = Instant::now();
+        let result = self.dispatch_command(command, args);
+        self.record_stats(command, started.elapsed(), result.is_err());
+        #[cfg(feature = "audit")]
+        self.audit(command, args, started, &result)?;
+        result
+    }
+
+    fn dispatch_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        let command = self.resolve_command(command)?;
+        let command = command.as_str();
+        self.check_capabilities(command)?;
+        self.handle.set_parent(self.parent_repl());
+        self.check_rate_limit(command)?;
+        #[cfg(feature = "clipboard")]
+        if command != "copy-last" {
+            self.command_output.clear();
+        }
+        let mut retries = 0;
+        loop {
+            match self.execute_command(command, args) {
+                Err(error) => {
+                    if !self.retry_command(command, args, error, &mut retries)? {
+                        return Ok(());
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn execute_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
+        let ask_missing_args = self.prompt_missing_args && self.is_local_terminal();
+        match self.commands.get_mut(command) {
+            Some(definition) => {
+                let mut argv: Vec<&str> = vec![command];
+                argv.extend(args);
+                let parsed = if ask_missing_args {
+                    definition.try_get_matches_asking(argv, &self.output)
+                } else {
+                    definition.try_get_matches(argv)
+                };
+                match parsed {
+                    Ok(matches) => {
+                        if let Some(undo) = &mut self.undo {
+                            undo.record_step(&self.context.lock());
+                        }
+                        if let Some(callback) = self.global_args_callback {
+                            callback(
+                                definition.command_matches(&matches),
+                                &mut self.context.lock(),
+                            )?;
+                        }
+                        if let Some(inspector) = self.matches_inspector {
+                            inspector(
+                                command,
+                                definition.command_matches(&matches),
+                                &mut self.context.lock(),
+                            )?;
+                        }
+                        let used = self
+                            .deprecations
+                            .take_used(command, definition.command_matches(&matches));
+                        for (name, replacement) in used {
+                            let warning = deprecation::warning(&self.messages, &name, &replacement);
+                            self.output
+                                .eprintln(&self.theme.warning.paint(warning).to_string())?;
+                        }
+                        #[cfg(feature = "capture")]
+                        let capture = self.capture_stdio.then(StdioCapture::start).flatten();
+                        let result = guard::catch_panic(self.catch_panics, command, || {
+                            if let Some(handle_callback) = definition.handle_callback {
+                                handle_callback(matches, &mut self.context.lock(), &mut self.handle)
+                            } else if let Some(derived_callback) = &definition.derived_callback {
+                                derived_callback(matches, &mut self.context.lock())
+                            } else {
+                                (definition
+                                    .callback
+                                    .expect("Must be filled for sync commands"))(
+                                    matches,
+                                    &mut self.context.lock(),
+                                )
+                            }
+                        });
+                        #[cfg(feature = "capture")]
+                        self.print_captured(capture)?;
+                        match result {
+                            Ok(Some(value)) => {
+                                self.results.push(&value);
+                                self.print_output(&value)?
+                            }
+                            Ok(None) => (),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Err(err) => self.handle_clap_error(command, err)?,
+                };
+                self.apply_handle_requests()?;
+                self.execute_after_command_callback()?;
+            }
+            None => {
+                if command == "help" {
+                    self.show_help(args)?;
+                } else if self.builtins.contains_key(command) {
+                    self.handle_builtin(command, args)?;
+                } else {
+                    return Err(self.unknown_command(command).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_output(&mut self, output: &str) -> Result<()> {
+        #[cfg(feature = "clipboard")]
+        {
+            if !self.command_output.is_empty() {
+                self.command_output.push('\n');
+            }
+            self.command_output.push_str(output);
+        }
+        match &mut self.output_capture {
+            Some(capture) => capture.push(output.to_string()),
+            None => self.output.println(output)?,
+        }
+        self.record_output(output)
+    }
+
+    fn record_output(&mut self, output: &str) -> Result<()> {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record_output(output) {
+                self.recorder = None;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn record_command(&mut self, line: &str) -> Result<()> {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record_command(line) {
+                self.recorder = None;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_builtin(&mut self, command: &str, args: &[&str]) -> Result<Option<ArgMatches>> {
+        let mut argv: Vec<&str> = vec![command];
+        argv.extend(args);
+        match self
+            .builtins
+            .get_mut(command)
+            .expect("builtin must be registered")
+            .try_get_matches_from_mut(argv)
+        {
+            Ok(matches) => Ok(Some(matches)),
+            Err(err) => {
+                self.handle_clap_error(command, err)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn handle_builtin(&mut self, command: &str, args: &[&str]) -> Result<()> {
+        let matches = match self.parse_builtin(command, args)? {
+            Some(matches) => matches,
+            None => return Ok(()),
+        };
+        match command {
+            "record" => self.builtin_record(&matches),
+            "session" => self.builtin_session(&matches),
+            "alias" => self.builtin_alias(&matches),
+            "set" => {
+                let name = matches.get_one::<String>("option").expect("required arg");
+                let value = matches.get_one::<String>("value").expect("required arg");
+                self.apply_setting(name, value)?;
+                #[cfg(feature = "config")]
+                if matches.get_flag("save") {
+                    let path = self.config_file.as_ref().ok_or_else(|| {
+                        Error::ConfigError(String::new(), "no configuration file".to_string())
+                    })?;
+                    crate::config::save_setting(path, name, value)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "config")]
+            "reload-config" => {
+                self.reload_config()?;
+                self.output.println(&self.messages.config_reloaded)
+            }
+            "checkpoint" => {
+                let undo = self.undo.as_mut().expect("registered with undo history");
+                match matches.get_one::<String>("name") {
+                    Some(name) => {
+                        undo.checkpoint(name, &self.context.lock());
+                        Ok(())
+                    }
+                    None => {
+                        let names: Vec<&str> = undo.checkpoints().map(String::as_str).collect();
+                        if names.is_empty() {
+                            Ok(())
+                        } else {
+                            let output = names.join("\n");
+                            self.print_output(&output)
+                        }
+                    }
+                }
+            }
+            "undo" => {
+                let undo = self.undo.as_mut().expect("registered with undo history");
+                let context = match matches.get_one::<String>("checkpoint") {
+                    Some(name) => undo.rollback(name)?,
+                    None => undo.undo_step()?,
+                };
+                *self.context.lock() = context;
+                Ok(())
+            }
+            "stats" => {
+                if matches.get_flag("reset") {
+                    self.reset_stats();
+                    return Ok(());
+                }
+                let [command, calls, failures, average] = &self.messages.stats_columns;
+                let width = self
+                    .stats
+                    .keys()
+                    .map(|name| width::display_width(name))
+                    .max()
+                    .unwrap_or(0)
+                    .max(width::display_width(command));
+                let mut output = format!(
+                    "{}  {:>6}  {:>8}  {:>10}",
+                    width::pad(command, width),
+                    calls,
+                    failures,
+                    average,
+                );
+                for (name, stats) in &self.stats {
+                    output.push_str(&format!(
+                        "\n{}  {:>6}  {:>8}  {:>10}",
+                        width::pad(name, width),
+                        stats.invocations(),
+                        stats.failures(),
+                        format!("{:.2?}", stats.average_duration()),
+                    ));
+                }
+                self.print_output(&output)
+            }
+            "examples" => {
+                let name = matches.get_one::<String>("command").expect("required arg");
+                let command = match self.commands.get(name.as_str()) {
+                    Some(definition) if self.missing_capability(definition).is_some() => None,
+                    Some(definition) => Some(definition.command.as_ref()),
+                    None => self.builtins.get(name.as_str()),
+                };
+                let examples = match command {
+                    Some(command) => self.command_examples(name, command),
+                    None => {
+                        let message = messages::fill(&self.messages.help_not_found, &[name]);
+                        return self.output.eprintln(&message);
+                    }
+                };
+                if examples.is_empty() {
+                    let message = messages::fill(&self.messages.no_examples, &[name]);
+                    return self.output.eprintln(&message);
+                }
+                let output = examples.join("\n");
+                self.print_output(&output)
+            }
+            "results" => {
+                let output = self
+                    .results
+                    .iter()
+                    .enumerate()
+                    .map(|(index, result)| {
+                        let reference = format!("${}", index + 1);
+                        let indent = format!("\n{}", " ".repeat(reference.len() + 2));
+                        format!(
+                            "{}  {}",
+                            reference,
+                            result.lines().collect::<Vec<_>>().join(&indent)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if output.is_empty() {
+                    return Ok(());
+                }
+                self.print_output(&output)
+            }
+            "version" => {
+                let output = format!("{} {}", self.name, self.version);
+                self.print_output(output.trim_end())
+            }
+            "about" => {
+                let mut output = format!("{} {}", self.name, self.version)
+                    .trim_end()
+                    .to_string();
+                if !self.description.is_empty() {
+                    output.push_str(&format!("\n{}", self.description));
+                }
+                let width = self
+                    .build_info
+                    .iter()
+                    .map(|(key, _)| width::display_width(key) + 1)
+                    .max()
+                    .unwrap_or(0);
+                for (key, value) in &self.build_info {
+                    output.push_str(&format!(
+                        "\n{} {}",
+                        width::pad(&format!("{}:", key), width),
+                        value
+                    ));
+                }
+                self.print_output(&output)
+            }
+            "show" => {
+                let width = Setting::NAMES
+                    .iter()
+                    .map(|name| name.len())
+                    .max()
+                    .unwrap_or(0);
+                let table: Vec<String> = Setting::ALL
+                    .iter()
+                    .map(|&setting| {
+                        format!(
+                            "{:<width$}  {:<3}  {}",
+                            setting.name(),
+                            format_switch(self.setting(setting)),
+                            setting.description(),
+                            width = width
+                        )
+                    })
+                    .collect();
+                self.print_output(&table.join("\n"))
+            }
+            #[cfg(feature = "clipboard")]
+            "copy-last" => clipboard::copy(&self.command_output),
+            #[cfg(feature = "clipboard")]
+            "paste" => {
+                let text = clipboard::paste()?;
+                let lines = text
+                    .lines()
+                    .enumerate()
+                    .map(|(index, line)| (index + 1, line.to_string()))
+                    .collect();
+                self.execute_script_lines("<clipboard>", lines, true, true)
+            }
+            "unalias" => {
+                let name = matches.get_one::<String>("name").expect("required arg");
+                if !self.aliases.remove(name)? {
+                    return Err(Error::UnknownAlias(name.to_string()));
+                }
+                Ok(())
+            }
+            "source" => {
+                let (file, path, lines) = self.read_source_file(&matches)?;
+                self.source_stack.push(path);
+                let result = self.execute_script_lines(&file, lines, false, false);
+                self.source_stack.pop();
+                result
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn setting(&self, setting: Setting) -> bool {
+        match setting {
+            Setting::Timing => self.timing,
+            Setting::Pager => self.help_pager,
+            Setting::QuickCompletions => self.quick_completions,
+            Setting::PartialCompletions => self.partial_completions,
+            Setting::Hints => self.hinter_enabled,
+            Setting::Highlighting => self.highlighter_enabled,
+        }
+    }
+
+    /// Change a setting by name, e.g. from the `set` built-in command
+    fn apply_setting(&mut self, name: &str, value: &str) -> Result<()> {
+        let setting =
+            Setting::from_name(name).ok_or_else(|| Error::UnknownSetting(name.to_string()))?;
+        let value = parse_switch(value)
+            .ok_or_else(|| Error::InvalidSetting(name.to_string(), value.to_string()))?;
+        match setting {
+            Setting::Timing => self.timing = value,
+            Setting::Pager => self.help_pager = value,
+            Setting::QuickCompletions => self.quick_completions = value,
+            Setting::PartialCompletions => self.partial_completions = value,
+            Setting::Hints => self.hinter_enabled = value,
+            Setting::Highlighting => self.highlighter_enabled = value,
+        }
+        if setting.affects_line_editor() {
+            self.rebuild_line_editor = true;
+        }
+        Ok(())
+    }
+
+    /// Replace the line editor of the session after settings changed which it was built with
+    fn refresh_line_editor(&mut self, session: &mut Session) -> Result<()> {
+        if std::mem::take(&mut self.rebuild_line_editor) && session.line_editor.is_some() {
+            // the old editor saves the history when dropped, before the new one loads it
+            session.line_editor = None;
+            session.line_editor = Some(self.build_line_editor()?);
+        }
+        Ok(())
+    }
+
+    fn raw_builtin_input<'a>(&self, command: &str, line: &'a str) -> Option<&'a str> {
+        if builtin::RAW_INPUT_BUILTINS.contains(&command)
+            && self.builtins.contains_key(command)
+            && !self.commands.contains_key(command)
+        {
+            Some(
+                line.split_once(char::is_whitespace)
+                    .map(|(_, input)| input.trim())
+                    .unwrap_or_default(),
+            )
+        } else {
+            None
+        }
+    }
+
+    fn handle_raw_builtin(&mut self, command: &str, input: &str) -> Result<()> {
+        match command {
+            #[cfg(feature = "lua")]
+            "eval" => self.builtin_eval(input),
+            _ => Err(self.unknown_command(&format!("{} {}", command, input))),
+        }
+    }
+
+    #[cfg(feature = "lua")]
+    fn builtin_eval(&mut self, code: &str) -> Result<()> {
+        let lua = self.lua.take().unwrap_or_else(mlua::Lua::new);
+        let result = self.eval_lua(&lua, code);
+        self.lua = Some(lua);
+        match result.map_err(|err| Error::EvalError(err.to_string()))? {
+            Some(output) => self.print_output(&output),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "lua")]
+    fn eval_lua(&mut self, lua: &mlua::Lua, code: &str) -> mlua::Result<Option<String>> {
+        if let Some(callback) = self.lua_init_callback {
+            callback(lua, &mut self.context.lock())?;
+        }
+        lua.scope(|scope| {
+            let run = scope.create_function_mut(|_, line: String| self.eval_lua_command(line))?;
+            lua.globals().set("run", run)?;
+            match lua.load(code).eval::<mlua::Value>()? {
+                mlua::Value::Nil => Ok(None),
+                value => {
+                    let tostring: mlua::Function = lua.globals().get("tostring")?;
+                    Ok(Some(tostring.call(value)?))
+                }
+            }
+        })
+    }
+
+    /// Executes a command for Lua code, returning its output instead of printing it
+    #[cfg(feature = "lua")]
+    fn eval_lua_command(&mut self, line: String) -> mlua::Result<Option<String>> {
+        #[cfg(feature = "async")]
+        if let Some(name) = line.split_whitespace().next() {
+            if let Some(definition) = self.commands.get(name) {
+                if definition.is_async() {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "'{}' is an async command and can't be run from eval",
+                        name
+                    )));
+                }
+            }
+        }
+        self.eval_line(&line)
+            .map_err(|err| mlua::Error::RuntimeError(err.to_string()))
+    }
+
+    fn builtin_alias(&mut self, matches: &ArgMatches) -> Result<()> {
+        let definition = matches
+            .get_many::<String>("definition")
+            .map(|values| {
+                values
+                    .map(|value| {
+                        if value.contains(char::is_whitespace) {
+                            format!("\"{}\"", value)
+                        } else {
+                            value.to_string()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        if definition.is_empty() {
+            let aliases = self.aliases.list()?;
+            if !aliases.is_empty() {
+                let output = aliases
+                    .iter()
+                    .map(|(name, expansion)| format!("alias {} = {}", name, expansion))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                self.print_output(&output)?;
+            }
+        } else if let Some((name, expansion)) = alias::parse_definition(&definition) {
+            self.aliases.define(name, expansion)?;
+        } else {
+            match self.aliases.get(&definition)? {
+                Some(expansion) => {
+                    self.print_output(&format!("alias {} = {}", definition, expansion))?
+                }
+                None => return Err(Error::UnknownAlias(definition)),
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn handle_builtin_async(&mut self, command: &str, args: &[&str]) -> Result<()> {
+        if command != "source" {
+            return self.handle_builtin(command, args);
+        }
+        let matches = match self.parse_builtin(command, args)? {
+            Some(matches) => matches,
+            None => return Ok(()),
+        };
+        let (file, path, lines) = self.read_source_file(&matches)?;
+        self.source_stack.push(path);
+        // boxed because sourced lines may recurse into this function again
+        let result = Box::pin(self.execute_script_lines_async(&file, lines, false, false)).await;
+        self.source_stack.pop();
+        result
+    }
+
+    fn read_source_file(&self, matches: &ArgMatches) -> Result<(String, PathBuf, script::Lines)> {
+        let mut path =
+            script::expand_tilde(matches.get_one::<String>("file").expect("required arg"));
+        if path.is_relative() {
+            if let Some(parent) = self.source_stack.last().and_then(|last| last.parent()) {
+                path = parent.join(path);
+            }
+        }
+        let path = path
+            .canonicalize()
+            .map_err(|err| Error::IoError(path.display().to_string(), err.to_string()))?;
+        let file = path.display().to_string();
+        if self.source_stack.contains(&path) {
+            return Err(Error::RecursiveSource(file));
+        }
+        let lines = script::read_lines(&path)
+            .map_err(|err| Error::IoError(file.clone(), err.to_string()))?;
+        let lines = script::expand(&file, lines)?;
+        Ok((file, path, lines))
+    }
+
+    fn builtin_record(&mut self, matches: &ArgMatches) -> Result<()> {
+        match matches.subcommand() {
+            Some(("start", args)) => {
+                let path =
+                    script::expand_tilde(args.get_one::<String>("file").expect("required arg"));
+                let recorder = Recorder::start(&path, args.get_flag("output"))?;
+                self.print_output(&messages::fill(
+                    &self.messages.recording_started,
+                    &[recorder.path()],
+                ))?;
+                self.recorder = Some(recorder);
+            }
+            Some(("stop", _)) => match self.recorder.take() {
+                Some(recorder) => self.print_output(&messages::fill(
+                    &self.messages.recording_stopped,
+                    &[recorder.path()],
+                ))?,
+                None => self.print_output(&self.messages.not_recording.clone())?,
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn builtin_session(&mut self, matches: &ArgMatches) -> Result<()> {
+        let workspace = self.workspace.as_mut().expect("registered with sessions");
+        match matches.subcommand() {
+            Some(("new", args)) => {
+                let name = args.get_one::<String>("name").expect("required arg");
+                workspace.create(name, &mut self.context.lock())?;
+            }
+            Some(("switch", args)) => {
+                let name = args.get_one::<String>("name").expect("required arg");
+                workspace.switch(name, &mut self.context.lock())?;
+            }
+            Some(("list", _)) => {
+                let output = workspace.list().join("\n");
+                return self.print_output(&output);
+            }
+            _ => return Ok(()),
+        }
+        // the steps to undo belong to the previous session
+        if let Some(undo) = &mut self.undo {
+            undo.clear_steps();
+        }
+        Ok(())
+    }
+
+    /// Apply what a command requested through its [ReplHandle]
+    fn apply_handle_requests(&mut self) -> Result<()> {
+        if let Some(prompt) = self.handle.take_prompt() {
+            self.prompt.update_prefix(&prompt);
+        }
+        for (setting, enabled) in self.handle.take_settings() {
+            self.apply_setting(&setting, format_switch(enabled))?;
+        }
+        Ok(())
+    }
+
+    fn execute_after_command_callback(&mut self) -> core::result::Result<(), E> {
+        if let Some(callback) = self.after_command_callback {
+            let result = callback(&mut self.context.lock());
+            match result {
+                Ok(Some(new_prompt)) => {
+                    self.prompt.update_prefix(&new_prompt);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    self.output.eprintln(&messages::fill(
+                        &self.messages.after_command_failed,
+                        &[&format!("{:?}", err)],
+                    ))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_after_command_callback_async(&mut self) -> core::result::Result<(), E> {
+        self.execute_after_command_callback()?;
+        if let Some(callback) = self.after_command_callback_async {
+            let result = callback(&mut self.context.lock()).await;
+            match result {
+                Ok(new_prompt) => {
+                    if let Some(new_prompt) = new_prompt {
+                        self.prompt.update_prefix(&new_prompt);
+                    }
+                }
+                Err(err) => {
+                    self.output.eprintln(&messages::fill(
+                        &self.messages.after_command_failed,
+                        &[&format!("{:?}", err)],
+                    ))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_lifecycle_callback(
+        &mut self,
+        callback: Option<LifecycleCallback<Context, E>>,
+    ) -> Result<()> {
+        if let Some(callback) = callback {
+            let result = callback(&mut self.context.lock());
+            match result {
+                Ok(Some(output)) => self.print_output(&output)?,
+                Ok(None) => {}
+                Err(err) => self.handle_error(err)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_lifecycle_callback_async(
+        &mut self,
+        callback: Option<LifecycleCallback<Context, E>>,
+        callback_async: Option<AsyncLifecycleCallback<Context, E>>,
+    ) -> Result<()> {
+        self.execute_lifecycle_callback(callback)?;
+        if let Some(callback) = callback_async {
+            let result = callback(&mut self.context.lock()).await;
+            match result {
+                Ok(Some(output)) => self.print_output(&output)?,
+                Ok(None) => {}
+                Err(err) => self.handle_error(err)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_start_callback(&mut self) -> Result<()> {
+        #[cfg(feature = "config")]
+        self.apply_config_file()?;
+        #[cfg(feature = "persistence")]
+        self.restore_context()?;
+        if !self.quiet_start {
+            return self.execute_lifecycle_callback(self.start_callback);
+        }
+        let previous_capture = self.start_capture();
+        let result = self.execute_lifecycle_callback(self.start_callback);
+        self.finish_capture(previous_capture);
+        result
+    }
+
+    fn execute_exit_callback(&mut self) -> Result<()> {
+        self.execute_lifecycle_callback(self.exit_callback)?;
+        #[cfg(feature = "persistence")]
+        self.save_context()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_start_callback_async(&mut self) -> Result<()> {
+        #[cfg(feature = "config")]
+        self.apply_config_file()?;
+        #[cfg(feature = "persistence")]
+        self.restore_context()?;
+        let callbacks = (self.start_callback, self.start_callback_async);
+        if !self.quiet_start {
+            return self
+                .execute_lifecycle_callback_async(callbacks.0, callbacks.1)
+                .await;
+        }
+        let previous_capture = self.start_capture();
+        let result = self
+            .execute_lifecycle_callback_async(callbacks.0, callbacks.1)
+            .await;
+        self.finish_capture(previous_capture);
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_exit_callback_async(&mut self) -> Result<()> {
+        self.execute_lifecycle_callback_async(self.exit_callback, self.exit_callback_async)
+            .await?;
+        #[cfg(feature = "persistence")]
+        self.save_context()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn handle_command_async(
+        &mut self,
+        command: &str,
+        args: &[&str],
+    ) -> core::result::Result<(), E> {
+        let started = Instant::now();
+        let result = self.dispatch_command_async(command, args).await;
+        self.record_stats(command, started.elapsed(), result.is_err());
+        #[cfg(feature = "audit")]
+        self.audit(command, args, started, &result)?;
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn dispatch_command_async(
+        &mut self,
+        command: &str,
+        args: &[&str],
+    ) -> core::result::Result<(), E> {
+        let command = self.resolve_command(command)?;
+        let command = command.as_str();
+        self.check_capabilities(command)?;
+        self.handle.set_parent(self.parent_repl());
+        self.check_rate_limit(command)?;
+        #[cfg(feature = "clipboard")]
+        if command != "copy-last" {
+            self.command_output.clear();
+        }
+        let mut retries = 0;
+        loop {
+            match self.execute_command_async(command, args).await {
+                Err(error) => {
+                    if !self.retry_command(command, args, error, &mut retries)? {
+                        return Ok(());
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_command_async(
+        &mut self,
+        command: &str,
+        args: &[&str],
+    ) -> core::result::Result<(), E> {
+        let ask_missing_args = self.prompt_missing_args && self.is_local_terminal();
+        match self.commands.get_mut(command) {
+            Some(definition) => {
+                let mut argv: Vec<&str> = vec![command];
+                argv.extend(args);
+                let parsed = if ask_missing_args {
+                    definition.try_get_matches_asking(argv, &self.output)
+                } else {
+                    definition.try_get_matches(argv)
+                };
+                match parsed {
+                    Ok(matches) => {
+                        if let Some(undo) = &mut self.undo {
+                            undo.record_step(&self.context.lock());
+                        }
+                        if let Some(callback) = self.global_args_callback {
+                            callback(
+                                definition.command_matches(&matches),
+                                &mut self.context.lock(),
+                            )?;
+                        }
+                        if let Some(inspector) = self.matches_inspector {
+                            inspector(
+                                command,
+                                definition.command_matches(&matches),
+                                &mut self.context.lock(),
+                            )?;
+                        }
+                        let used = self
+                            .deprecations
+                            .take_used(command, definition.command_matches(&matches));
+                        for (name, replacement) in used {
+                            let warning = deprecation::warning(&self.messages, &name, &replacement);
+                            self.output
+                                .eprintln(&self.theme.warning.paint(warning).to_string())?;
+                        }
+                        let catch_panics = self.catch_panics;
+                        #[cfg(feature = "capture")]
+                        let capture = self.capture_stdio.then(StdioCapture::start).flatten();
+                        let result = if let Some(async_callback) = definition.async_callback {
+                            guard::catch_panic_async(
+                                catch_panics,
+                                command,
+                                async_callback(matches, &mut self.context.lock()),
+                            )
+                            .await
+                        } else if let Some(async_handle_callback) = definition.async_handle_callback
+                        {
+                            guard::catch_panic_async(
+                                catch_panics,
+                                command,
+                                async_handle_callback(
+                                    matches,
+                                    &mut self.context.lock(),
+                                    &mut self.handle,
+                                ),
+                            )
+                            .await
+                        } else {
+                            guard::catch_panic(catch_panics, command, || {
+                                if let Some(handle_callback) = definition.handle_callback {
+                                    handle_callback(
+                                        matches,
+                                        &mut self.context.lock(),
+                                        &mut self.handle,
+                                    )
+                                } else if let Some(derived_callback) = &definition.derived_callback
+                                {
+                                    derived_callback(matches, &mut self.context.lock())
+                                } else {
+                                    definition
+                                        .callback
+                                        .expect("Either async or sync callback must be set")(
+                                        matches,
+                                        &mut self.context.lock(),
+                                    )
+                                }
+                            })
+                        };
+                        #[cfg(feature = "capture")]
+                        self.print_captured(capture)?;
+                        match result {
+                            Ok(Some(value)) => {
+                                self.results.push(&value);
+                                self.print_output(&value)?
+                            }
+                            Ok(None) => (),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Err(err) => self.handle_clap_error(command, err)?,
+                };
+                self.apply_handle_requests()?;
+                self.execute_after_command_callback_async().await?;
+            }
+            None => {
+                if command == "help" {
+                    self.show_help(args)?;
+                } else if self.builtins.contains_key(command) {
+                    self.handle_builtin_async(command, args).await?;
+                } else {
+                    return Err(self.unknown_command(command).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Split a line into the command and its arguments, borrowing from the line where
+    /// possible
+    fn parse_line<'a>(&self, line: &'a str) -> (Cow<'a, str>, Vec<Cow<'a, str>>) {
+        let mut args = script::split_words_borrowed(line);
+        let command = if args.is_empty() {
+            Cow::Borrowed("")
+        } else {
+            args.remove(0)
+        };
+        (command, args)
+    }
+
+    /// Process a line and the commands it enqueued with [ReplHandle::enqueue]
+    fn process_line(&mut self, line: &str) -> core::result::Result<(), E> {
+        if let Some(question) = self.question.take() {
+            if let Some((command, args)) = self.answer_question(question, line) {
+                let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+                return self.handle_command(&command, &args);
+            }
+            return Ok(());
+        }
+        self.process_single_line(line, false)?;
+        while let Some(queued) = self.next_queued_line() {
+            if self.echo_queued {
+                self.echo_command(&queued)?;
+            }
+            if let Err(err) = self.process_single_line(&queued, true) {
+                self.handle.clear_queue();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// The next command enqueued by a callback, None once a command requested to quit
+    fn next_queued_line(&mut self) -> Option<String> {
+        if self.handle.is_quitting() {
+            self.handle.clear_queue();
+            return None;
+        }
+        self.handle.dequeue()
+    }
+
+    fn process_single_line(&mut self, line: &str, queued: bool) -> core::result::Result<(), E> {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            let expanded = self.aliases.expand(trimmed)?;
+            let (head, body) = match validator::split_body(&expanded, &self.body_commands) {
+                Some((head, body)) => (head, Some(body)),
+                None => (expanded.as_ref(), None),
+            };
+            let (command, args) = self.parse_line(head);
+            let mut args: Vec<Cow<str>> = args
+                .into_iter()
+                .map(|arg| self.results.substitute(arg))
+                .collect();
+            // the body is passed as it was entered, without substitutions
+            args.extend(body.map(Cow::Owned));
+            // enqueued commands are recorded as part of the command which enqueued them
+            if command != "record" && !queued && self.source_stack.is_empty() {
+                self.record_command(trimmed)?;
+            }
+            let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+            let started = Instant::now();
+            let result = match self.raw_builtin_input(&command, &expanded) {
+                Some(input) => self.handle_raw_builtin(&command, input).map_err(E::from),
+                None => self.handle_command(&command, &args),
+            };
+            self.print_timing(started)?;
+            if let Err(err) = result {
+                self.record_output(&err.to_string())?;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn process_line_async(&mut self, line: &str) -> core::result::Result<(), E> {
+        if let Some(question) = self.question.take() {
+            if let Some((command, args)) = self.answer_question(question, line) {
+                let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+                return self.handle_command_async(&command, &args).await;
+            }
+            return Ok(());
+        }
+        self.process_single_line_async(line, false).await?;
+        while let Some(queued) = self.next_queued_line() {
+            if self.echo_queued {
+                self.echo_command(&queued)?;
+            }
+            if let Err(err) = self.process_single_line_async(&queued, true).await {
+                self.handle.clear_queue();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn process_single_line_async(
+        &mut self,
+        line: &str,
+        queued: bool,
+    ) -> core::result::Result<(), E> {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            let expanded = self.aliases.expand(trimmed)?;
+            let (head, body) = match validator::split_body(&expanded, &self.body_commands) {
+                Some((head, body)) => (head, Some(body)),
+                None => (expanded.as_ref(), None),
+            };
+            let (command, args) = self.parse_line(head);
+            let mut args: Vec<Cow<str>> = args
+                .into_iter()
+                .map(|arg| self.results.substitute(arg))
+                .collect();
+            // the body is passed as it was entered, without substitutions
+            args.extend(body.map(Cow::Owned));
+            if command != "record" && !queued && self.source_stack.is_empty() {
+                self.record_command(trimmed)?;
+            }
+            let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+            let started = Instant::now();
+            let result = match self.raw_builtin_input(&command, &expanded) {
+                Some(input) => self.handle_raw_builtin(&command, input).map_err(E::from),
+                None => self.handle_command_async(&command, &args).await,
+            };
+            self.print_timing(started)?;
+            if let Err(err) = result {
+                self.record_output(&err.to_string())?;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// What Repls created with [ReplHandle::nested] inherit
+    fn parent_repl(&self) -> ParentRepl {
+        ParentRepl {
+            name: self.name.clone(),
+            history: self.history.clone().zip(self.history_capacity),
+            theme: self.theme.clone(),
+            console_mode: self.console_mode,
+        }
+    }
+
+    fn record_stats(&mut self, command: &str, duration: Duration, failed: bool) {
+        // unknown commands would grow the statistics without bound
+        if command == "help"
+            || self.commands.contains_key(command)
+            || self.builtins.contains_key(command)
+        {
+            self.stats
+                .entry(command.to_string())
+                .or_default()
+                .record(duration, failed);
+        }
+    }
+
+    /// Usage statistics of the commands executed so far by name, e.g. to export them for
+    /// telemetry
+    pub fn stats(&self) -> &BTreeMap<String, CommandStats> {
+        &self.stats
+    }
+
+    /// Clear the usage statistics
+    pub fn reset_stats(&mut self) {
+        self.stats.clear();
+    }
+
+    /// Write the entry of an executed command to the audit log
+    #[cfg(feature = "audit")]
+    fn audit(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        started: Instant,
+        result: &core::result::Result<(), E>,
+    ) -> Result<()> {
+        match &mut self.audit_log {
+            Some(audit_log) => audit_log.record(
+                command,
+                args,
+                started.elapsed(),
+                result.as_ref().err().map(ToString::to_string),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn print_timing(&self, started: Instant) -> Result<()> {
+        if self.timing {
+            self.output.println(&messages::fill(
+                &self.messages.elapsed,
+                &[&format!("{:.2?}", started.elapsed())],
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn read_rc_file(&self) -> Result<Option<(String, script::Lines)>> {
+        let path = match &self.rc_file {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let file = path.display().to_string();
+        match script::read_lines(path) {
+            Ok(lines) => Ok(Some((file.clone(), script::expand(&file, lines)?))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::IoError(file, err.to_string())),
+        }
+    }
+
+    fn report_script_error(&mut self, error: Error, abort_on_error: bool) -> Result<()> {
+        if abort_on_error {
+            return Err(error);
+        }
+        self.handle_error(error.into())
+    }
+
+    fn echo_command(&self, line: &str) -> Result<()> {
+        self.output
+            .println(&format!("{}{}", self.prompt.render_plain(), line))
+    }
+
+    fn execute_script_lines(
+        &mut self,
+        file: &str,
+        lines: script::Lines,
+        echo: bool,
+        abort_on_error: bool,
+    ) -> Result<()> {
+        for (line_number, line) in lines {
+            if self.handle.is_quitting() {
+                break;
+            }
+            if echo {
+                self.echo_command(&line)?;
+            }
+            if let Err(err) = self.process_line(&line) {
+                let error = Error::ScriptError(file.to_string(), line_number, err.to_string());
+                self.report_script_error(error, abort_on_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_script_lines_async(
+        &mut self,
+        file: &str,
+        lines: script::Lines,
+        echo: bool,
+        abort_on_error: bool,
+    ) -> Result<()> {
+        for (line_number, line) in lines {
+            if self.handle.is_quitting() {
+                break;
+            }
+            if echo {
+                self.echo_command(&line)?;
+            }
+            if let Err(err) = self.process_line_async(&line).await {
+                let error = Error::ScriptError(file.to_string(), line_number, err.to_string());
+                self.report_script_error(error, abort_on_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_rc_file(&mut self) -> Result<()> {
+        let abort_on_error = self.rc_file_abort_on_error;
+        match self.read_rc_file() {
+            Ok(Some((file, lines))) => {
+                self.execute_script_lines(&file, lines, false, abort_on_error)
+            }
+            Ok(None) => Ok(()),
+            Err(error) => self.report_script_error(error, abort_on_error),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_rc_file_async(&mut self) -> Result<()> {
+        let abort_on_error = self.rc_file_abort_on_error;
+        match self.read_rc_file() {
+            Ok(Some((file, lines))) => {
+                self.execute_script_lines_async(&file, lines, false, abort_on_error)
+                    .await
+            }
+            Ok(None) => Ok(()),
+            Err(error) => self.report_script_error(error, abort_on_error),
+        }
+    }
+
+    /// The rc file and then the init commands
+    fn execute_startup(&mut self) -> Result<()> {
+        self.execute_rc_file()?;
+        for (index, command) in self.init_commands.clone().into_iter().enumerate() {
+            if self.handle.is_quitting() {
+                break;
+            }
+            if self.echo_queued {
+                self.echo_command(&command)?;
+            }
+            if let Err(err) = self.process_line(&command) {
+                let error = Error::InitCommandError(index + 1, command, err.to_string());
+                self.handle_init_failure(error)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn execute_startup_async(&mut self) -> Result<()> {
+        self.execute_rc_file_async().await?;
+        for (index, command) in self.init_commands.clone().into_iter().enumerate() {
+            if self.handle.is_quitting() {
+                break;
+            }
+            if self.echo_queued {
+                self.echo_command(&command)?;
+            }
+            if let Err(err) = self.process_line_async(&command).await {
+                let error = Error::InitCommandError(index + 1, command, err.to_string());
+                self.handle_init_failure(error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_init_failure(&mut self, error: Error) -> Result<()> {
+        match self.init_failure_policy {
+            InitFailurePolicy::Abort => Err(error),
+            InitFailurePolicy::Continue => self.handle_error(error.into()),
+            InitFailurePolicy::PromptUser => {
+                let question = messages::fill(&self.messages.init_continue, &[&error.to_string()]);
+                if self.is_local_terminal() && self.confirm(&question) {
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            }
+        }
+    }
+
+    fn read_script(script_path: &str) -> Result<(String, script::Lines)> {
+        let path = script::expand_tilde(script_path);
+        let file = path.display().to_string();
+        match script::read_lines(&path) {
+            Ok(lines) => Ok((file.clone(), script::expand(&file, lines)?)),
+            Err(err) => Err(Error::IoError(file, err.to_string())),
+        }
+    }
+
+    /// Re-run the commands of a script, e.g. one written by the `record` built-in command
+    /// (see [with_record_command](Repl::with_record_command)). Every command is echoed
+    /// with the prompt before it is executed, recorded output comments are skipped.
+    pub fn replay(&mut self, script_path: &str) -> Result<()> {
+        let (file, lines) = Self::read_script(script_path)?;
+        self.execute_script_lines(&file, lines, true, false)
+    }
+
+    /// Re-run the commands of a script, see [replay](Repl::replay)
+    #[cfg(feature = "async")]
+    pub async fn replay_async(&mut self, script_path: &str) -> Result<()> {
+        let (file, lines) = Self::read_script(script_path)?;
+        self.execute_script_lines_async(&file, lines, true, false)
+            .await
+    }
+
+    fn build_line_editor(&mut self) -> Result<Reedline> {
+        let completer = Box::new(
+            ReplCompleter::new(self.index.clone(), &self.completions)
+                .with_details(self.completion_details)
+                .with_help_description(&self.messages.help_completion),
+        );
+        let completion_menu: Box<dyn Menu> = if self.accessible {
+            Box::new(PlainMenu::new())
+        } else {
+            Box::new(
+                ColumnarMenu::default()
+                    .with_name("completion_menu")
+                    .with_text_style(self.theme.menu_text)
+                    .with_selected_text_style(self.theme.menu_selected)
+                    .with_description_text_style(self.theme.menu_description),
+            )
+        };
+        let validator: Box<dyn Validator> = match &self.validator {
+            Some(validator) => Box::new(validator.clone()),
+            None => Box::new(DefaultValidator),
+        };
+        let validator: Box<dyn Validator> = if self.body_commands.is_empty() {
+            validator
+        } else {
+            Box::new(BodyValidator::new(self.body_commands.clone(), validator))
+        };
+        let emacs = Emacs::new(self.keybindings.clone());
+        let edit_mode: Box<dyn EditMode> = if self.abbreviations.is_empty() {
+            Box::new(emacs)
+        } else {
+            Box::new(AbbreviationEditMode::new(emacs))
+        };
+        let edit_mode: Box<dyn EditMode> = match &self.external_editor {
+            Some(_) => Box::new(ExternalEditorEditMode::new(
+                edit_mode,
+                self.external_editor_submits,
+            )),
+            None => edit_mode,
+        };
+        let edit_mode: Box<dyn EditMode> = if self.paste_confirmation {
+            Box::new(TypedAheadEditMode::new(edit_mode, self.typed_ahead.clone()))
+        } else {
+            edit_mode
+        };
+        let mut line_editor = Reedline::create()
+            .with_edit_mode(edit_mode)
+            .with_completer(completer)
+            .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+                AbbreviationMenu::new(self.abbreviations.clone()),
+            )))
+            .with_validator(validator)
+            .with_partial_completions(self.partial_completions)
+            .with_quick_completions(self.quick_completions);
+        if self.accessible {
+            line_editor = line_editor.with_ansi_colors(false).with_animation(false);
+        }
+        if let Some(stash) = &self.line_stash {
+            line_editor = line_editor
+                .with_menu(ReedlineMenu::EngineCompleter(Box::new(StashMenu::save(
+                    stash.clone(),
+                ))))
+                .with_menu(ReedlineMenu::EngineCompleter(Box::new(StashMenu::restore(
+                    stash.clone(),
+                ))));
+        }
+        if let Some(command) = &self.external_editor {
+            line_editor =
+                line_editor.with_buffer_editor(command.clone(), editor::EXTENSION.to_string());
+        }
+
+        if self.highlighter_enabled && !self.accessible {
+            line_editor = line_editor.with_highlighter(match &self.highlighter {
+                Some(highlighter) => Box::new(highlighter.clone()),
+                None => Box::new(ReplHighlighter::new(
+                    self.index.clone(),
+                    self.theme.highlight,
+                )),
+            });
+        }
+
+        if self.hinter_enabled && !self.accessible {
+            let examples = self.examples.values().flatten().cloned().collect();
+            line_editor = line_editor.with_hinter(Box::new(ReplHinter::new(
+                self.index.clone(),
+                examples,
+                self.hinter_style,
+            )));
+        }
+
+        if let (Some(history_path), Some(capacity)) = (&self.history, self.history_capacity) {
+            if self.history_lazy {
+                let history = LazyHistory::new(
+                    capacity,
+                    history_path.to_path_buf(),
+                    self.history_failure.clone(),
+                );
+                return Ok(line_editor.with_history(Box::new(history)));
+            }
+            match FileBackedHistory::with_file(capacity, history_path.to_path_buf()) {
+                Ok(history) => line_editor = line_editor.with_history(Box::new(history)),
+                Err(err) => {
+                    let error =
+                        Error::HistoryError(history_path.display().to_string(), err.to_string());
+                    self.handle_error(error.into())?;
+                }
+            }
+        }
+
+        Ok(line_editor)
+    }
+
+    fn start_capture(&mut self) -> Option<Vec<String>> {
+        self.output_capture.replace(vec![])
+    }
+
+    fn finish_capture(&mut self, previous_capture: Option<Vec<String>>) -> Option<String> {
+        let output =
+            std::mem::replace(&mut self.output_capture, previous_capture).unwrap_or_default();
+        if output.is_empty() {
+            None
+        } else {
+            Some(output.join("\n"))
+        }
+    }
+
+    /// Execute a single line like the interactive loop does, but return the output instead
+    /// of printing it, and the error instead of passing it to the error handler. This allows
+    /// unit-testing command callbacks and dispatch without a terminal:
+    ///
+    /// ```rust,no_run
+    /// # use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+    /// # use reedline_repl_rs::{Repl, Result};
+    /// # fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+    /// #     Ok(Some(format!("Hello, {}", args.get_one::<String>("who").unwrap())))
+    /// # }
+    /// let mut repl = Repl::new(())
+    ///     .with_command(Command::new("hello").arg(Arg::new("who").required(true)), hello);
+    /// assert_eq!(repl.eval_line("hello Friend")?, Some("Hello, Friend".to_string()));
+    /// # Ok::<(), reedline_repl_rs::Error>(())
+    /// ```
+    pub fn eval_line(&mut self, line: &str) -> core::result::Result<Option<String>, E> {
+        let previous_capture = self.start_capture();
+        let result = self.process_line(line);
+        let output = self.finish_capture(previous_capture);
+        result.map(|_| output)
+    }
+
+    /// Execute a single line and return its output, see [eval_line](Repl::eval_line)
+    #[cfg(feature = "async")]
+    pub async fn eval_line_async(&mut self, line: &str) -> core::result::Result<Option<String>, E> {
+        let previous_capture = self.start_capture();
+        let result = self.process_line_async(line).await;
+        let output = self.finish_capture(previous_capture);
+        result.map(|_| output)
+    }
+
+    /// Start a session on a stream, see [ReplSession](crate::ReplSession). Does nothing
+    /// until the user is logged in, the session passes the entered lines to
+    /// `submit_password` and starts again once that succeeded
+    #[cfg(feature = "session")]
+    pub(crate) async fn begin_stream_session(&mut self) -> Result<()> {
+        if self.login_pending() {
+            return Ok(());
+        }
+        self.write_banner()?;
+        self.execute_start_callback_async().await?;
+        self.execute_startup_async().await?;
+        self.refresh_prompt();
+        self.refresh_capabilities();
+        Ok(())
+    }
+
+    #[cfg(feature = "session")]
+    pub(crate) async fn end_stream_session(&mut self) -> Result<()> {
+        if self.login_pending() {
+            return Ok(());
+        }
+        self.execute_exit_callback_async().await
+    }
+
+    #[cfg(any(feature = "session", feature = "tui"))]
+    pub(crate) fn set_help_width(&mut self, width: Option<usize>) {
+        self.help_width = width;
+    }
+
+    /// Start a session driven by the host application, see [ReplConsole](crate::ReplConsole).
+    /// Does nothing until the user is logged in, like a stream session
+    #[cfg(feature = "tui")]
+    pub(crate) fn begin_embedded_session(&mut self) -> Result<()> {
+        if self.login_pending() {
+            return Ok(());
+        }
+        self.write_banner()?;
+        self.execute_start_callback()?;
+        self.execute_startup()?;
+        self.refresh_prompt();
+        self.refresh_capabilities();
+        Ok(())
+    }
+
+    #[cfg(feature = "tui")]
+    pub(crate) fn end_embedded_session(&mut self) -> Result<()> {
+        if self.login_pending() {
+            return Ok(());
+        }
+        self.execute_exit_callback()
+    }
+
+    /// Completer for the current commands, for line editors other than reedline
+    #[cfg(any(feature = "tui", feature = "bench"))]
+    pub(crate) fn completer(&self) -> ReplCompleter {
+        ReplCompleter::new(self.index.clone(), &self.completions)
+            .with_details(self.completion_details)
+            .with_help_description(&self.messages.help_completion)
+    }
+
+    /// Process a line like the interactive loop does, reporting errors to the error handler
+    pub(crate) fn execute_line(&mut self, line: String) -> Result<()> {
+        if let Err(err) = self.process_line(&line) {
+            self.handle_error(err)?;
+        }
+        self.confirm_quit();
+        self.refresh_prompt();
+        self.refresh_capabilities();
+        Ok(())
+    }
+
+    /// Process a line like the interactive loop does, reporting errors to the error handler
+    #[cfg(feature = "async")]
+    pub(crate) async fn execute_line_async(&mut self, line: String) -> Result<()> {
+        if let Err(err) = self.process_line_async(&line).await {
+            self.handle_error(err)?;
+        }
+        self.confirm_quit();
+        self.refresh_prompt();
+        self.refresh_capabilities();
+        Ok(())
+    }
+
+    /// The prompt of sessions without line editing, the login prompt until the user is
+    /// logged in and (y/N) while a question waits for its answer
+    pub(crate) fn prompt_text(&self) -> String {
+        if self.login_pending() {
+            return self.messages.login_prompt.clone();
+        }
+        if self.question.is_some() {
+            return "(y/N) ".to_string();
+        }
+        self.prompt.render_plain()
+    }
+
+    pub(crate) fn output_mut(&mut self) -> &mut Output {
+        &mut self.output
+    }
+
+    /// Run a session reading lines from `input` and writing to the given writers, with
+    /// prompts but without line editing, e.g. for a network connection
+    #[cfg(feature = "serve")]
+    pub(crate) fn run_line_session(
+        &mut self,
+        mut input: impl std::io::BufRead,
+        stdout: Box<dyn Write + Send>,
+        stderr: Box<dyn Write + Send>,
+    ) -> Result<()> {
+        self.output.set_stdout(stdout);
+        self.output.set_stderr(stderr);
+        if !self.login(|repl| repl.read_input_password(&mut input))? {
+            return Ok(());
+        }
+        self.execute_start_callback()?;
+        let result = self.read_eval_lines(&mut input);
+        self.execute_exit_callback()?;
+        result
+    }
+
+    #[cfg(feature = "serve")]
+    fn read_eval_lines(&mut self, input: &mut impl std::io::BufRead) -> Result<()> {
+        self.print_banner()?;
+        self.execute_startup()?;
+        while !self.handle.is_quitting() {
+            self.output.print(&self.prompt_text())?;
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => return Err(Error::IoError("<input>".to_string(), err.to_string())),
             }
-            None => {
-                if command == "help" {
-                    self.show_help(args)?;
-                } else {
-                    return Err(Error::UnknownCommand(command.to_string()).into());
-                }
+            if let Err(err) = self.process_line(&line) {
+                self.handle_error(err)?;
             }
+            self.confirm_quit();
+        }
+        Ok(())
+    }
+
+    /// Reads the password as line from the input of a served connection, where it can't be
+    /// hidden
+    #[cfg(feature = "serve")]
+    fn read_input_password(&mut self, input: &mut impl std::io::BufRead) -> Result<Option<String>> {
+        self.output.print(&self.messages.login_prompt)?;
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+            Err(err) => Err(Error::IoError("<input>".to_string(), err.to_string())),
         }
+    }
+
+    fn read_stdin_line() -> Result<Option<String>> {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line)),
+            Err(err) => Err(Error::IoError("<stdin>".to_string(), err.to_string())),
+        }
+    }
+
+    /// Machine mode for editor plugins and GUIs: reads one JSON request per line from stdin
+    /// and writes one JSON response per line to the output, instead of using the terminal.
+    ///
+    /// ```text
+    /// {"id": 1, "cmd": "add", "args": ["1", 2]}
+    /// {"id":1,"ok":true,"output":"3"}
+    /// ```
+    ///
+    /// `id` is optional and returned as is. Failed commands are answered with
+    /// `"ok": false` and the error message in `error`. Output of the start and exit
+    /// callbacks is discarded to keep the output parseable.
+    ///
+    /// With a [login callback](Repl::with_login) every request is refused until a
+    /// `{"cmd": "login", "args": ["<password>"]}` request lets the user in
+    #[cfg(feature = "json")]
+    pub fn run_json(&mut self) -> Result<()> {
+        if !self.login_json()? {
+            return Ok(());
+        }
+        let previous_capture = self.start_capture();
+        self.execute_start_callback()?;
+        self.finish_capture(previous_capture);
+        let result = self.read_eval_json();
+        let previous_capture = self.start_capture();
+        self.execute_exit_callback()?;
+        self.finish_capture(previous_capture);
+        result
+    }
 
+    #[cfg(feature = "json")]
+    fn read_eval_json(&mut self) -> Result<()> {
+        while !self.handle.is_quitting() {
+            let line = match Self::read_stdin_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match json::parse_request(&line) {
+                Ok(request) => {
+                    let args: Vec<&str> = request.args.iter().map(String::as_str).collect();
+                    let previous_capture = self.start_capture();
+                    let result = self.handle_command(&request.command, &args);
+                    let output = self.finish_capture(previous_capture);
+                    self.json_response(request.id, result, output)
+                }
+                Err((id, message)) => json::failure(id, &message),
+            };
+            self.output.println(&response)?;
+        }
         Ok(())
     }
 
-    fn parse_line(&self, line: &str) -> (String, Vec<String>) {
-        let r = regex::Regex::new(r#"("[^"\n]+"|[\S]+)"#).unwrap();
-        let mut args = r
-            .captures_iter(line)
-            .map(|a| a[0].to_string().replace('\"', ""))
-            .collect::<Vec<String>>();
-        let command: String = args.drain(..1).collect();
-        (command, args)
+    /// Machine mode reading JSON requests from stdin, see [run_json](Repl::run_json)
+    #[cfg(all(feature = "json", feature = "async"))]
+    pub async fn run_json_async(&mut self) -> Result<()> {
+        if !self.login_json()? {
+            return Ok(());
+        }
+        let previous_capture = self.start_capture();
+        self.execute_start_callback_async().await?;
+        self.finish_capture(previous_capture);
+        let result = self.read_eval_json_async().await;
+        let previous_capture = self.start_capture();
+        self.execute_exit_callback_async().await?;
+        self.finish_capture(previous_capture);
+        result
     }
 
-    fn process_line(&mut self, line: String) -> core::result::Result<(), E> {
-        let trimmed = line.trim();
-        if !trimmed.is_empty() {
-            let (command, args) = self.parse_line(trimmed);
-            let args = args.iter().fold(vec![], |mut state, a| {
-                state.push(a.as_str());
-                state
-            });
-            self.handle_command(&command, &args)?;
+    #[cfg(all(feature = "json", feature = "async"))]
+    async fn read_eval_json_async(&mut self) -> Result<()> {
+        while !self.handle.is_quitting() {
+            let line = match Self::read_stdin_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match json::parse_request(&line) {
+                Ok(request) => {
+                    let args: Vec<&str> = request.args.iter().map(String::as_str).collect();
+                    let previous_capture = self.start_capture();
+                    let result = self.handle_command_async(&request.command, &args).await;
+                    let output = self.finish_capture(previous_capture);
+                    self.json_response(request.id, result, output)
+                }
+                Err((id, message)) => json::failure(id, &message),
+            };
+            self.output.println(&response)?;
+        }
+        Ok(())
+    }
+
+    /// Answers requests until a `login` request passes a password the login callback
+    /// accepts, returns false if it never did
+    #[cfg(feature = "json")]
+    fn login_json(&mut self) -> Result<bool> {
+        while self.login_pending() && !self.handle.is_quitting() {
+            let line = match Self::read_stdin_line()? {
+                Some(line) => line,
+                None => {
+                    self.handle.exit(1);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match json::parse_request(&line) {
+                Ok(request) if request.command == "login" => {
+                    let password = request.args.first().map(String::as_str).unwrap_or_default();
+                    match self.attempt_login(password) {
+                        Ok(true) => json::success(request.id, None),
+                        Ok(false) if self.handle.is_quitting() => {
+                            json::failure(request.id, &self.messages.login_failed)
+                        }
+                        Ok(false) => json::failure(request.id, &self.messages.login_denied),
+                        Err(err) => json::failure(request.id, &err.to_string()),
+                    }
+                }
+                Ok(request) => json::failure(request.id, &self.messages.login_required),
+                Err((id, message)) => json::failure(id, &message),
+            };
+            self.output.println(&response)?;
+        }
+        Ok(!self.login_pending())
+    }
+
+    #[cfg(feature = "json")]
+    fn json_response(
+        &mut self,
+        id: serde_json::Value,
+        result: core::result::Result<(), E>,
+        output: Option<String>,
+    ) -> String {
+        match result {
+            Ok(()) => json::success(id, output),
+            Err(err) => {
+                self.handle.fail();
+                json::failure(id, &err.to_string())
+            }
+        }
+    }
+
+    /// Executes commands read line by line from a non-interactive stdin (e.g. a pipe),
+    /// printing their results without prompts
+    fn run_non_interactive(&mut self) -> Result<()> {
+        self.execute_start_callback()?;
+        let result = self.read_eval_stdin();
+        self.execute_exit_callback()?;
+        result
+    }
+
+    fn read_eval_stdin(&mut self) -> Result<()> {
+        self.execute_startup()?;
+        while !self.handle.is_quitting() {
+            let line = match Self::read_stdin_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            if let Err(err) = self.process_line(&line) {
+                self.handle.fail();
+                self.handle_error(err)?;
+            }
         }
         Ok(())
     }
 
     #[cfg(feature = "async")]
-    async fn process_line_async(&mut self, line: String) -> core::result::Result<(), E> {
-        let trimmed = line.trim();
-        if !trimmed.is_empty() {
-            let (command, args) = self.parse_line(trimmed);
-            let args = args.iter().fold(vec![], |mut state, a| {
-                state.push(a.as_str());
-                state
-            });
-            self.handle_command_async(&command, &args).await?;
+    async fn run_non_interactive_async(&mut self) -> Result<()> {
+        self.execute_start_callback_async().await?;
+        let result = self.read_eval_stdin_async().await;
+        self.execute_exit_callback_async().await?;
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn read_eval_stdin_async(&mut self) -> Result<()> {
+        self.execute_startup_async().await?;
+        while !self.handle.is_quitting() {
+            let line = match Self::read_stdin_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            if let Err(err) = self.process_line_async(&line).await {
+                self.handle.fail();
+                self.handle_error(err)?;
+            }
         }
         Ok(())
     }
 
-    fn build_line_editor(&mut self) -> Result<Reedline> {
-        let mut valid_commands: Vec<String> = self
+    /// Split command line arguments into the program name and the command
+    fn split_cli_args<I, T>(args: I) -> (String, Vec<String>)
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let mut args = args.into_iter().map(Into::into);
+        let bin_name = args
+            .next()
+            .map(|program| {
+                Path::new(&program)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or(program)
+            })
+            .unwrap_or_default();
+        let mut args: Vec<String> = args.collect();
+        if let Some(first) = args.first_mut() {
+            if first == "--help" || first == "-h" {
+                *first = "help".to_string();
+            }
+        }
+        (bin_name, args)
+    }
+
+    /// Print a completion script if the command line is `completions <shell>`, returns
+    /// false for any other command line
+    #[cfg(feature = "completions")]
+    fn print_completions(&mut self, bin_name: &str, args: &[String]) -> Result<bool> {
+        if args[0] != completions::COMPLETIONS_COMMAND
+            || self.commands.contains_key(completions::COMPLETIONS_COMMAND)
+        {
+            return Ok(false);
+        }
+        let shell = match args.get(1) {
+            Some(shell) => shell,
+            None => {
+                return Err(Error::MissingRequiredArgument(
+                    completions::COMPLETIONS_COMMAND.to_string(),
+                    "shell".to_string(),
+                ))
+            }
+        };
+        let commands = self
             .commands
-            .iter()
-            .map(|(_, command)| command.name.clone())
-            .collect();
-        valid_commands.push("help".to_string());
-        let completer = Box::new(ReplCompleter::new(&self.commands));
-        let completion_menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
-        let validator = Box::new(DefaultValidator);
-        let mut line_editor = Reedline::create()
-            .with_edit_mode(Box::new(Emacs::new(self.keybindings.clone())))
-            .with_completer(completer)
-            .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
-            .with_highlighter(Box::new(ExampleHighlighter::new(valid_commands.clone())))
-            .with_validator(validator)
-            .with_partial_completions(self.partial_completions)
-            .with_quick_completions(self.quick_completions);
+            .values()
+            .map(|definition| definition.command.as_ref())
+            .chain(self.builtins.values());
+        let bin_name = if bin_name.is_empty() {
+            self.name.as_str()
+        } else {
+            bin_name
+        };
+        let script = completions::script(shell, bin_name, commands)?;
+        self.output.print(&script)?;
+        Ok(true)
+    }
 
-        if self.hinter_enabled {
-            line_editor = line_editor.with_hinter(Box::new(
-                DefaultHinter::default().with_style(self.hinter_style),
-            ));
+    /// Execute a single command given on the command line, or the interactive REPL if
+    /// no arguments were given. The first item is expected to be the program name, so
+    /// `std::env::args()` can be passed as is:
+    ///
+    /// - `myapp status --all` runs the registered `status` command and returns
+    /// - `myapp` starts the REPL as [run](Repl::run) does
+    /// - with the `completions` feature, `myapp completions zsh` prints a completion script
+    ///   for the given shell, unless a `completions` command is registered
+    pub fn run_with_args<I, T>(&mut self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        #[allow(unused_variables)]
+        let (bin_name, args) = Self::split_cli_args(args);
+        if args.is_empty() {
+            return self.run();
+        }
+        #[cfg(feature = "completions")]
+        if self.print_completions(&bin_name, &args)? {
+            return Ok(());
+        }
+        if !self.login(Self::read_password)? {
+            return Ok(());
+        }
+        self.execute_start_callback()?;
+        let result = self.execute_args(&args);
+        self.execute_exit_callback()?;
+        result
+    }
+
+    fn execute_args(&mut self, args: &[String]) -> Result<()> {
+        self.execute_startup()?;
+        let argv: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        if let Err(err) = self.handle_command(argv[0], &argv[1..]) {
+            self.handle.fail();
+            self.handle_error(err)?;
         }
+        Ok(())
+    }
 
-        if let Some(history_path) = &self.history {
-            let capacity = self.history_capacity.unwrap();
-            let history =
-                FileBackedHistory::with_file(capacity, history_path.to_path_buf()).unwrap();
-            line_editor = line_editor.with_history(Box::new(history));
+    /// Execute a single command given on the command line, or the interactive REPL if
+    /// no arguments were given. See [run_with_args](Repl::run_with_args)
+    #[cfg(feature = "async")]
+    pub async fn run_with_args_async<I, T>(&mut self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        #[allow(unused_variables)]
+        let (bin_name, args) = Self::split_cli_args(args);
+        if args.is_empty() {
+            return self.run_async().await;
         }
+        #[cfg(feature = "completions")]
+        if self.print_completions(&bin_name, &args)? {
+            return Ok(());
+        }
+        if !self.login(Self::read_password)? {
+            return Ok(());
+        }
+        self.execute_start_callback_async().await?;
+        let result = self.execute_args_async(&args).await;
+        self.execute_exit_callback_async().await?;
+        result
+    }
 
-        Ok(line_editor)
+    #[cfg(feature = "async")]
+    async fn execute_args_async(&mut self, args: &[String]) -> Result<()> {
+        self.execute_startup_async().await?;
+        let argv: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        if let Err(err) = self.handle_command_async(argv[0], &argv[1..]).await {
+            self.handle.fail();
+            self.handle_error(err)?;
+        }
+        Ok(())
+    }
+
+    /// The exit code set by commands through [ReplHandle::exit], or 1 if a command failed
+    /// when executed from the command line or from piped input
+    pub fn exit_code(&self) -> i32 {
+        self.handle.exit_code()
+    }
+
+    fn exit_code_of(&self, result: Result<()>) -> i32 {
+        match result {
+            Ok(()) => self.exit_code(),
+            Err(err) => {
+                let _ = self.output.eprintln(&err.to_string());
+                1
+            }
+        }
+    }
+
+    /// Execute REPL and return its exit code, to be passed to `std::process::exit` by
+    /// wrapper binaries. Fatal errors are printed and result in exit code 1
+    pub fn run_with_exit_code(&mut self) -> i32 {
+        let result = self.run();
+        self.exit_code_of(result)
+    }
+
+    /// Execute REPL and return its exit code, see [run_with_exit_code](Repl::run_with_exit_code)
+    #[cfg(feature = "async")]
+    pub async fn run_with_exit_code_async(&mut self) -> i32 {
+        let result = self.run_async().await;
+        self.exit_code_of(result)
     }
 
     /// Execute REPL
+    ///
+    /// If stdin is not a terminal (e.g. `echo "status" | myapp`) the commands are read
+    /// line by line from stdin instead and no prompt or banner is printed.
+    ///
+    /// On Unix SIGTERM and SIGHUP end the REPL gracefully: the terminal is restored, the
+    /// history saved and the exit callback called, also while a line is being edited. The
+    /// exit code is 128 + signal number.
+    /// Ctrl-Z suspends the REPL, `fg` resumes it.
+    ///
+    /// On dumb terminals (`TERM=dumb` or no raw mode support) lines are read without line
+    /// editing, highlighting or completion.
     pub fn run(&mut self) -> Result<()> {
+        if !self.login(Self::read_password)? {
+            return Ok(());
+        }
+        if !std::io::stdin().is_tty() {
+            return self.run_non_interactive();
+        }
+        self.print_banner()?;
+        self.execute_start_callback()?;
+        let result = self.read_eval_loop();
+        self.execute_exit_callback()?;
+        result
+    }
+
+    /// Perform a single read/eval/print iteration and return, so the REPL can be driven
+    /// by the event loop of a host application instead of [run](Repl::run). The first call
+    /// prints the banner, calls the start callback and executes the rc file.
+    ///
+    /// Returns false once the REPL has ended, after the exit callback has been called.
+    pub fn step(&mut self) -> Result<bool> {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                if !self.login(Self::read_password)? {
+                    return Ok(false);
+                }
+                self.print_banner()?;
+                self.execute_start_callback()?;
+                self.execute_startup()?;
+                self.start_session()?
+            }
+        };
+        let result = self.read_eval(&mut session);
+        if let Ok(true) = result {
+            self.session = Some(session);
+            return result;
+        }
+        self.end_session(session);
+        self.execute_exit_callback()?;
+        result
+    }
+
+    fn print_banner(&mut self) -> Result<()> {
         enable_virtual_terminal_processing();
+        self.apply_console_capabilities();
+        self.write_banner()?;
+        Ok(())
+    }
+
+    fn write_banner(&mut self) -> Result<()> {
+        if self.quiet_start {
+            return Ok(());
+        }
         if let Some(banner) = &self.banner {
-            println!("{}", banner);
+            self.output.println(banner)?;
         }
-        let mut line_editor = self.build_line_editor()?;
+        if let Some(callback) = self.banner_callback {
+            let banner = self.context.with_ref(callback);
+            self.output.println(&banner)?;
+        }
+        Ok(())
+    }
 
-        loop {
-            let sig = line_editor
-                .read_line(&self.prompt)
-                .expect("failed to read_line");
-            match sig {
-                Signal::Success(line) => {
-                    if let Err(err) = self.process_line(line) {
-                        (self.error_handler)(err, self)?;
-                    }
+    fn apply_console_capabilities(&mut self) {
+        let mut capabilities = ConsoleCapabilities::detect(self.console_mode);
+        if self.accessible {
+            capabilities.ansi = false;
+            capabilities.unicode = false;
+        }
+        self.prompt.set_ascii(!capabilities.unicode);
+        self.prompt.set_plain(!capabilities.ansi);
+        self.prompt.set_right(!self.accessible);
+        if self.output.is_terminal() {
+            self.output
+                .set_console(!capabilities.ansi, capabilities.crlf);
+        }
+        if !capabilities.ansi {
+            yansi::Paint::disable();
+            self.hinter_enabled = false;
+        }
+    }
+
+    /// Returns true if line editing isn't possible because `TERM` is `dumb` or the
+    /// terminal doesn't support raw mode, e.g. in Emacs shell buffers
+    fn is_dumb_terminal() -> bool {
+        std::env::var("TERM").is_ok_and(|term| term == "dumb")
+            || crossterm::terminal::enable_raw_mode()
+                .and_then(|_| crossterm::terminal::disable_raw_mode())
+                .is_err()
+    }
+
+    fn start_session(&mut self) -> Result<Session> {
+        #[cfg(unix)]
+        self.bind_control_socket()?;
+        if Self::is_dumb_terminal() {
+            return Ok(Session {
+                line_editor: None,
+                idle_timer: None,
+                _signals: None,
+                _guard: None,
+            });
+        }
+        Ok(Session {
+            _guard: Some(TerminalGuard),
+            line_editor: Some(self.build_line_editor()?),
+            idle_timer: self
+                .idle_timeout
+                .map(|(timeout, _)| IdleTimer::new(timeout, self.idle_warning)),
+            _signals: SignalHandlers::register(self.handle.interrupts()).ok(),
+        })
+    }
+
+    /// Asks what to do if `line` was the first one of a paste of several lines, queueing
+    /// the others. Returns the line to run, an empty one if the paste is dropped
+    fn confirm_paste(&mut self, line: String) -> Result<Signal> {
+        let pasted = paste::pending_lines(&self.typed_ahead)
+            .map_err(|err| Error::IoError("<stdin>".to_string(), err.to_string()))?;
+        if pasted.is_empty() {
+            return Ok(Signal::Success(line));
+        }
+        let mut lines = vec![line];
+        lines.extend(pasted);
+        for line in &lines[1..] {
+            self.output.println(line)?;
+        }
+        let count = lines.len().to_string();
+        let question = match self.external_editor {
+            Some(_) => &self.messages.paste_confirm_edit,
+            None => &self.messages.paste_confirm,
+        };
+        self.output
+            .print(&format!("{} ", messages::fill(question, &[&count])))?;
+        let answer = Self::read_stdin_line()?.unwrap_or_default().to_lowercase();
+        let lines = match (answer.trim(), &self.external_editor) {
+            ("y" | "yes", _) => lines,
+            ("e" | "edit", Some(editor)) => paste::edit(editor, &lines)
+                .map_err(|err| Error::IoError(editor.clone(), err.to_string()))?
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        let mut lines = lines.into_iter();
+        let first = lines.next().unwrap_or_default();
+        for line in lines {
+            self.handle.enqueue(&line);
+        }
+        Ok(Signal::Success(first))
+    }
+
+    /// Reads a line from stdin after printing a plain prompt, for dumb terminals
+    fn read_plain_line(&mut self) -> Result<Option<Signal>> {
+        if !self.is_running() {
+            return Ok(None);
+        }
+        self.output
+            .print(&crate::width::strip_ansi(&self.prompt.render_plain()))?;
+        let mut line = match Self::read_stdin_line()? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        if validator::opens_body(&line, &self.body_commands) {
+            while let Some(next) = Self::read_stdin_line()? {
+                line.push_str(&next);
+                if next.trim() == validator::BODY_END {
+                    break;
                 }
-                Signal::CtrlC => {
-                    if self.stop_on_ctrl_c {
-                        break;
+            }
+        }
+        Ok(Some(Signal::Success(line)))
+    }
+
+    /// The line signalling waiting control socket requests, if there are any
+    fn control_signal(&mut self) -> Option<Signal> {
+        #[cfg(unix)]
+        if self.control.as_mut().is_some_and(ControlSocket::pending) {
+            return Some(Signal::Success(control::CONTROL_COMMAND.to_string()));
+        }
+        None
+    }
+
+    #[cfg(unix)]
+    fn bind_control_socket(&mut self) -> Result<()> {
+        if let Some(path) = &self.control_socket {
+            match ControlSocket::bind(path, self.handle.interrupts()) {
+                Ok(control) => self.control = Some(control),
+                Err(err) => {
+                    let error = Error::IoError(path.display().to_string(), err.to_string());
+                    self.handle_error(error.into())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_session(&mut self, session: Session) {
+        drop(session);
+        #[cfg(unix)]
+        {
+            self.control = None;
+        }
+        if let Some(signal) = self.handle.interrupts().received() {
+            self.handle.exit(128 + signal);
+        }
+    }
+
+    /// Passes the error of reading the history in the background to the error handler
+    fn report_history_failure(&mut self) -> Result<()> {
+        if let Some(err) = self.history_failure.take() {
+            let path = self.history.as_deref().unwrap_or(Path::new(""));
+            let error = Error::HistoryError(path.display().to_string(), err.to_string());
+            self.handle_error(error.into())?;
+        }
+        Ok(())
+    }
+
+    /// Handles an interrupted `read_line`, returning true if the REPL should end
+    fn handle_idle(&mut self, idle_timer: Option<&mut IdleTimer>) -> Result<bool> {
+        let event = match idle_timer {
+            Some(idle_timer) => idle_timer.poll(),
+            None => None,
+        };
+        match (event, self.idle_timeout) {
+            (Some(IdleEvent::Warning(remaining)), _) => {
+                self.output.println(&format!(
+                    "\n{}",
+                    messages::fill(
+                        &self.messages.idle_warning,
+                        &[&format!("{:.0}", remaining.as_secs_f64())]
+                    )
+                ))?;
+            }
+            (Some(IdleEvent::Timeout), Some((_, IdleAction::Exit))) => {
+                self.output
+                    .println(&format!("\n{}", self.messages.idle_timeout))?;
+                return Ok(true);
+            }
+            (Some(IdleEvent::Timeout), Some((_, IdleAction::Callback(callback)))) => {
+                self.output.println("")?;
+                self.execute_lifecycle_callback(Some(callback))?;
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Returns true if the user can be asked on the terminal: stdin and the output are the
+    /// terminal and the output isn't captured, e.g. for a JSON response
+    fn is_local_terminal(&self) -> bool {
+        std::io::stdin().is_tty() && self.output.is_terminal() && self.output_capture.is_none()
+    }
+
+    /// Returns true while a login callback is set which hasn't let the user in yet
+    pub(crate) fn login_pending(&self) -> bool {
+        self.login.is_some() && !self.logged_in
+    }
+
+    /// Asks for the password with `read` until the login callback lets the user in, returns
+    /// false if it never did. `read` returns None at the end of the input
+    fn login(&mut self, mut read: impl FnMut(&mut Self) -> Result<Option<String>>) -> Result<bool> {
+        while self.login_pending() && !self.handle.is_quitting() {
+            match read(self)? {
+                Some(password) => {
+                    self.submit_password(&password)?;
+                }
+                None => self.handle.exit(1),
+            }
+        }
+        Ok(!self.login_pending())
+    }
+
+    /// Passes a password to the login callback and reports a rejected attempt, returns true
+    /// if the user was let in
+    pub(crate) fn submit_password(&mut self, password: &str) -> Result<bool> {
+        match self.attempt_login(password) {
+            Ok(true) => return Ok(true),
+            Ok(false) => self.output.eprintln(&self.messages.login_denied)?,
+            Err(err) => self.handle_error(err)?,
+        }
+        if self.handle.is_quitting() {
+            self.output.eprintln(&self.messages.login_failed)?;
+        }
+        Ok(false)
+    }
+
+    /// Passes a password to the login callback, ending the REPL with exit code 1 after the
+    /// last failed attempt
+    fn attempt_login(&mut self, password: &str) -> core::result::Result<bool, E> {
+        let (callback, max_attempts) = match self.login {
+            Some(login) => login,
+            None => return Ok(true),
+        };
+        self.login_attempts += 1;
+        let result = callback(password, self.login_attempts, &mut self.context.lock());
+        self.logged_in = matches!(result, Ok(true));
+        if !self.logged_in && self.login_attempts >= max_attempts {
+            self.handle.exit(1);
+        }
+        result
+    }
+
+    /// Reads the password on the terminal without echo, or as line from a non-interactive
+    /// stdin
+    fn read_password(&mut self) -> Result<Option<String>> {
+        if !std::io::stdin().is_tty() {
+            return Ok(Self::read_stdin_line()?
+                .map(|line| line.trim_end_matches(['\r', '\n']).to_string()));
+        }
+        self.output.print(&self.messages.login_prompt)?;
+        let password = login::read_hidden()
+            .map_err(|err| Error::IoError("<stdin>".to_string(), err.to_string()))?;
+        self.output.println("")?;
+        Ok(password)
+    }
+
+    fn confirm_exit(&mut self) -> bool {
+        let question = match self.exit_confirmation {
+            Some(callback) if !self.exit_confirmed => callback(&mut self.context.lock()),
+            _ => None,
+        };
+        match question {
+            None => true,
+            Some(question) if self.is_local_terminal() => self.confirm(&question),
+            // the next line answers it, the prompt of sessions shows (y/N) until then
+            Some(question) => {
+                if self.output.println(&question).is_ok() {
+                    self.question = Some(Question::Exit);
+                }
+                false
+            }
+        }
+    }
+
+    /// Takes a line as answer to the question, returns the command to execute again if
+    /// the user confirmed a retry
+    fn answer_question(&mut self, question: Question, line: &str) -> Option<(String, Vec<String>)> {
+        if !is_yes(line) {
+            return None;
+        }
+        match question {
+            Question::Exit => {
+                self.exit_confirmed = true;
+                self.handle.quit();
+                None
+            }
+            Question::Retry(command, args) => Some((command, args)),
+        }
+    }
+
+    /// Asks a yes/no question on the local terminal, returns true if the answer starts
+    /// with 'y'
+    fn confirm(&self, question: &str) -> bool {
+        self.output.print(&format!("{} (y/N) ", question)).is_ok()
+            && matches!(Self::read_stdin_line(), Ok(Some(answer)) if is_yes(&answer))
+    }
+
+    /// Asks the exit confirmation callback if a command requested to quit
+    fn confirm_quit(&mut self) {
+        if self.handle.is_quitting() && !self.confirm_exit() {
+            self.handle.cancel_quit();
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        !self.handle.is_quitting() && self.handle.interrupts().received().is_none()
+    }
+
+    /// Reads the next line, returning None if the REPL should end
+    fn read_signal(&mut self, session: &mut Session) -> Result<Option<Signal>> {
+        if let Some(control) = self.control_signal() {
+            return Ok(Some(control));
+        }
+        self.refresh_prompt();
+        self.refresh_capabilities();
+        self.check_resize();
+        self.report_history_failure()?;
+        self.print_notifications()?;
+        let line_editor = match session.line_editor.as_mut() {
+            Some(line_editor) => line_editor,
+            None => return self.read_plain_line(),
+        };
+        while self.is_running() {
+            if let Some(control) = self.control_signal() {
+                return Ok(Some(control));
+            }
+            self.print_notifications()?;
+            let interrupts = self.handle.interrupts();
+            let alarm = session
+                .idle_timer
+                .as_ref()
+                .map(|idle_timer| idle_timer.alarm(interrupts));
+            match signal::read_line(line_editor, &self.prompt, interrupts) {
+                Ok(Some(sig)) => {
+                    drop(alarm);
+                    if let Some(idle_timer) = session.idle_timer.as_mut() {
+                        idle_timer.reset();
                     }
+                    return match sig {
+                        Signal::Success(line) if self.paste_confirmation => {
+                            self.confirm_paste(line).map(Some)
+                        }
+                        sig => Ok(Some(sig)),
+                    };
                 }
-                Signal::CtrlD => {
-                    if self.stop_on_ctrl_d {
+                Ok(None) => {
+                    if self.handle_idle(session.idle_timer.as_mut())? {
                         break;
                     }
                 }
+                Err(err) => {
+                    self.handle_error(Error::ReadlineError(err.to_string()).into())?;
+                    break;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// One iteration of the interactive loop, returns false once the REPL should end
+    fn read_eval(&mut self, session: &mut Session) -> Result<bool> {
+        #[cfg(feature = "config")]
+        self.reload_changed_config(session)?;
+        match self.read_signal(session)? {
+            Some(Signal::Success(line)) if line == signal::SUSPEND_COMMAND => signal::suspend(),
+            #[cfg(unix)]
+            Some(Signal::Success(line)) if line == control::CONTROL_COMMAND => {
+                while let Some(request) =
+                    self.control.as_mut().and_then(ControlSocket::next_request)
+                {
+                    let output = match self.eval_line(&request.line) {
+                        Ok(output) => output.unwrap_or_default(),
+                        Err(err) => err.to_string(),
+                    };
+                    request.reply(output);
+                }
+            }
+            Some(Signal::Success(line)) => {
+                if let Err(err) = self.process_line(&line) {
+                    self.handle_error(err)?;
+                }
+                self.refresh_line_editor(session)?;
             }
+            Some(Signal::CtrlC) if self.stop_on_ctrl_c => return Ok(!self.confirm_exit()),
+            Some(Signal::CtrlD) if self.stop_on_ctrl_d => return Ok(!self.confirm_exit()),
+            Some(_) => {}
+            None => return Ok(false),
         }
-        disable_virtual_terminal_processing();
+        self.confirm_quit();
+        Ok(self.is_running())
+    }
+
+    fn read_eval_loop(&mut self) -> Result<()> {
+        self.execute_startup()?;
+        let mut session = self.start_session()?;
+        while self.read_eval(&mut session)? {}
+        self.end_session(session);
         Ok(())
     }
 
     /// Execute REPL
+    ///
+    /// If stdin is not a terminal (e.g. `echo "status" | myapp`) the commands are read
+    /// line by line from stdin instead and no prompt or banner is printed.
+    ///
+    /// On Unix SIGTERM and SIGHUP end the REPL gracefully: the terminal is restored, the
+    /// history saved and the exit callback called, also while a line is being edited. The
+    /// exit code is 128 + signal number.
+    /// Ctrl-Z suspends the REPL, `fg` resumes it.
     #[cfg(feature = "async")]
     pub async fn run_async(&mut self) -> Result<()> {
-        enable_virtual_terminal_processing();
-        if let Some(banner) = &self.banner {
-            println!("{}", banner);
+        if !self.login(Self::read_password)? {
+            return Ok(());
+        }
+        if !std::io::stdin().is_tty() {
+            return self.run_non_interactive_async().await;
         }
-        let mut line_editor = self.build_line_editor()?;
+        self.print_banner()?;
+        self.execute_start_callback_async().await?;
+        let result = self.read_eval_loop_async().await;
+        self.execute_exit_callback_async().await?;
+        result
+    }
 
-        loop {
-            let sig = line_editor
-                .read_line(&self.prompt)
-                .expect("failed to read_line");
-            match sig {
-                Signal::Success(line) => {
-                    if let Err(err) = self.process_line_async(line).await {
-                        (self.error_handler)(err, self)?;
-                    }
+    /// Perform a single read/eval/print iteration and return, see [step](Repl::step)
+    #[cfg(feature = "async")]
+    pub async fn step_async(&mut self) -> Result<bool> {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                if !self.login(Self::read_password)? {
+                    return Ok(false);
                 }
-                Signal::CtrlC => {
-                    if self.stop_on_ctrl_c {
-                        break;
-                    }
+                self.print_banner()?;
+                self.execute_start_callback_async().await?;
+                self.execute_startup_async().await?;
+                self.start_session()?
+            }
+        };
+        let result = self.read_eval_async(&mut session).await;
+        if let Ok(true) = result {
+            self.session = Some(session);
+            return result;
+        }
+        self.end_session(session);
+        self.execute_exit_callback_async().await?;
+        result
+    }
+
+    #[cfg(feature = "async")]
+    async fn read_eval_async(&mut self, session: &mut Session) -> Result<bool> {
+        #[cfg(feature = "config")]
+        self.reload_changed_config(session)?;
+        match self.read_signal(session)? {
+            Some(Signal::Success(line)) if line == signal::SUSPEND_COMMAND => signal::suspend(),
+            #[cfg(unix)]
+            Some(Signal::Success(line)) if line == control::CONTROL_COMMAND => {
+                while let Some(request) =
+                    self.control.as_mut().and_then(ControlSocket::next_request)
+                {
+                    let output = match self.eval_line_async(&request.line).await {
+                        Ok(output) => output.unwrap_or_default(),
+                        Err(err) => err.to_string(),
+                    };
+                    request.reply(output);
                 }
-                Signal::CtrlD => {
-                    if self.stop_on_ctrl_d {
-                        break;
-                    }
+            }
+            Some(Signal::Success(line)) => {
+                if let Err(err) = self.process_line_async(&line).await {
+                    self.handle_error(err)?;
                 }
+                self.refresh_line_editor(session)?;
             }
+            Some(Signal::CtrlC) if self.stop_on_ctrl_c => return Ok(!self.confirm_exit()),
+            Some(Signal::CtrlD) if self.stop_on_ctrl_d => return Ok(!self.confirm_exit()),
+            Some(_) => {}
+            None => return Ok(false),
         }
-        disable_virtual_terminal_processing();
+        self.confirm_quit();
+        Ok(self.is_running())
+    }
+
+    #[cfg(feature = "async")]
+    async fn read_eval_loop_async(&mut self) -> Result<()> {
+        self.execute_startup_async().await?;
+        let mut session = self.start_session()?;
+        while self.read_eval_async(&mut session).await? {}
+        self.end_session(session);
         Ok(())
     }
 }
 
+/// Returns true if the answer to a yes/no question starts with 'y'
+fn is_yes(answer: &str) -> bool {
+    answer.trim().to_lowercase().starts_with('y')
+}
+
+/// Modification time of the configuration file, None if it doesn't exist
+#[cfg(feature = "config")]
+fn config_modified(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 #[cfg(windows)]
 pub fn enable_virtual_terminal_processing() {
     use winapi_util::console::Console;
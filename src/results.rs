@@ -0,0 +1,111 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+/// The outputs of the last commands, referenced in later lines as `$_` or `$1` for the
+/// most recent one and `$2`, `$3`, ... for earlier ones, see
+/// [Repl::with_result_history](crate::Repl::with_result_history)
+#[derive(Default)]
+pub(crate) struct ResultHistory {
+    capacity: usize,
+    // most recent first
+    results: VecDeque<String>,
+}
+
+impl ResultHistory {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            results: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn push(&mut self, output: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.results.len() == self.capacity {
+            self.results.pop_back();
+        }
+        self.results.push_front(output.to_string());
+    }
+
+    /// The results, most recent first
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &str> {
+        self.results.iter().map(String::as_str)
+    }
+
+    /// The result referenced by `_` or its number, starting at 1 for the most recent one
+    fn get(&self, reference: &str) -> Option<&str> {
+        let index = match reference {
+            "_" => 0,
+            number => number.parse::<usize>().ok()?.checked_sub(1)?,
+        };
+        self.results.get(index).map(String::as_str)
+    }
+
+    /// Replace the result references in an argument. References to results which don't
+    /// exist are kept, so arguments like regular expressions can still contain `$1`
+    pub(crate) fn substitute<'a>(&self, arg: Cow<'a, str>) -> Cow<'a, str> {
+        static REFERENCE: OnceLock<Regex> = OnceLock::new();
+        if !self.is_enabled() || !arg.contains('$') {
+            return arg;
+        }
+        let r = REFERENCE.get_or_init(|| Regex::new(r"\$(_|\d+)").unwrap());
+        match r.replace_all(&arg, |captures: &regex::Captures| {
+            self.get(&captures[1]).unwrap_or(&captures[0]).to_string()
+        }) {
+            Cow::Borrowed(_) => arg,
+            Cow::Owned(substituted) => Cow::Owned(substituted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(outputs: &[&str]) -> ResultHistory {
+        let mut history = ResultHistory::new(2);
+        for output in outputs {
+            history.push(output);
+        }
+        history
+    }
+
+    #[test]
+    fn references_are_replaced() {
+        let history = history(&["first", "second"]);
+        assert_eq!(
+            history.substitute(Cow::Borrowed("$_ $1 $2")),
+            "second second first"
+        );
+    }
+
+    #[test]
+    fn unknown_references_are_kept() {
+        let history = history(&["first", "second", "third"]);
+        assert_eq!(history.substitute(Cow::Borrowed("$0 $3 $x")), "$0 $3 $x");
+    }
+
+    #[test]
+    fn arguments_without_references_are_borrowed() {
+        let history = history(&["first"]);
+        assert!(matches!(
+            history.substitute(Cow::Borrowed("plain")),
+            Cow::Borrowed("plain")
+        ));
+    }
+
+    #[test]
+    fn disabled_history_substitutes_nothing() {
+        let mut history = ResultHistory::new(0);
+        history.push("ignored");
+        assert_eq!(history.substitute(Cow::Borrowed("$_")), "$_");
+    }
+}
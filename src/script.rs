@@ -0,0 +1,436 @@
+use crate::error::{Error, Result};
+use regex::Regex;
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Lines of a script with their line numbers
+pub(crate) type Lines = Vec<(usize, String)>;
+
+/// Upper bound for the number of commands a script may expand to through loops
+const MAX_EXPANDED_LINES: usize = 100_000;
+
+/// Expand a leading `~` to the current user's home directory
+pub(crate) fn expand_tilde(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix('~') {
+        if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') {
+            if let Some(home) = home_dir() {
+                return home.join(rest.trim_start_matches(['/', '\\']));
+            }
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Read a command file, returning every non-empty line which isn't a `#` comment
+/// together with its (1-based) line number
+pub(crate) fn read_lines(path: &Path) -> io::Result<Lines> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_string()))
+        .collect())
+}
+
+enum Statement {
+    Command(usize, String),
+    Let(String, String),
+    Repeat(usize, String, Vec<Statement>),
+    For(String, String, Vec<Statement>),
+    If(usize, String, Vec<Statement>, Vec<Statement>),
+}
+
+/// Expand the control flow constructs of a script into the plain list of commands to run:
+///
+/// - `let name = value` defines a variable which is substituted for `$name` or `${name}`
+/// - `repeat 3 { ... }` runs the enclosed commands 3 times
+/// - `for name in a b "c d" { ... }` runs the enclosed commands once per value
+/// - `if $name == value { ... } else { ... }` compares two words with `==` or `!=`
+///
+/// Blocks are opened at the end of a line and closed by a line containing only `}`.
+/// Unknown variables are left untouched.
+pub(crate) fn expand(file: &str, lines: Lines) -> Result<Lines> {
+    let mut pos = 0;
+    let statements = parse_block(file, &lines, &mut pos)?;
+    if let Some((line_number, line)) = lines.get(pos) {
+        return Err(syntax_error(
+            file,
+            *line_number,
+            &format!("unexpected '{}'", line),
+        ));
+    }
+    let mut expanded = vec![];
+    flatten(file, &statements, &mut vec![], &mut expanded)?;
+    Ok(expanded)
+}
+
+fn syntax_error(file: &str, line_number: usize, message: &str) -> Error {
+    Error::ScriptError(file.to_string(), line_number, format!("Error: {}", message))
+}
+
+fn block_header<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let header = line.strip_prefix(keyword)?.strip_suffix('{')?;
+    if header.starts_with(char::is_whitespace) {
+        Some(header.trim())
+    } else {
+        None
+    }
+}
+
+/// Parses statements until the end of the script or a closing `}` / `} else {` line,
+/// which is left for the caller to consume
+fn parse_block(file: &str, lines: &[(usize, String)], pos: &mut usize) -> Result<Vec<Statement>> {
+    let mut statements = vec![];
+    while let Some((line_number, line)) = lines.get(*pos) {
+        let line_number = *line_number;
+        if line == "}" || line == "} else {" {
+            return Ok(statements);
+        }
+        *pos += 1;
+        if let Some(count) = block_header(line, "repeat") {
+            let body = parse_body(file, lines, pos, line_number)?;
+            statements.push(Statement::Repeat(line_number, count.to_string(), body));
+        } else if let Some(header) = block_header(line, "for") {
+            let (variable, values) = match header.split_once(" in ") {
+                Some((variable, values)) if !variable.trim().is_empty() => {
+                    (variable.trim().to_string(), values.trim().to_string())
+                }
+                _ => {
+                    return Err(syntax_error(
+                        file,
+                        line_number,
+                        "expected 'for <name> in <values> {'",
+                    ))
+                }
+            };
+            let body = parse_body(file, lines, pos, line_number)?;
+            statements.push(Statement::For(variable, values, body));
+        } else if let Some(condition) = block_header(line, "if") {
+            let body = parse_body(file, lines, pos, line_number)?;
+            let else_body = if lines[*pos - 1].1 == "} else {" {
+                parse_body(file, lines, pos, line_number)?
+            } else {
+                vec![]
+            };
+            statements.push(Statement::If(
+                line_number,
+                condition.to_string(),
+                body,
+                else_body,
+            ));
+        } else if let Some((variable, value)) = line
+            .strip_prefix("let ")
+            .and_then(|definition| definition.split_once('='))
+        {
+            statements.push(Statement::Let(
+                variable.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        } else {
+            statements.push(Statement::Command(line_number, line.clone()));
+        }
+    }
+    Ok(statements)
+}
+
+/// Parses the body of a block and consumes its closing line
+fn parse_body(
+    file: &str,
+    lines: &[(usize, String)],
+    pos: &mut usize,
+    opening_line: usize,
+) -> Result<Vec<Statement>> {
+    let body = parse_block(file, lines, pos)?;
+    if *pos >= lines.len() {
+        return Err(syntax_error(file, opening_line, "missing '}'"));
+    }
+    *pos += 1;
+    Ok(body)
+}
+
+fn substitute(text: &str, variables: &[(String, String)]) -> String {
+    static VARIABLE: OnceLock<Regex> = OnceLock::new();
+    let r = VARIABLE.get_or_init(|| Regex::new(r"\$\{(\w+)\}|\$(\w+)").unwrap());
+    r.replace_all(text, |captures: &regex::Captures| {
+        let name = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .expect("one group matches")
+            .as_str();
+        match variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+        {
+            Some((_, value)) => value.clone(),
+            None => captures[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+/// Split into words, keeping double quoted strings together
+pub(crate) fn split_words(text: &str) -> Vec<String> {
+    split_words_borrowed(text)
+        .into_iter()
+        .map(Cow::into_owned)
+        .collect()
+}
+
+/// Split into words like [split_words], borrowing from `text` unless quotes have to be
+/// removed from the middle of a word
+pub(crate) fn split_words_borrowed(text: &str) -> Vec<Cow<'_, str>> {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    let r = WORD.get_or_init(|| Regex::new(r#"("[^"\n]+"|[\S]+)"#).unwrap());
+    r.find_iter(text)
+        .map(|word| {
+            let word = word.as_str();
+            match word
+                .strip_prefix('"')
+                .and_then(|word| word.strip_suffix('"'))
+            {
+                Some(quoted) if !quoted.contains('"') => Cow::Borrowed(quoted),
+                _ if word.contains('"') => Cow::Owned(word.replace('"', "")),
+                _ => Cow::Borrowed(word),
+            }
+        })
+        .collect()
+}
+
+fn evaluate(
+    file: &str,
+    line_number: usize,
+    condition: &str,
+    variables: &[(String, String)],
+) -> Result<bool> {
+    let words: Vec<String> = split_words(condition)
+        .iter()
+        .map(|word| substitute(word, variables))
+        .collect();
+    match words.as_slice() {
+        [left, op, right] if op == "==" => Ok(left == right),
+        [left, op, right] if op == "!=" => Ok(left != right),
+        _ => Err(syntax_error(
+            file,
+            line_number,
+            "expected '<word> == <word>' or '<word> != <word>'",
+        )),
+    }
+}
+
+fn flatten(
+    file: &str,
+    statements: &[Statement],
+    variables: &mut Vec<(String, String)>,
+    expanded: &mut Lines,
+) -> Result<()> {
+    let scope = variables.len();
+    for statement in statements {
+        match statement {
+            Statement::Command(line_number, line) => {
+                if expanded.len() >= MAX_EXPANDED_LINES {
+                    return Err(syntax_error(
+                        file,
+                        *line_number,
+                        "script expands to too many commands",
+                    ));
+                }
+                expanded.push((*line_number, substitute(line, variables)));
+            }
+            Statement::Let(variable, value) => {
+                let value = substitute(value, variables);
+                variables.push((variable.clone(), value));
+            }
+            Statement::Repeat(line_number, count, body) => {
+                let count: usize = substitute(count, variables)
+                    .parse()
+                    .map_err(|_| syntax_error(file, *line_number, "expected 'repeat <count> {'"))?;
+                if count > MAX_EXPANDED_LINES - expanded.len() {
+                    return Err(syntax_error(
+                        file,
+                        *line_number,
+                        "script expands to too many commands",
+                    ));
+                }
+                for _ in 0..count {
+                    let before = expanded.len();
+                    flatten(file, body, variables, expanded)?;
+                    // every iteration expands the same, so an empty one ends the loop
+                    if expanded.len() == before {
+                        break;
+                    }
+                }
+            }
+            Statement::For(variable, values, body) => {
+                for value in split_words(values) {
+                    variables.push((variable.clone(), substitute(&value, variables)));
+                    flatten(file, body, variables, expanded)?;
+                    variables.pop();
+                }
+            }
+            Statement::If(line_number, condition, body, else_body) => {
+                if evaluate(file, *line_number, condition, variables)? {
+                    flatten(file, body, variables, expanded)?;
+                } else {
+                    flatten(file, else_body, variables, expanded)?;
+                }
+            }
+        }
+    }
+    variables.truncate(scope);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(script: &str) -> Lines {
+        script
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(number, line)| (number, line.to_string()))
+            .collect()
+    }
+
+    fn commands(script: &str) -> Vec<String> {
+        expand("test", lines(script))
+            .unwrap()
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect()
+    }
+
+    fn error(script: &str) -> Error {
+        expand("test", lines(script)).unwrap_err()
+    }
+
+    #[test]
+    fn plain_commands_keep_their_line_numbers() {
+        let expanded = expand("test", lines("first\n\nsecond")).unwrap();
+        assert_eq!(
+            expanded,
+            vec![(1, "first".to_string()), (3, "second".to_string())]
+        );
+    }
+
+    #[test]
+    fn variables_are_substituted() {
+        assert_eq!(
+            commands("let name = world\nsay $name ${name}s $other"),
+            vec!["say world worlds $other"]
+        );
+    }
+
+    #[test]
+    fn nested_blocks_expand_in_order() {
+        let script = "repeat 2 {\n  for x in a \"b c\" {\n    echo $x\n  }\n}";
+        assert_eq!(
+            commands(script),
+            vec!["echo a", "echo b c", "echo a", "echo b c"]
+        );
+    }
+
+    #[test]
+    fn loop_lines_keep_the_number_of_their_line() {
+        let expanded = expand("test", lines("repeat 2 {\necho\n}")).unwrap();
+        assert_eq!(
+            expanded,
+            vec![(2, "echo".to_string()), (2, "echo".to_string())]
+        );
+    }
+
+    #[test]
+    fn if_takes_else_branch() {
+        let script = "let mode = fast\nif $mode == slow {\nwait\n} else {\nrun\n}";
+        assert_eq!(commands(script), vec!["run"]);
+        let script = "let mode = fast\nif $mode != slow {\nrun\n}";
+        assert_eq!(commands(script), vec!["run"]);
+    }
+
+    #[test]
+    fn variables_end_with_their_block() {
+        let script = "for x in a {\nlet y = $x\n}\necho $y";
+        assert_eq!(commands(script), vec!["echo $y"]);
+    }
+
+    #[test]
+    fn unclosed_block_reports_opening_line() {
+        assert_eq!(
+            error("echo\nrepeat 2 {\necho"),
+            Error::ScriptError("test".to_string(), 2, "Error: missing '}'".to_string())
+        );
+    }
+
+    #[test]
+    fn unexpected_closing_brace_is_reported() {
+        assert_eq!(
+            error("echo\n}"),
+            Error::ScriptError("test".to_string(), 2, "Error: unexpected '}'".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_headers_are_reported() {
+        assert!(matches!(
+            error("for x {\n}"),
+            Error::ScriptError(_, 1, message) if message.contains("for <name> in <values>")
+        ));
+        assert!(matches!(
+            error("echo\nrepeat many {\necho\n}"),
+            Error::ScriptError(_, 2, message) if message.contains("repeat <count>")
+        ));
+        assert!(matches!(
+            error("if a {\n}"),
+            Error::ScriptError(_, 1, message) if message.contains("==")
+        ));
+    }
+
+    #[test]
+    fn expansion_is_capped() {
+        let script = "repeat 1000 {\nrepeat 1000 {\necho\n}\n}";
+        assert!(matches!(
+            error(script),
+            Error::ScriptError(_, _, message) if message.contains("too many commands")
+        ));
+        assert!(matches!(
+            error("repeat 100001 {\necho\n}"),
+            Error::ScriptError(_, 1, message) if message.contains("too many commands")
+        ));
+        assert_eq!(commands("repeat 100000 {\n}").len(), 0);
+    }
+
+    #[test]
+    fn substitute_prefers_latest_definition() {
+        let variables = vec![
+            ("a".to_string(), "1".to_string()),
+            ("a".to_string(), "2".to_string()),
+        ];
+        assert_eq!(substitute("$a-${a}-$ab", &variables), "2-2-$ab");
+    }
+
+    #[test]
+    fn split_words_keeps_quoted_strings() {
+        assert_eq!(
+            split_words_borrowed(r#"say "hello world"  x"y"z"#),
+            vec!["say", "hello world", "xyz"]
+        );
+        assert!(matches!(
+            split_words_borrowed(r#""quoted" plain"#).as_slice(),
+            [Cow::Borrowed("quoted"), Cow::Borrowed("plain")]
+        ));
+    }
+}
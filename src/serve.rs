@@ -0,0 +1,124 @@
+//! Serving Repl sessions over TCP, e.g. as admin console of a daemon
+
+use crate::error::{Error, Result};
+use crate::output::StripAnsi;
+use crate::{ConnectionErrorCallback, Repl};
+use std::fmt::Display;
+use std::io::{BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// The connection a Repl is built for by [Server]
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    /// Number of the session, counting from 1 in the order of connection
+    pub id: u64,
+    /// Address of the client, if known
+    pub peer: Option<SocketAddr>,
+}
+
+/// Line based TCP server running many sessions at once, each one on its own thread and
+/// without line editing.
+///
+/// Every connection gets its own Repl from `make_repl`, so all sessions share the command
+/// set while prompt, history and context belong to the session. The [SessionInfo] allows
+/// e.g. a prompt per session. State shared between the sessions has to be thread-safe,
+/// e.g. an `Arc<RwLock<_>>` cloned into the Context of each Repl:
+///
+/// ```rust,no_run
+#[doc = include_str!("../examples/serve.rs")]
+/// ```
+pub struct Server<F> {
+    make_repl: Arc<F>,
+    ansi: bool,
+    sessions: AtomicU64,
+    on_error: Option<ConnectionErrorCallback>,
+}
+
+impl<F, Context, E> Server<F>
+where
+    F: Fn(&SessionInfo) -> Repl<Context, E> + Send + Sync + 'static,
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// Create a server building the Repl of each connection with `make_repl`
+    pub fn new(make_repl: F) -> Self {
+        Self {
+            make_repl: Arc::new(make_repl),
+            ansi: false,
+            sessions: AtomicU64::new(0),
+            on_error: None,
+        }
+    }
+
+    /// Send ANSI escape sequences (colors) to the clients, defaults to false which strips
+    /// them from prompts and output
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    /// Give the server a callback which receives the errors ending a session, e.g. a
+    /// broken connection. They are dropped otherwise
+    pub fn with_on_connection_error(mut self, callback: ConnectionErrorCallback) -> Self {
+        self.on_error = Some(callback);
+        self
+    }
+
+    /// Listen on the given address and serve connections until accepting fails
+    pub fn serve<A: ToSocketAddrs>(self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|err| Error::IoError("<listener>".to_string(), err.to_string()))?;
+        self.serve_listener(listener)
+    }
+
+    /// Serve connections of a bound listener, each one on its own thread. Returns the error
+    /// if accepting a connection fails
+    pub fn serve_listener(self, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream =
+                stream.map_err(|err| Error::IoError("<listener>".to_string(), err.to_string()))?;
+            let make_repl = Arc::clone(&self.make_repl);
+            let ansi = self.ansi;
+            let on_error = self.on_error;
+            let info = SessionInfo {
+                id: self.sessions.fetch_add(1, Ordering::SeqCst) + 1,
+                peer: stream.peer_addr().ok(),
+            };
+            thread::spawn(move || {
+                let result = serve_connection(make_repl.as_ref(), &info, stream, ansi);
+                if let (Err(err), Some(on_error)) = (result, on_error) {
+                    on_error(&info, &err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn serve_connection<F, Context, E>(
+    make_repl: &F,
+    info: &SessionInfo,
+    stream: TcpStream,
+    ansi: bool,
+) -> Result<()>
+where
+    F: Fn(&SessionInfo) -> Repl<Context, E>,
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    let io_error =
+        |err: std::io::Error| Error::IoError("<connection>".to_string(), err.to_string());
+    let input = BufReader::new(stream.try_clone().map_err(io_error)?);
+    let stdout = stream.try_clone().map_err(io_error)?;
+    let (stdout, stderr): (Box<dyn Write + Send>, Box<dyn Write + Send>) = if ansi {
+        (Box::new(stdout), Box::new(stream))
+    } else {
+        (
+            Box::new(StripAnsi::new(stdout)),
+            Box::new(StripAnsi::new(stream)),
+        )
+    };
+    let mut repl = make_repl(info);
+    repl.run_line_session(input, stdout, stderr)
+}
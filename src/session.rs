@@ -0,0 +1,279 @@
+//! REPL sessions on arbitrary async byte streams, e.g. the channel of an SSH server
+
+use crate::error::{Error, Result};
+use crate::output::SharedBuffer;
+use crate::width;
+use crate::Repl;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const BACKSPACE: u8 = 0x08;
+const ESCAPE: u8 = 0x1b;
+const DELETE: u8 = 0x7f;
+
+/// Handle to announce terminal size changes to a running [ReplSession], e.g. from the
+/// `window_change_request` of an SSH handler
+#[derive(Clone, Default)]
+pub struct ResizeHandle {
+    // columns in the high and rows in the low 16 bits, so both change together
+    size: Arc<AtomicU32>,
+}
+
+impl ResizeHandle {
+    /// The terminal now has the given size
+    pub fn resize(&self, columns: u16, rows: u16) {
+        self.size
+            .store((columns as u32) << 16 | rows as u32, Ordering::SeqCst);
+    }
+
+    /// Columns and rows, None until the size has been reported
+    fn size(&self) -> Option<(u16, u16)> {
+        match self.size.load(Ordering::SeqCst) {
+            0 => None,
+            size => Some(((size >> 16) as u16, size as u16)),
+        }
+    }
+}
+
+/// A Repl session reading keystrokes from an [AsyncRead] and writing to an [AsyncWrite],
+/// so a REPL can be mounted in an SSH server handler or similar. The client is expected to
+/// be a terminal in raw mode: input is echoed and edited with Backspace, Enter executes
+/// the line, Ctrl-C discards it and Ctrl-D on an empty line ends the session. Up and Down
+/// recall the lines entered in this session, other escape sequences are ignored. With a
+/// [login callback](Repl::with_login) the password is read without echo before the banner.
+///
+/// ```rust,ignore
+/// let session = ReplSession::new(make_repl(), reader, writer);
+/// let resize = session.resize_handle();
+/// // in the window change handler: resize.resize(columns, rows)
+/// session.run().await?;
+/// ```
+pub struct ReplSession<Context, E: Display, R, W> {
+    repl: Repl<Context, E>,
+    reader: R,
+    writer: W,
+    output: SharedBuffer,
+    resize: ResizeHandle,
+    // size when it was last checked, to call the resize callback of the Repl on changes
+    size: Option<(u16, u16)>,
+    history: Vec<String>,
+}
+
+/// Number of entered lines kept per session
+const HISTORY_CAPACITY: usize = 100;
+
+enum Input {
+    Text,
+    Escape,
+    Sequence,
+}
+
+impl<Context, E, R, W> ReplSession<Context, E, R, W>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// Create a session running `repl` on the given streams
+    pub fn new(repl: Repl<Context, E>, reader: R, writer: W) -> Self {
+        let output = SharedBuffer::new();
+        let repl = repl
+            .with_output_writer(Box::new(output.clone()))
+            .with_error_writer(Box::new(output.clone()))
+            .with_help_pager(false);
+        Self {
+            repl,
+            reader,
+            writer,
+            output,
+            resize: ResizeHandle::default(),
+            size: None,
+            history: vec![],
+        }
+    }
+
+    /// Handle to report window size changes while the session runs
+    pub fn resize_handle(&self) -> ResizeHandle {
+        self.resize.clone()
+    }
+
+    /// Run the session until the client disconnects, presses Ctrl-D on an empty line or a
+    /// command quits the Repl. Returns the Repl, e.g. to inspect its context
+    pub async fn run(mut self) -> Result<Repl<Context, E>> {
+        self.repl.begin_stream_session().await?;
+        let result = self.read_eval_loop().await;
+        self.repl.end_stream_session().await?;
+        self.flush_output().await?;
+        result.map(|_| self.repl)
+    }
+
+    async fn read_eval_loop(&mut self) -> Result<()> {
+        let mut line: Vec<u8> = vec![];
+        let mut input = Input::Text;
+        let mut buffer = [0u8; 1024];
+        let mut position = self.history.len();
+        self.size = self.resize.size();
+        self.write_prompt().await?;
+        while self.repl.is_running() {
+            let count = self.reader.read(&mut buffer).await.map_err(io_error)?;
+            if count == 0 {
+                break;
+            }
+            self.check_resize();
+            for &byte in &buffer[..count] {
+                match (&input, byte) {
+                    (Input::Escape, b'[') | (Input::Escape, b'O') => input = Input::Sequence,
+                    (Input::Escape, _) => input = Input::Text,
+                    (Input::Sequence, b'A') if position > 0 => {
+                        input = Input::Text;
+                        position -= 1;
+                        line = self.history[position].clone().into_bytes();
+                        self.rewrite_line(&line).await?;
+                    }
+                    (Input::Sequence, b'B') if position < self.history.len() => {
+                        input = Input::Text;
+                        position += 1;
+                        line = self
+                            .history
+                            .get(position)
+                            .map(|entry| entry.clone().into_bytes())
+                            .unwrap_or_default();
+                        self.rewrite_line(&line).await?;
+                    }
+                    // a control sequence ends with a byte in the range @ to ~
+                    (Input::Sequence, 0x40..=0x7e) => input = Input::Text,
+                    (Input::Sequence, _) => {}
+                    (Input::Text, ESCAPE) => input = Input::Escape,
+                    (Input::Text, b'\r') | (Input::Text, b'\n') => {
+                        self.write(b"\r\n").await?;
+                        let entered = String::from_utf8_lossy(&line).into_owned();
+                        line.clear();
+                        if self.repl.login_pending() {
+                            self.login(&entered).await?;
+                            if !self.repl.is_running() {
+                                return Ok(());
+                            }
+                            self.write_prompt().await?;
+                            continue;
+                        }
+                        self.add_history(&entered);
+                        position = self.history.len();
+                        self.execute(entered).await?;
+                        if !self.repl.is_running() {
+                            return Ok(());
+                        }
+                        self.write_prompt().await?;
+                    }
+                    (Input::Text, BACKSPACE) | (Input::Text, DELETE) => {
+                        // remove the last character, including all bytes of multi-byte ones,
+                        // and as many columns as it took
+                        let start = (0..line.len())
+                            .rev()
+                            .find(|&index| line[index] & 0xc0 != 0x80)
+                            .unwrap_or(0);
+                        let removed = String::from_utf8_lossy(&line[start..]).into_owned();
+                        line.truncate(start);
+                        if self.repl.login_pending() {
+                            continue;
+                        }
+                        let columns = removed.chars().map(width::char_width).sum::<usize>();
+                        self.write("\x08 \x08".repeat(columns).as_bytes()).await?;
+                    }
+                    (Input::Text, CTRL_C) => {
+                        line.clear();
+                        self.write(b"^C\r\n").await?;
+                        self.write_prompt().await?;
+                    }
+                    (Input::Text, CTRL_D) if line.is_empty() => {
+                        self.write(b"\r\n").await?;
+                        return Ok(());
+                    }
+                    (Input::Text, byte) if byte >= 0x20 => {
+                        line.push(byte);
+                        // the password isn't echoed
+                        if !self.repl.login_pending() {
+                            self.write(&[byte]).await?;
+                        }
+                    }
+                    (Input::Text, _) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, line: String) -> Result<()> {
+        let columns = self.size.map(|(columns, _)| columns as usize);
+        self.repl.set_help_width(columns);
+        self.repl.execute_line_async(line).await?;
+        self.flush_output().await
+    }
+
+    /// Call the resize callback of the Repl if the size changed since the last check. The
+    /// first size reported isn't a resize, like on a local terminal
+    fn check_resize(&mut self) {
+        let size = self.resize.size();
+        if size == self.size {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.size, size);
+        if let (Some(_), Some((columns, rows))) = (previous, size) {
+            self.repl.resized(columns, rows);
+        }
+    }
+
+    /// Pass an entered password to the login callback and start the session once it let the
+    /// user in
+    async fn login(&mut self, password: &str) -> Result<()> {
+        if self.repl.submit_password(password)? {
+            self.repl.begin_stream_session().await?;
+        }
+        self.flush_output().await
+    }
+
+    fn add_history(&mut self, entered: &str) {
+        let entered = entered.trim();
+        if entered.is_empty() || self.history.last().map(String::as_str) == Some(entered) {
+            return;
+        }
+        if self.history.len() == HISTORY_CAPACITY {
+            self.history.remove(0);
+        }
+        self.history.push(entered.to_string());
+    }
+
+    /// Replace the line shown after the prompt
+    async fn rewrite_line(&mut self, line: &[u8]) -> Result<()> {
+        self.write(b"\r\x1b[K").await?;
+        self.write_prompt().await?;
+        self.write(line).await
+    }
+
+    async fn write_prompt(&mut self) -> Result<()> {
+        let prompt = self.repl.prompt_text();
+        self.write(prompt.as_bytes()).await
+    }
+
+    /// Send everything the Repl printed, with line feeds turned into CRLF for the terminal
+    async fn flush_output(&mut self) -> Result<()> {
+        let output = self.output.take();
+        if output.is_empty() {
+            return Ok(());
+        }
+        let output = output.replace("\r\n", "\n").replace('\n', "\r\n");
+        self.write(output.as_bytes()).await
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes).await.map_err(io_error)?;
+        self.writer.flush().await.map_err(io_error)
+    }
+}
+
+fn io_error(err: std::io::Error) -> Error {
+    Error::IoError("<session>".to_string(), err.to_string())
+}
@@ -0,0 +1,77 @@
+//! Settings of a session changed with the `set` built-in command
+
+/// A setting of the Repl which users can change mid-session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Setting {
+    Timing,
+    Pager,
+    QuickCompletions,
+    PartialCompletions,
+    Hints,
+    Highlighting,
+}
+
+impl Setting {
+    pub(crate) const ALL: [Setting; 6] = [
+        Setting::Timing,
+        Setting::Pager,
+        Setting::QuickCompletions,
+        Setting::PartialCompletions,
+        Setting::Hints,
+        Setting::Highlighting,
+    ];
+
+    pub(crate) const NAMES: [&'static str; 6] = [
+        "timing",
+        "pager",
+        "quick-completions",
+        "partial-completions",
+        "hints",
+        "highlighting",
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .zip(Self::NAMES)
+            .find(|(_, setting_name)| *setting_name == name)
+            .map(|(setting, _)| *setting)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Setting::Timing => "Print how long each command took",
+            Setting::Pager => "Page help which is longer than the terminal",
+            Setting::QuickCompletions => "Complete without menu if there is only one candidate",
+            Setting::PartialCompletions => "Complete the common prefix of all candidates",
+            Setting::Hints => "Suggest the rest of the line from the history",
+            Setting::Highlighting => "Highlight the input line",
+        }
+    }
+
+    /// True if the line editor has to be rebuilt for a change to take effect
+    pub(crate) fn affects_line_editor(self) -> bool {
+        !matches!(self, Setting::Timing | Setting::Pager)
+    }
+}
+
+/// Parse `on`/`off` and their usual synonyms
+pub(crate) fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+pub(crate) fn format_switch(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
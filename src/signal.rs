@@ -0,0 +1,285 @@
+//! Signal handling while the interactive REPL is running.
+//!
+//! SIGTERM and SIGHUP end the REPL gracefully. reedline offers no way to interrupt a
+//! pending `read_line`, so [read_line] paints the prompt itself and only hands over to
+//! reedline once a key has been pressed. Until then it polls the [Interrupts] of its Repl
+//! and returns as soon as a signal has been received or the Repl has been woken, e.g. by
+//! an [Alarm] or a notification. A termination signal arriving while a line is being
+//! edited raises SIGWINCH, so reedline repaints the prompt, and the prompt unwinds out of
+//! reedline, the only way to leave the edited line.
+//!
+//! SIGTSTP suspends the process with the terminal restored, raw mode is re-entered and
+//! the prompt repainted once the process is continued.
+use crossterm::style::Color;
+use crossterm::{cursor, event, terminal, QueueableCommand};
+use reedline::{Prompt, PromptEditMode, PromptHistorySearch, Reedline, Signal};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Line returned by `read_line` when Ctrl-Z has been pressed
+pub(crate) const SUSPEND_COMMAND: &str = "\u{1a}";
+
+/// How often the flags are checked while waiting for the first key
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Not reading a line
+const IDLE: u8 = 0;
+/// Waiting for the first key with the prompt painted by [read_line]
+const WAITING: u8 = 1;
+/// reedline is editing a line
+const EDITING: u8 = 2;
+
+/// Flags leaving the pending [read_line] of a Repl, shared with the threads setting them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Interrupts(Arc<Flags>);
+
+#[derive(Debug, Default)]
+struct Flags {
+    received: AtomicI32,
+    woken: AtomicBool,
+    resumed: AtomicBool,
+    reading: AtomicU8,
+}
+
+impl Interrupts {
+    /// The termination signal received since registering, if any
+    pub(crate) fn received(&self) -> Option<i32> {
+        match self.0.received.load(Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// Leaves a pending [read_line] unless a line is being edited, in which case the
+    /// next one returns right away
+    pub(crate) fn wake(&self) {
+        self.0.woken.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Keeps SIGTERM, SIGHUP and SIGTSTP from leaving the terminal in raw mode while registered
+pub(crate) struct SignalHandlers {
+    #[cfg(unix)]
+    handle: signal_hook::iterator::Handle,
+}
+
+impl SignalHandlers {
+    #[cfg(unix)]
+    pub(crate) fn register(interrupts: &Interrupts) -> io::Result<Self> {
+        use signal_hook::consts::{SIGHUP, SIGTERM, SIGTSTP, SIGWINCH};
+
+        let flags = Arc::clone(&interrupts.0);
+        flags.received.store(0, Ordering::SeqCst);
+        let mut signals = signal_hook::iterator::Signals::new([SIGTERM, SIGHUP, SIGTSTP])?;
+        let handle = signals.handle();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                let reading = flags.reading.load(Ordering::SeqCst);
+                if signal == SIGTSTP {
+                    if reading != IDLE {
+                        let _ = terminal::disable_raw_mode();
+                    }
+                    let _ = signal_hook::low_level::emulate_default_handler(SIGTSTP);
+                    if reading != IDLE {
+                        let _ = terminal::enable_raw_mode();
+                        flags.resumed.store(true, Ordering::SeqCst);
+                        // makes reedline repaint the line being edited
+                        let _ = signal_hook::low_level::raise(SIGWINCH);
+                    }
+                } else {
+                    flags.received.store(signal, Ordering::SeqCst);
+                    if reading == EDITING {
+                        // makes reedline repaint, see InterruptiblePrompt
+                        let _ = signal_hook::low_level::raise(SIGWINCH);
+                    }
+                }
+            }
+        });
+        Ok(Self { handle })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn register(_interrupts: &Interrupts) -> io::Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SignalHandlers {
+    fn drop(&mut self) {
+        self.handle.close();
+    }
+}
+
+/// Wakes the Repl after the given duration unless dropped before
+pub(crate) struct Alarm {
+    _cancel: mpsc::Sender<()>,
+}
+
+impl Alarm {
+    pub(crate) fn set(after: Duration, interrupts: &Interrupts) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let interrupts = interrupts.clone();
+        std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(after) {
+                interrupts.wake();
+            }
+        });
+        Self { _cancel: cancel }
+    }
+}
+
+/// Reads a line like [Reedline::read_line], returning `None` if a termination signal
+/// has been received or the Repl has been woken before a key was pressed
+pub(crate) fn read_line(
+    editor: &mut Reedline,
+    prompt: &(dyn Prompt + Sync),
+    interrupts: &Interrupts,
+) -> io::Result<Option<Signal>> {
+    let flags = &interrupts.0;
+    flags.reading.store(WAITING, Ordering::SeqCst);
+    let pressed = wait_for_key(editor, prompt, flags);
+    let result = match pressed {
+        Ok(true) => {
+            flags.reading.store(EDITING, Ordering::SeqCst);
+            edit_line(editor, prompt, flags)
+        }
+        Ok(false) => Ok(None),
+        Err(err) => Err(err),
+    };
+    flags.reading.store(IDLE, Ordering::SeqCst);
+    result
+}
+
+/// Lets reedline edit the line, returns `None` if a termination signal has been received
+/// meanwhile
+fn edit_line(
+    editor: &mut Reedline,
+    prompt: &(dyn Prompt + Sync),
+    flags: &Flags,
+) -> io::Result<Option<Signal>> {
+    let prompt = InterruptiblePrompt { prompt, flags };
+    match panic::catch_unwind(AssertUnwindSafe(|| editor.read_line(&prompt))) {
+        Ok(result) => result.map(Some),
+        Err(payload) if payload.is::<Interrupted>() => {
+            // reedline didn't get to leave raw mode
+            terminal::disable_raw_mode()?;
+            io::stdout().write_all(b"\r\n")?;
+            io::stdout().flush()?;
+            Ok(None)
+        }
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Payload unwinding out of reedline once a termination signal has been received
+struct Interrupted;
+
+/// Prompt leaving reedline when it is painted after a termination signal has been
+/// received. Unwinding with [resume_unwind](panic::resume_unwind) doesn't call the panic
+/// hook, so nothing is printed
+struct InterruptiblePrompt<'a> {
+    prompt: &'a (dyn Prompt + Sync),
+    flags: &'a Flags,
+}
+
+impl Prompt for InterruptiblePrompt<'_> {
+    fn render_prompt_left(&self) -> Cow<'_, str> {
+        if self.flags.received.load(Ordering::SeqCst) != 0 {
+            panic::resume_unwind(Box::new(Interrupted));
+        }
+        self.prompt.render_prompt_left()
+    }
+
+    fn render_prompt_right(&self) -> Cow<'_, str> {
+        self.prompt.render_prompt_right()
+    }
+
+    fn render_prompt_indicator(&self, edit_mode: PromptEditMode) -> Cow<'_, str> {
+        self.prompt.render_prompt_indicator(edit_mode)
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
+        self.prompt.render_prompt_multiline_indicator()
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: PromptHistorySearch,
+    ) -> Cow<'_, str> {
+        self.prompt
+            .render_prompt_history_search_indicator(history_search)
+    }
+
+    fn get_prompt_color(&self) -> Color {
+        self.prompt.get_prompt_color()
+    }
+
+    fn get_indicator_color(&self) -> Color {
+        self.prompt.get_indicator_color()
+    }
+
+    fn get_prompt_right_color(&self) -> Color {
+        self.prompt.get_prompt_right_color()
+    }
+}
+
+/// Paints the prompt and waits for a key, returns false if interrupted before. The prompt
+/// is removed again either way, reedline paints its own once it takes over
+fn wait_for_key(editor: &Reedline, prompt: &dyn Prompt, flags: &Flags) -> io::Result<bool> {
+    let painted = format!(
+        "{}{}",
+        prompt.render_prompt_left(),
+        prompt.render_prompt_indicator(editor.prompt_edit_mode())
+    );
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let result = (|| {
+        stdout.write_all(painted.replace('\n', "\r\n").as_bytes())?;
+        stdout.flush()?;
+        loop {
+            if flags.received.load(Ordering::SeqCst) != 0
+                || flags.woken.swap(false, Ordering::SeqCst)
+            {
+                return Ok(false);
+            }
+            if flags.resumed.swap(false, Ordering::SeqCst) {
+                erase(&mut stdout, &painted)?;
+                stdout.write_all(painted.replace('\n', "\r\n").as_bytes())?;
+                stdout.flush()?;
+            }
+            if event::poll(POLL_INTERVAL)? {
+                return Ok(true);
+            }
+        }
+    })();
+    let erased = erase(&mut stdout, &painted);
+    terminal::disable_raw_mode()?;
+    erased?;
+    result
+}
+
+/// Moves back to where `painted` started and clears everything from there
+fn erase(stdout: &mut io::Stdout, painted: &str) -> io::Result<()> {
+    stdout.write_all(b"\r")?;
+    let lines = painted.matches('\n').count() as u16;
+    if lines > 0 {
+        stdout.queue(cursor::MoveUp(lines))?;
+    }
+    stdout.queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
+    stdout.flush()
+}
+
+/// Suspends the process like SIGTSTP does, returning once it is continued
+#[cfg(unix)]
+pub(crate) fn suspend() {
+    let _ = signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP);
+}
+
+#[cfg(not(unix))]
+pub(crate) fn suspend() {}
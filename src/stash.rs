@@ -0,0 +1,144 @@
+//! Keeping the line discarded by Ctrl-C, see
+//! [Repl::with_line_stash](crate::Repl::with_line_stash)
+//!
+//! reedline clears the line on Ctrl-C before the Repl sees it and can't start a prompt
+//! with text in it, so both happen through menus accepted right after being activated:
+//! [StashMenu] saves the line before Ctrl-C clears it, and restores it on a keybinding.
+
+use reedline::{Completer, LineBuffer, Menu, MenuEvent, Painter, ReedlineEvent, Suggestion};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Name of the menu saving the line
+pub(crate) const STASH_MENU: &str = "stash_menu";
+
+/// Name of the menu restoring the saved line
+pub(crate) const RESTORE_MENU: &str = "restore_menu";
+
+/// The line last discarded by Ctrl-C, shared by the line editors of all sessions
+pub(crate) type LineStash = Arc<Mutex<Option<String>>>;
+
+/// Event of Ctrl-C: saves the line, then clears it as usual
+pub(crate) fn stash_event() -> ReedlineEvent {
+    // history search handles neither menus nor multiple events, Ctrl-C still cancels it
+    ReedlineEvent::UntilFound(vec![
+        ReedlineEvent::Multiple(vec![
+            ReedlineEvent::Esc,
+            ReedlineEvent::Menu(STASH_MENU.to_string()),
+            ReedlineEvent::Enter,
+            ReedlineEvent::CtrlC,
+        ]),
+        ReedlineEvent::CtrlC,
+    ])
+}
+
+/// Event of the keybinding replacing the line by the saved one
+pub(crate) fn restore_event() -> ReedlineEvent {
+    ReedlineEvent::Multiple(vec![
+        ReedlineEvent::Esc,
+        ReedlineEvent::Menu(RESTORE_MENU.to_string()),
+        ReedlineEvent::Enter,
+    ])
+}
+
+/// Menu without entries which saves the line to the stash, or restores it from there, when
+/// accepted
+pub(crate) struct StashMenu {
+    stash: LineStash,
+    restore: bool,
+    active: bool,
+}
+
+impl StashMenu {
+    pub(crate) fn save(stash: LineStash) -> Self {
+        Self {
+            stash,
+            restore: false,
+            active: false,
+        }
+    }
+
+    pub(crate) fn restore(stash: LineStash) -> Self {
+        Self {
+            stash,
+            restore: true,
+            active: false,
+        }
+    }
+}
+
+impl Menu for StashMenu {
+    fn name(&self) -> &str {
+        if self.restore {
+            RESTORE_MENU
+        } else {
+            STASH_MENU
+        }
+    }
+
+    fn indicator(&self) -> &str {
+        ""
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::Activate(_) => self.active = true,
+            MenuEvent::Deactivate => self.active = false,
+            _ => {}
+        }
+    }
+
+    fn can_quick_complete(&self) -> bool {
+        false
+    }
+
+    fn can_partially_complete(
+        &mut self,
+        _values_updated: bool,
+        _line_buffer: &mut LineBuffer,
+        _completer: &mut dyn Completer,
+    ) -> bool {
+        false
+    }
+
+    fn update_values(&mut self, _line_buffer: &mut LineBuffer, _completer: &mut dyn Completer) {}
+
+    fn update_working_details(
+        &mut self,
+        _line_buffer: &mut LineBuffer,
+        _completer: &mut dyn Completer,
+        _painter: &Painter,
+    ) {
+    }
+
+    fn replace_in_buffer(&self, line_buffer: &mut LineBuffer) {
+        let mut stash = self.stash.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.restore {
+            if let Some(line) = stash.clone() {
+                line_buffer.set_buffer(line);
+            }
+        } else if !line_buffer.get_buffer().trim().is_empty() {
+            // Ctrl-C on an empty line keeps the line saved before
+            *stash = Some(line_buffer.get_buffer().to_string());
+        }
+    }
+
+    fn menu_required_lines(&self, _terminal_columns: u16) -> u16 {
+        0
+    }
+
+    fn menu_string(&self, _available_lines: u16, _use_ansi_coloring: bool) -> String {
+        String::new()
+    }
+
+    fn min_rows(&self) -> u16 {
+        0
+    }
+
+    fn get_values(&self) -> &[Suggestion] {
+        &[]
+    }
+}
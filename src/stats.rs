@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+/// Usage statistics of a command, see [Repl::stats](crate::Repl::stats)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandStats {
+    invocations: u64,
+    failures: u64,
+    total_duration: Duration,
+}
+
+impl CommandStats {
+    pub(crate) fn record(&mut self, duration: Duration, failed: bool) {
+        self.invocations += 1;
+        self.failures += u64::from(failed);
+        self.total_duration += duration;
+    }
+
+    /// How often the command has been executed
+    pub fn invocations(&self) -> u64 {
+        self.invocations
+    }
+
+    /// How often the command returned an error
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Time spent in the command over all invocations
+    pub fn total_duration(&self) -> Duration {
+        self.total_duration
+    }
+
+    /// Average time an invocation took
+    pub fn average_duration(&self) -> Duration {
+        match self.invocations {
+            0 => Duration::ZERO,
+            invocations => self.total_duration.div_f64(invocations as f64),
+        }
+    }
+}
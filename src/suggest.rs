@@ -0,0 +1,15 @@
+/// Minimum Jaro-Winkler similarity for a name to be suggested, the same as clap uses
+const SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// Returns the candidate most similar to a mistyped input, if any is similar enough
+pub(crate) fn did_you_mean<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro_winkler(input, candidate), candidate))
+        .filter(|(similarity, _)| *similarity > SIMILARITY_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, candidate)| candidate)
+}
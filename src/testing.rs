@@ -0,0 +1,234 @@
+//! Utilities to regression-test your REPL against golden transcripts
+//!
+//! A transcript looks like a terminal session: every line starting with the current prompt
+//! is entered as a command, all other lines are the output (stdout and stderr) expected
+//! from it. ANSI escape sequences and trailing whitespace are ignored when comparing.
+//!
+//! ```rust,no_run
+//! use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+//! use reedline_repl_rs::testing::assert_transcript;
+//! use reedline_repl_rs::{Repl, Result};
+//!
+//! fn hello<T>(args: ArgMatches, _context: &mut T) -> Result<Option<String>> {
+//!     Ok(Some(format!("Hello, {}", args.get_one::<String>("who").unwrap())))
+//! }
+//!
+//! let mut repl = Repl::<(), reedline_repl_rs::Error>::new(())
+//!     .with_name("MyApp")
+//!     .with_command(Command::new("hello").arg(Arg::new("who").required(true)), hello);
+//! assert_transcript(
+//!     &mut repl,
+//!     "MyApp〉hello Friend
+//! Hello, Friend
+//! MyApp〉bye
+//! Error: Unknown command 'bye'",
+//! );
+//! ```
+
+use crate::error::{Error, Result};
+use crate::output::Output;
+use crate::Repl;
+use std::fmt::{Debug, Display};
+use std::io::Write;
+use std::path::Path;
+
+/// Environment variable which makes [assert_golden_file] overwrite the golden file with the
+/// actual transcript instead of comparing against it
+pub const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+
+pub use crate::output::SharedBuffer;
+
+/// Remove ANSI escape sequences (colors, cursor movement) from text
+pub fn strip_ansi(text: &str) -> String {
+    crate::width::strip_ansi(text)
+}
+
+/// Normalize a transcript for comparison: strip ANSI escape sequences, line endings,
+/// trailing whitespace and trailing empty lines
+pub fn normalize(text: &str) -> String {
+    let stripped = strip_ansi(text);
+    let lines: Vec<&str> = stripped.lines().map(|line| line.trim_end()).collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Returns the entered command if line starts with the prompt
+fn transcript_input(line: &str, prompt: &str) -> Option<String> {
+    let line = strip_ansi(line);
+    let prompt = strip_ansi(prompt);
+    if line.trim_end() == prompt.trim_end() {
+        return Some(String::new());
+    }
+    line.strip_prefix(&prompt).map(|input| input.to_string())
+}
+
+fn capture(
+    output: &mut Output,
+    buffer: &SharedBuffer,
+) -> (Box<dyn Write + Send>, Box<dyn Write + Send>) {
+    (
+        output.set_stdout(Box::new(buffer.clone())),
+        output.set_stderr(Box::new(buffer.clone())),
+    )
+}
+
+fn restore(output: &mut Output, writers: (Box<dyn Write + Send>, Box<dyn Write + Send>)) {
+    output.set_stdout(writers.0);
+    output.set_stderr(writers.1);
+}
+
+fn echo(buffer: &SharedBuffer, prompt: &str, input: &str) -> Result<()> {
+    writeln!(buffer.clone(), "{}{}", prompt, input)
+        .map_err(|err| Error::IoError("<transcript>".to_string(), err.to_string()))
+}
+
+fn run_lines<Context, E>(
+    repl: &mut Repl<Context, E>,
+    transcript: &str,
+    buffer: &SharedBuffer,
+) -> Result<()>
+where
+    E: Display + From<Error> + Debug,
+{
+    for line in transcript.lines() {
+        let prompt = repl.prompt_text();
+        if let Some(input) = transcript_input(line, &prompt) {
+            echo(buffer, &prompt, &input)?;
+            repl.execute_line(input)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+async fn run_lines_async<Context, E>(
+    repl: &mut Repl<Context, E>,
+    transcript: &str,
+    buffer: &SharedBuffer,
+) -> Result<()>
+where
+    E: Display + From<Error> + Debug,
+{
+    for line in transcript.lines() {
+        let prompt = repl.prompt_text();
+        if let Some(input) = transcript_input(line, &prompt) {
+            echo(buffer, &prompt, &input)?;
+            repl.execute_line_async(input).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Enter the commands of transcript into repl and return the transcript of the actual
+/// session, i.e. the commands with prompt followed by the output they produced
+pub fn run_transcript<Context, E>(repl: &mut Repl<Context, E>, transcript: &str) -> Result<String>
+where
+    E: Display + From<Error> + Debug,
+{
+    let buffer = SharedBuffer::new();
+    let writers = capture(repl.output_mut(), &buffer);
+    let result = run_lines(repl, transcript, &buffer);
+    restore(repl.output_mut(), writers);
+    result.map(|_| buffer.contents())
+}
+
+/// Enter the commands of transcript into repl and return the transcript of the actual
+/// session, see [run_transcript]
+#[cfg(feature = "async")]
+pub async fn run_transcript_async<Context, E>(
+    repl: &mut Repl<Context, E>,
+    transcript: &str,
+) -> Result<String>
+where
+    E: Display + From<Error> + Debug,
+{
+    let buffer = SharedBuffer::new();
+    let writers = capture(repl.output_mut(), &buffer);
+    let result = run_lines_async(repl, transcript, &buffer).await;
+    restore(repl.output_mut(), writers);
+    result.map(|_| buffer.contents())
+}
+
+fn compare(expected: &str, actual: &str) {
+    let (expected, actual) = (normalize(expected), normalize(actual));
+    if expected == actual {
+        return;
+    }
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let line = expected_lines
+        .iter()
+        .zip(actual_lines.iter())
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or_else(|| expected_lines.len().min(actual_lines.len()));
+    panic!(
+        "transcript differs at line {}\n--- expected\n{}\n--- actual\n{}\n",
+        line + 1,
+        expected,
+        actual
+    );
+}
+
+/// Run the commands of the expected transcript and panic if the actual session differs
+pub fn assert_transcript<Context, E>(repl: &mut Repl<Context, E>, expected: &str)
+where
+    E: Display + From<Error> + Debug,
+{
+    let actual = run_transcript(repl, expected).expect("failed to run transcript");
+    compare(expected, &actual);
+}
+
+/// Run the commands of the expected transcript and panic if the actual session differs
+#[cfg(feature = "async")]
+pub async fn assert_transcript_async<Context, E>(repl: &mut Repl<Context, E>, expected: &str)
+where
+    E: Display + From<Error> + Debug,
+{
+    let actual = run_transcript_async(repl, expected)
+        .await
+        .expect("failed to run transcript");
+    compare(expected, &actual);
+}
+
+fn read_golden(path: &Path) -> String {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("failed to read golden file {}: {}", path.display(), err))
+}
+
+fn update_golden(path: &Path, actual: &str) -> bool {
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_none() {
+        return false;
+    }
+    std::fs::write(path, normalize(actual) + "\n")
+        .unwrap_or_else(|err| panic!("failed to write golden file {}: {}", path.display(), err));
+    true
+}
+
+/// Run the commands of the golden transcript file and panic if the actual session differs.
+/// With the `UPDATE_GOLDEN` environment variable set, the file is overwritten with the
+/// actual transcript instead.
+pub fn assert_golden_file<Context, E>(repl: &mut Repl<Context, E>, path: &Path)
+where
+    E: Display + From<Error> + Debug,
+{
+    let expected = read_golden(path);
+    let actual = run_transcript(repl, &expected).expect("failed to run transcript");
+    if !update_golden(path, &actual) {
+        compare(&expected, &actual);
+    }
+}
+
+/// Run the commands of the golden transcript file and panic if the actual session differs,
+/// see [assert_golden_file]
+#[cfg(feature = "async")]
+pub async fn assert_golden_file_async<Context, E>(repl: &mut Repl<Context, E>, path: &Path)
+where
+    E: Display + From<Error> + Debug,
+{
+    let expected = read_golden(path);
+    let actual = run_transcript_async(repl, &expected)
+        .await
+        .expect("failed to run transcript");
+    if !update_golden(path, &actual) {
+        compare(&expected, &actual);
+    }
+}
@@ -0,0 +1,172 @@
+//! Colors of everything a Repl paints, with built-in presets
+
+use crate::highlighter::HighlightColors;
+use nu_ansi_term::{Color as LineColor, Style as LineStyle};
+use yansi::{Color, Style};
+
+/// The colors of a Repl, set with [Repl::with_theme](crate::Repl::with_theme). Start from a
+/// preset and change single colors with struct update syntax:
+///
+/// ```rust
+/// # use reedline_repl_rs::Theme;
+/// # use reedline_repl_rs::yansi::{Color, Style};
+/// let theme = Theme {
+///     prompt: Some(Style::new(Color::Magenta).bold()),
+///     ..Theme::dark()
+/// };
+/// ```
+///
+/// The line editor is painted by reedline and uses `nu_ansi_term` styles, everything else
+/// `yansi` styles.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// The prompt, None keeps the colors of the prompt text
+    pub prompt: Option<Style>,
+    /// History suggestions shown after the cursor
+    pub hinter: LineStyle,
+    /// The input line
+    pub highlight: HighlightColors,
+    /// Entries of the completion menu
+    pub menu_text: LineStyle,
+    /// The selected entry of the completion menu
+    pub menu_selected: LineStyle,
+    /// Descriptions in the completion menu
+    pub menu_description: LineStyle,
+    /// The `error:` prefix of [chain_error_handler](crate::chain_error_handler)
+    pub error: Style,
+    /// The `caused by:` prefix of [chain_error_handler](crate::chain_error_handler)
+    pub error_cause: Style,
+    /// The Repl name in the help overview
+    pub help_name: Style,
+    /// Section headings of the help
+    pub help_heading: Style,
+    /// Command names in the help overview
+    pub help_command: Style,
+    /// Deprecated commands in the help overview
+    pub help_deprecated: Style,
+    /// Warnings, e.g. about using a deprecated command
+    pub warning: Style,
+    /// Notifications about something that finished successfully
+    pub success: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    /// Bright colors for dark terminal backgrounds, the default
+    pub fn dark() -> Self {
+        Self {
+            prompt: None,
+            hinter: LineStyle::new().italic().fg(LineColor::LightGray),
+            highlight: HighlightColors::default(),
+            menu_text: LineColor::DarkGray.normal(),
+            menu_selected: LineColor::Green.bold().reverse(),
+            menu_description: LineColor::Yellow.normal(),
+            error: Style::new(Color::Red).bold(),
+            error_cause: Style::new(Color::Yellow),
+            help_name: Style::new(Color::Green).bold(),
+            help_heading: Style::new(Color::Yellow).bold(),
+            help_command: Style::default(),
+            help_deprecated: Style::default().dimmed(),
+            warning: Style::new(Color::Yellow),
+            success: Style::new(Color::Green),
+        }
+    }
+
+    /// Dark colors for light terminal backgrounds
+    pub fn light() -> Self {
+        Self {
+            prompt: Some(Style::new(Color::Blue).bold()),
+            hinter: LineStyle::new().italic().fg(LineColor::DarkGray),
+            highlight: HighlightColors {
+                command: LineColor::Blue,
+                unknown_command: LineColor::Red,
+                flag: LineColor::Purple,
+                invalid_flag: LineColor::Red,
+                value: LineColor::Cyan,
+                string: LineColor::Green,
+                argument: LineColor::Black,
+            },
+            menu_text: LineStyle::new().fg(LineColor::Black),
+            menu_selected: LineStyle::new().on(LineColor::Blue).fg(LineColor::White),
+            menu_description: LineStyle::new().fg(LineColor::Purple),
+            error: Style::new(Color::Red).bold(),
+            error_cause: Style::new(Color::Magenta),
+            help_name: Style::new(Color::Blue).bold(),
+            help_heading: Style::new(Color::Magenta).bold(),
+            help_command: Style::default(),
+            help_deprecated: Style::default().dimmed(),
+            warning: Style::new(Color::Magenta),
+            success: Style::new(Color::Green),
+        }
+    }
+
+    /// No colors, only bold, italic and reverse
+    pub fn monochrome() -> Self {
+        Self {
+            prompt: Some(Style::default().bold()),
+            hinter: LineStyle::new().italic(),
+            highlight: HighlightColors {
+                command: LineColor::Default,
+                unknown_command: LineColor::Default,
+                flag: LineColor::Default,
+                invalid_flag: LineColor::Default,
+                value: LineColor::Default,
+                string: LineColor::Default,
+                argument: LineColor::Default,
+            },
+            menu_text: LineStyle::new(),
+            menu_selected: LineStyle::new().reverse(),
+            menu_description: LineStyle::new().italic(),
+            error: Style::default().bold(),
+            error_cause: Style::default().italic(),
+            help_name: Style::default().bold(),
+            help_heading: Style::default().bold(),
+            help_command: Style::default(),
+            help_deprecated: Style::default().dimmed(),
+            warning: Style::default().bold(),
+            success: Style::default(),
+        }
+    }
+
+    /// The Solarized palette
+    pub fn solarized() -> Self {
+        let base01 = (88, 110, 117);
+        let base1 = (147, 161, 161);
+        let yellow = (181, 137, 0);
+        let red = (220, 50, 47);
+        let blue = (38, 139, 210);
+        let cyan = (42, 161, 152);
+        let green = (133, 153, 0);
+        let line = |(r, g, b)| LineColor::Rgb(r, g, b);
+        let color = |(r, g, b)| Color::RGB(r, g, b);
+        Self {
+            prompt: Some(Style::new(color(blue)).bold()),
+            hinter: LineStyle::new().italic().fg(line(base01)),
+            highlight: HighlightColors {
+                command: line(green),
+                unknown_command: line(red),
+                flag: line(cyan),
+                invalid_flag: line(red),
+                value: line(blue),
+                string: line(yellow),
+                argument: line(base1),
+            },
+            menu_text: LineStyle::new().fg(line(base1)),
+            menu_selected: LineStyle::new().on(line(blue)).fg(line(base1)),
+            menu_description: LineStyle::new().fg(line(cyan)),
+            error: Style::new(color(red)).bold(),
+            error_cause: Style::new(color(yellow)),
+            help_name: Style::new(color(blue)).bold(),
+            help_heading: Style::new(color(yellow)).bold(),
+            help_command: Style::new(color(cyan)),
+            help_deprecated: Style::new(color(base01)),
+            warning: Style::new(color(yellow)),
+            success: Style::new(color(green)),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Embedding a Repl as console pane of a ratatui application
+
+use crate::completer::ReplCompleter;
+use crate::error::{Error, Result};
+use crate::output::SharedBuffer;
+use crate::width::{self, display_width, strip_ansi};
+use crate::Repl;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::Widget;
+use reedline::{Completer, Suggestion};
+use std::cell::Cell;
+use std::fmt::Display;
+
+/// Number of output lines kept for scrolling
+const SCROLLBACK: usize = 1000;
+/// Number of entered lines kept for Up and Down
+const HISTORY_CAPACITY: usize = 100;
+/// Rows of the completion menu shown above the input line
+const MENU_ROWS: usize = 5;
+
+/// A Repl as widget of a ratatui application, so a TUI can embed a command console
+/// without giving the whole terminal to reedline.
+///
+/// The console shows the scrollable output above an input line with a completion menu.
+/// The application passes key events to [handle_key](ReplConsole::handle_key) and renders
+/// `&console` into the area of its layout. Key events are those of the `crossterm` version
+/// re-exported by this crate.
+///
+/// ```rust,ignore
+/// let mut console = ReplConsole::new(make_repl())?;
+/// loop {
+///     terminal.draw(|frame| {
+///         let area = frame.size();
+///         frame.render_widget(&console, area);
+///         let (x, y) = console.cursor_position(area);
+///         frame.set_cursor(x, y);
+///     })?;
+///     if let Event::Key(key) = event::read()? {
+///         if !console.handle_key(key)? {
+///             break;
+///         }
+///     }
+/// }
+/// ```
+///
+/// Keys: Enter executes the line, Tab completes or cycles through the completion menu,
+/// Up and Down recall the history (or move in the menu), PageUp and PageDown scroll the
+/// output, Esc closes the menu and Ctrl-C discards the line. With a
+/// [login callback](Repl::with_login) the password is entered on the hidden input line
+/// before the banner.
+pub struct ReplConsole<Context, E: Display> {
+    repl: Repl<Context, E>,
+    output: SharedBuffer,
+    completer: ReplCompleter,
+    lines: Vec<String>,
+    input: String,
+    history: Vec<String>,
+    history_position: usize,
+    suggestions: Vec<Suggestion>,
+    selected: usize,
+    scroll: usize,
+    width: Cell<Option<usize>>,
+}
+
+impl<Context, E> ReplConsole<Context, E>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    /// Create a console running `repl`. Prints the banner, calls the start callback and
+    /// executes the rc file
+    pub fn new(repl: Repl<Context, E>) -> Result<Self> {
+        let output = SharedBuffer::new();
+        let mut repl = repl
+            .with_output_writer(Box::new(output.clone()))
+            .with_error_writer(Box::new(output.clone()))
+            .with_help_pager(false);
+        repl.begin_embedded_session()?;
+        let completer = repl.completer();
+        let mut console = Self {
+            repl,
+            output,
+            completer,
+            lines: vec![],
+            input: String::new(),
+            history: vec![],
+            history_position: 0,
+            suggestions: vec![],
+            selected: 0,
+            scroll: 0,
+            width: Cell::new(None),
+        };
+        console.collect_output();
+        Ok(console)
+    }
+
+    /// The Repl of the console, e.g. to inspect its context
+    pub fn repl(&self) -> &Repl<Context, E> {
+        &self.repl
+    }
+
+    /// The Repl of the console, e.g. to change its context
+    pub fn repl_mut(&mut self) -> &mut Repl<Context, E> {
+        &mut self.repl
+    }
+
+    /// Returns true while the Repl runs
+    pub fn is_running(&self) -> bool {
+        self.repl.is_running()
+    }
+
+    /// Handle a key press. Returns false once a command quit the Repl, after its exit
+    /// callback has been called
+    pub fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if !self.repl.is_running() {
+            return Ok(false);
+        }
+        let menu_open = !self.suggestions.is_empty();
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.clear();
+                self.suggestions.clear();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.suggestions.clear();
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.suggestions.clear();
+            }
+            KeyCode::Tab | KeyCode::Down if menu_open => {
+                self.selected = (self.selected + 1) % self.suggestions.len();
+            }
+            KeyCode::BackTab | KeyCode::Up if menu_open => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(self.suggestions.len() - 1);
+            }
+            KeyCode::Enter if menu_open => self.accept_suggestion(),
+            KeyCode::Esc => self.suggestions.clear(),
+            KeyCode::Tab if !self.repl.login_pending() => self.complete(),
+            KeyCode::Up if self.history_position > 0 => {
+                self.history_position -= 1;
+                self.input = self.history[self.history_position].clone();
+            }
+            KeyCode::Down if self.history_position < self.history.len() => {
+                self.history_position += 1;
+                self.input = self
+                    .history
+                    .get(self.history_position)
+                    .cloned()
+                    .unwrap_or_default();
+            }
+            KeyCode::PageUp => {
+                self.scroll = (self.scroll + MENU_ROWS * 2).min(self.lines.len());
+            }
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(MENU_ROWS * 2),
+            KeyCode::Enter => return self.execute(),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Position of the terminal cursor at the end of the input line, when the console is
+    /// rendered into `area`
+    pub fn cursor_position(&self, area: Rect) -> (u16, u16) {
+        let column = (display_width(&self.prompt()) + display_width(self.visible_input()))
+            .min(area.width.saturating_sub(1) as usize);
+        (
+            area.x + column as u16,
+            area.y + area.height.saturating_sub(1),
+        )
+    }
+
+    fn prompt(&self) -> String {
+        strip_ansi(&self.repl.prompt_text())
+    }
+
+    /// The input line as shown, nothing while a password is entered
+    fn visible_input(&self) -> &str {
+        if self.repl.login_pending() {
+            ""
+        } else {
+            &self.input
+        }
+    }
+
+    fn execute(&mut self) -> Result<bool> {
+        if self.repl.login_pending() {
+            return self.login();
+        }
+        let line = std::mem::take(&mut self.input);
+        self.lines.push(format!("{}{}", self.prompt(), line));
+        self.add_history(&line);
+        self.scroll = 0;
+        self.repl.set_help_width(self.width.get());
+        self.repl.execute_line(line)?;
+        self.collect_output();
+        if self.repl.is_running() {
+            return Ok(true);
+        }
+        self.repl.end_embedded_session()?;
+        self.collect_output();
+        Ok(false)
+    }
+
+    /// Pass the entered password to the login callback and start the session once it let the
+    /// user in
+    fn login(&mut self) -> Result<bool> {
+        let password = std::mem::take(&mut self.input);
+        self.lines.push(self.prompt());
+        if self.repl.submit_password(&password)? {
+            self.repl.begin_embedded_session()?;
+        }
+        self.collect_output();
+        Ok(self.repl.is_running())
+    }
+
+    fn add_history(&mut self, line: &str) {
+        let line = line.trim();
+        if !line.is_empty() && self.history.last().map(String::as_str) != Some(line) {
+            if self.history.len() == HISTORY_CAPACITY {
+                self.history.remove(0);
+            }
+            self.history.push(line.to_string());
+        }
+        self.history_position = self.history.len();
+    }
+
+    fn complete(&mut self) {
+        let suggestions = self.completer.complete(&self.input, self.input.len());
+        match suggestions.len() {
+            0 => {}
+            1 => {
+                self.suggestions = suggestions;
+                self.selected = 0;
+                self.accept_suggestion();
+            }
+            _ => {
+                self.suggestions = suggestions;
+                self.selected = 0;
+            }
+        }
+    }
+
+    fn accept_suggestion(&mut self) {
+        if let Some(suggestion) = self.suggestions.get(self.selected) {
+            let start = suggestion.span.start.min(self.input.len());
+            self.input.truncate(start);
+            self.input.push_str(&suggestion.value);
+            if suggestion.append_whitespace {
+                self.input.push(' ');
+            }
+        }
+        self.suggestions.clear();
+    }
+
+    fn collect_output(&mut self) {
+        let output = strip_ansi(&self.output.take());
+        self.lines
+            .extend(output.lines().map(|line| line.to_string()));
+        if self.lines.len() > SCROLLBACK {
+            self.lines.drain(..self.lines.len() - SCROLLBACK);
+        }
+    }
+
+    /// The output lines wrapped at `width` columns
+    fn wrapped_lines(&self, width: usize) -> Vec<String> {
+        let mut wrapped = vec![];
+        for line in &self.lines {
+            wrapped.extend(width::chunks(line, width.max(1)));
+        }
+        wrapped
+    }
+}
+
+impl<Context, E> Widget for &ReplConsole<Context, E>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+{
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        let width = area.width as usize;
+        self.width.set(Some(width));
+        let output_rows = area.height as usize - 1;
+
+        let lines = self.wrapped_lines(width);
+        let end = lines.len().saturating_sub(self.scroll.min(lines.len()));
+        let start = end.saturating_sub(output_rows);
+        for (row, line) in lines[start..end].iter().enumerate() {
+            buf.set_stringn(area.x, area.y + row as u16, line, width, Style::default());
+        }
+
+        let input_y = area.y + area.height - 1;
+        let input = format!("{}{}", self.prompt(), self.visible_input());
+        // keep the end of long input visible
+        let input = width::tail(&input, width.saturating_sub(1));
+        buf.set_stringn(area.x, input_y, input, width, Style::default());
+
+        // the menu covers the output right above the input line
+        let rows = self.suggestions.len().min(MENU_ROWS).min(output_rows);
+        let first = (self.selected + 1).saturating_sub(rows);
+        for (row, suggestion) in self.suggestions[first..first + rows].iter().enumerate() {
+            let y = input_y - rows as u16 + row as u16;
+            let style = if first + row == self.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            let text = match &suggestion.description {
+                Some(description) => format!(" {}  {} ", suggestion.value, description),
+                None => format!(" {} ", suggestion.value),
+            };
+            buf.set_stringn(area.x, y, " ".repeat(width), width, Style::default());
+            buf.set_stringn(area.x, y, &text, width, style);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Snapshots of the Context for the `checkpoint` and `undo` built-in commands
+
+use crate::error::{Error, Result};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of commands which can be undone
+const UNDO_STEPS: usize = 100;
+
+pub(crate) struct UndoHistory<Context> {
+    // Context: Clone is only required where undo is turned on
+    clone: fn(&Context) -> Context,
+    steps: VecDeque<Context>,
+    checkpoints: BTreeMap<String, Context>,
+}
+
+impl<Context> UndoHistory<Context> {
+    pub(crate) fn new() -> Self
+    where
+        Context: Clone,
+    {
+        Self {
+            clone: Context::clone,
+            steps: VecDeque::new(),
+            checkpoints: BTreeMap::new(),
+        }
+    }
+
+    /// Remember the Context before a command changes it
+    pub(crate) fn record_step(&mut self, context: &Context) {
+        if self.steps.len() == UNDO_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps.push_back((self.clone)(context));
+    }
+
+    /// Forget the steps, e.g. as they belong to another session
+    pub(crate) fn clear_steps(&mut self) {
+        self.steps.clear();
+    }
+
+    /// The Context before the last command
+    pub(crate) fn undo_step(&mut self) -> Result<Context> {
+        self.steps.pop_back().ok_or(Error::NothingToUndo)
+    }
+
+    pub(crate) fn checkpoint(&mut self, name: &str, context: &Context) {
+        self.checkpoints
+            .insert(name.to_string(), (self.clone)(context));
+    }
+
+    /// The Context saved as checkpoint `name`. The checkpoint is kept, so it can be
+    /// returned to again
+    pub(crate) fn rollback(&mut self, name: &str) -> Result<Context> {
+        let context = self
+            .checkpoints
+            .get(name)
+            .ok_or_else(|| Error::UnknownCheckpoint(name.to_string()))?;
+        Ok((self.clone)(context))
+    }
+
+    pub(crate) fn checkpoints(&self) -> impl Iterator<Item = &String> {
+        self.checkpoints.keys()
+    }
+}
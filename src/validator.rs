@@ -0,0 +1,74 @@
+//! Deciding whether the input line is complete
+
+use reedline::{ValidationResult, Validator};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// A validator shared by the line editors of all sessions of a Repl
+#[derive(Clone)]
+pub(crate) struct SharedValidator(pub(crate) Arc<Mutex<Box<dyn Validator>>>);
+
+impl Validator for SharedValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        self.0
+            .lock()
+            .expect("validator lock poisoned")
+            .validate(line)
+    }
+}
+
+/// Marker ending the line of a command which takes a multi-line body
+pub(crate) const BODY_START: &str = "<<";
+
+/// Line ending a multi-line body
+pub(crate) const BODY_END: &str = ".";
+
+/// Keeps the input open while the body of a command added with
+/// [with_command_body](crate::Repl::with_command_body) is entered
+pub(crate) struct BodyValidator {
+    commands: HashSet<String>,
+    inner: Box<dyn Validator>,
+}
+
+impl BodyValidator {
+    pub(crate) fn new(commands: HashSet<String>, inner: Box<dyn Validator>) -> Self {
+        Self { commands, inner }
+    }
+}
+
+impl Validator for BodyValidator {
+    fn validate(&self, line: &str) -> ValidationResult {
+        if opens_body(line, &self.commands)
+            && line.lines().skip(1).last().map(str::trim) != Some(BODY_END)
+        {
+            return ValidationResult::Incomplete;
+        }
+        self.inner.validate(line)
+    }
+}
+
+/// Returns true if the first line of the input is a command taking a body ending with the
+/// body marker
+pub(crate) fn opens_body(input: &str, commands: &HashSet<String>) -> bool {
+    let first = input.lines().next().unwrap_or_default().trim();
+    let command = first.split_whitespace().next().unwrap_or_default();
+    first.ends_with(BODY_START) && commands.contains(command)
+}
+
+/// Split an input with a body into the command line without the body marker and the body
+/// without the closing line, None if it doesn't have one
+pub(crate) fn split_body<'a>(
+    input: &'a str,
+    commands: &HashSet<String>,
+) -> Option<(&'a str, String)> {
+    if !opens_body(input, commands) {
+        return None;
+    }
+    let mut lines = input.lines();
+    let head = lines.next()?.trim_end().strip_suffix(BODY_START)?;
+    let mut body: Vec<&str> = lines.collect();
+    if body.last().map(|line| line.trim()) == Some(BODY_END) {
+        body.pop();
+    }
+    Some((head, body.join("\n")))
+}
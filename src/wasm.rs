@@ -0,0 +1,251 @@
+//! Commands implemented by sandboxed WebAssembly modules
+
+use crate::error::Error;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, TypedFunc,
+};
+
+/// The values of the Context WASM plugins may read and write. Everything is denied per
+/// default, implement the methods to grant access.
+///
+/// A plugin module loaded with [Repl::load_wasm_plugin](crate::Repl::load_wasm_plugin)
+/// exports:
+/// - `memory`
+/// - `alloc(len: i32) -> i32` reserving `len` bytes for data passed in by the host
+/// - `commands() -> i64` returning its commands as UTF-8 lines of `name<TAB>about`
+/// - `run(ptr: i32, len: i32) -> i64` executing the command line given as UTF-8 with the
+///   command name and each argument on a line of its own, returning the output or, as
+///   negated value, an error message
+///
+/// Strings are returned as `ptr << 32 | len` in the plugin's memory, a length of 0 means
+/// no output. The module can't access the host except through the imports of module
+/// `repl`, which are answered by the Context's [WasmCapabilities]:
+/// - `get(key_ptr: i32, key_len: i32) -> i64` returning the value or -1
+/// - `set(key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32) -> i32` returning 1
+///   if the value was accepted
+pub trait WasmCapabilities {
+    /// The value of `key`, None if it doesn't exist or may not be read
+    fn get(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    /// Set `key` to `value`, returns false if the value was rejected
+    fn set(&mut self, _key: &str, _value: &str) -> bool {
+        false
+    }
+}
+
+struct HostState {
+    // only set while a command runs, see WasmPlugin::run
+    capabilities: Option<*mut dyn WasmCapabilities>,
+}
+
+/// Fuel of a call into a module if not set with [Repl::with_wasm_fuel](crate::Repl::with_wasm_fuel)
+pub(crate) const DEFAULT_FUEL: u64 = 1_000_000_000;
+
+/// Commands of a plugin module as `(name, about)` pairs
+type PluginCommands = Vec<(String, String)>;
+
+/// A loaded plugin module, shared by the callbacks of its commands
+pub(crate) struct WasmPlugin {
+    name: String,
+    store: Store<HostState>,
+    fuel: u64,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    run: TypedFunc<(i32, i32), i64>,
+}
+
+impl WasmPlugin {
+    /// Load and instantiate a module, returns the plugin with its commands. Every call into
+    /// the module may consume `fuel`, roughly the number of instructions, so a module can't
+    /// hang the REPL
+    pub(crate) fn load(
+        path: &Path,
+        fuel: u64,
+    ) -> Result<(Rc<RefCell<Self>>, PluginCommands), Error> {
+        let name = path.display().to_string();
+        let load_error = |err: anyhow::Error| Error::PluginError(name.clone(), err.to_string());
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(load_error)?;
+        let module = Module::from_file(&engine, path).map_err(load_error)?;
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("repl", "get", host_get)
+            .and_then(|linker| linker.func_wrap("repl", "set", host_set))
+            .map_err(load_error)?;
+        let mut store = Store::new(&engine, HostState { capabilities: None });
+        refuel(&mut store, fuel).map_err(load_error)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(load_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| Error::PluginError(name.clone(), "no memory exported".to_string()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(load_error)?;
+        let run = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "run")
+            .map_err(load_error)?;
+        refuel(&mut store, fuel).map_err(load_error)?;
+        let listing = Self::list_commands(&instance, &mut store, memory).map_err(load_error)?;
+        let commands = listing
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| match line.split_once('\t') {
+                Some((name, about)) => (name.trim().to_string(), about.trim().to_string()),
+                None => (line.trim().to_string(), String::new()),
+            })
+            .collect();
+        let plugin = Self {
+            name,
+            store,
+            fuel,
+            memory,
+            alloc,
+            run,
+        };
+        Ok((Rc::new(RefCell::new(plugin)), commands))
+    }
+
+    fn list_commands(
+        instance: &Instance,
+        store: &mut Store<HostState>,
+        memory: Memory,
+    ) -> anyhow::Result<String> {
+        let commands = instance.get_typed_func::<(), i64>(&mut *store, "commands")?;
+        let (ptr, len) = unpack(commands.call(&mut *store, ())?);
+        read_string(memory.data(&*store), ptr, len)
+    }
+
+    /// Name of the plugin module, for error messages
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Execute a command line, granting the module access to `capabilities` meanwhile
+    pub(crate) fn run(
+        &mut self,
+        line: &[String],
+        capabilities: &mut dyn WasmCapabilities,
+    ) -> Result<Option<String>, String> {
+        let input = line.join("\n");
+        let len = i32::try_from(input.len()).map_err(|_| "command line too long".to_string())?;
+        refuel(&mut self.store, self.fuel).map_err(|err| err.to_string())?;
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(|err| err.to_string())?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, input.as_bytes())
+            .map_err(|err| err.to_string())?;
+        // SAFETY: the pointer is only used by the host functions during the call below,
+        // while the borrow of `capabilities` is alive, and removed afterwards
+        let capabilities: *mut (dyn WasmCapabilities + '_) = capabilities;
+        self.store.data_mut().capabilities = Some(unsafe {
+            std::mem::transmute::<*mut (dyn WasmCapabilities + '_), *mut dyn WasmCapabilities>(
+                capabilities,
+            )
+        });
+        let result = self.run.call(&mut self.store, (ptr, len));
+        self.store.data_mut().capabilities = None;
+        // the trap, e.g. running out of fuel, is the root cause of the error
+        let packed = result.map_err(|err| err.root_cause().to_string())?;
+        let data = self.memory.data(&self.store);
+        if packed < 0 {
+            let (ptr, len) = unpack(packed.checked_neg().ok_or("invalid error returned")?);
+            return Err(read_string(data, ptr, len).map_err(|err| err.to_string())?);
+        }
+        let (ptr, len) = unpack(packed);
+        let output = read_string(data, ptr, len).map_err(|err| err.to_string())?;
+        Ok(if output.is_empty() {
+            None
+        } else {
+            Some(output)
+        })
+    }
+}
+
+/// Give the store `fuel` for the next call into the module
+fn refuel(store: &mut Store<HostState>, fuel: u64) -> anyhow::Result<()> {
+    let remaining = store.fuel_remaining().unwrap_or(0);
+    if remaining < fuel {
+        store.add_fuel(fuel - remaining)?;
+    }
+    Ok(())
+}
+
+/// Split a string passed as `ptr << 32 | len` into pointer and length
+fn unpack(packed: i64) -> (u32, u32) {
+    ((packed as u64 >> 32) as u32, packed as u32)
+}
+
+fn pack(ptr: u32, len: u32) -> i64 {
+    ((ptr as u64) << 32 | len as u64) as i64
+}
+
+fn read_string(data: &[u8], ptr: u32, len: u32) -> anyhow::Result<String> {
+    let start = ptr as usize;
+    let bytes = start
+        .checked_add(len as usize)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| anyhow::anyhow!("string out of bounds"))?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn caller_memory(caller: &mut Caller<'_, HostState>) -> anyhow::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("no memory exported"))
+}
+
+fn caller_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> anyhow::Result<String> {
+    let memory = caller_memory(caller)?;
+    read_string(memory.data(&*caller), ptr as u32, len as u32)
+}
+
+fn capabilities<'a>(
+    caller: &'a mut Caller<'_, HostState>,
+) -> anyhow::Result<&'a mut dyn WasmCapabilities> {
+    match caller.data_mut().capabilities {
+        // SAFETY: set by WasmPlugin::run for the duration of the call only
+        Some(capabilities) => Ok(unsafe { &mut *capabilities }),
+        None => Err(anyhow::anyhow!("capabilities used outside of a command")),
+    }
+}
+
+fn host_get(mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32) -> anyhow::Result<i64> {
+    let key = caller_string(&mut caller, key_ptr, key_len)?;
+    let value = match capabilities(&mut caller)?.get(&key) {
+        Some(value) => value,
+        None => return Ok(-1),
+    };
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| anyhow::anyhow!("no alloc exported"))?
+        .typed::<i32, i32>(&caller)?;
+    let len = i32::try_from(value.len())?;
+    let ptr = alloc.call(&mut caller, len)?;
+    let memory = caller_memory(&mut caller)?;
+    memory.write(&mut caller, ptr as u32 as usize, value.as_bytes())?;
+    Ok(pack(ptr as u32, len as u32))
+}
+
+fn host_set(
+    mut caller: Caller<'_, HostState>,
+    key_ptr: i32,
+    key_len: i32,
+    value_ptr: i32,
+    value_len: i32,
+) -> anyhow::Result<i32> {
+    let key = caller_string(&mut caller, key_ptr, key_len)?;
+    let value = caller_string(&mut caller, value_ptr, value_len)?;
+    Ok(capabilities(&mut caller)?.set(&key, &value) as i32)
+}
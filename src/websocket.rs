@@ -0,0 +1,93 @@
+//! REPL sessions over a WebSocket, for browser terminals like xterm.js
+
+use crate::error::{Error, Result};
+use crate::session::{ReplSession, ResizeHandle};
+use crate::Repl;
+use futures_util::future::{self, Either};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::fmt::Display;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Run a [ReplSession] on an accepted WebSocket until either side ends it. Returns the
+/// Repl, e.g. to inspect its context.
+///
+/// Binary and text messages from the client are keystrokes, as sent by xterm.js `onData`.
+/// A text message `{"type": "resize", "cols": 120, "rows": 40}` reports a new terminal
+/// size instead. The output is sent as binary messages to be written to the terminal.
+pub async fn run_websocket<Context, E, S>(
+    repl: Repl<Context, E>,
+    socket: WebSocketStream<S>,
+) -> Result<Repl<Context, E>>
+where
+    E: Display + From<Error> + std::fmt::Debug,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut sink, mut messages) = socket.split();
+    let (client, server) = tokio::io::duplex(4096);
+    let (server_reader, server_writer) = tokio::io::split(server);
+    let (mut client_reader, mut client_writer) = tokio::io::split(client);
+    let session = ReplSession::new(repl, server_reader, server_writer);
+    let resize = session.resize_handle();
+
+    let incoming = Box::pin(async move {
+        while let Some(Ok(message)) = messages.next().await {
+            let written = match message {
+                Message::Binary(bytes) => client_writer.write_all(&bytes).await,
+                Message::Text(text) if apply_resize(&text, &resize) => Ok(()),
+                Message::Text(text) => client_writer.write_all(text.as_bytes()).await,
+                Message::Close(_) => break,
+                _ => Ok(()),
+            };
+            if written.is_err() {
+                break;
+            }
+        }
+        // the session reads the end of its input and finishes
+        let _ = client_writer.shutdown().await;
+    });
+    let outgoing = async move {
+        let mut buffer = [0u8; 4096];
+        while let Ok(count) = client_reader.read(&mut buffer).await {
+            if count == 0
+                || sink
+                    .send(Message::Binary(buffer[..count].to_vec()))
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    };
+    let running = Box::pin(future::join(session.run(), outgoing));
+
+    match future::select(running, incoming).await {
+        Either::Left(((result, _), _)) => result,
+        Either::Right((_, running)) => running.await.0,
+    }
+}
+
+/// Text message from the client which isn't keystrokes
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    Resize { cols: u16, rows: u16 },
+}
+
+/// Applies a resize message, returns false for any other text
+fn apply_resize(text: &str, resize: &ResizeHandle) -> bool {
+    let text = text.trim();
+    if !text.starts_with('{') {
+        return false;
+    }
+    match serde_json::from_str(text) {
+        Ok(ClientMessage::Resize { cols, rows }) => {
+            resize.resize(cols, rows);
+            true
+        }
+        Err(_) => false,
+    }
+}
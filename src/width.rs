@@ -0,0 +1,71 @@
+//! Measuring text in terminal columns, so wide CJK characters and emoji, which take two
+//! columns, and ANSI escape sequences, which take none, don't misalign layouts
+
+use regex::Regex;
+use std::sync::OnceLock;
+#[cfg(any(feature = "session", feature = "tui"))]
+use unicode_width::UnicodeWidthChar;
+use unicode_width::UnicodeWidthStr;
+
+/// Remove ANSI escape sequences (colors, cursor movement) from text
+pub(crate) fn strip_ansi(text: &str) -> String {
+    static ESCAPE: OnceLock<Regex> = OnceLock::new();
+    let r = ESCAPE.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap());
+    r.replace_all(text, "").into_owned()
+}
+
+/// Number of columns `text` takes on the terminal
+pub(crate) fn display_width(text: &str) -> usize {
+    if text.contains('\x1b') {
+        strip_ansi(text).width()
+    } else {
+        text.width()
+    }
+}
+
+/// Number of columns `c` takes on the terminal
+#[cfg(any(feature = "session", feature = "tui"))]
+pub(crate) fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// `text` followed by spaces up to `width` columns
+pub(crate) fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Split into chunks of at most `width` columns, a character wider than `width` gets a
+/// chunk of its own
+#[cfg(feature = "tui")]
+pub(crate) fn chunks(text: &str, width: usize) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    let mut chunk_width = 0;
+    for c in text.chars() {
+        let c_width = char_width(c);
+        if chunk_width + c_width > width && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_width = 0;
+        }
+        chunk.push(c);
+        chunk_width += c_width;
+    }
+    if !chunk.is_empty() || chunks.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// The end of `text` which fits into `width` columns
+#[cfg(feature = "tui")]
+pub(crate) fn tail(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (index, c) in text.char_indices().rev() {
+        used += char_width(c);
+        if used > width {
+            return &text[index + c.len_utf8()..];
+        }
+    }
+    text
+}
@@ -0,0 +1,68 @@
+//! Named sessions, each with its own Context, sharing one terminal, see
+//! [Repl::with_sessions](crate::Repl::with_sessions)
+
+use crate::error::{Error, Result};
+use crate::ContextFactory;
+use std::collections::BTreeMap;
+
+/// Name of the session the Repl starts in
+pub(crate) const FIRST_SESSION: &str = "main";
+
+/// The sessions in the background, the Context of the current one is the Repl's
+pub(crate) struct Workspace<Context> {
+    factory: ContextFactory<Context>,
+    current: String,
+    inactive: BTreeMap<String, Context>,
+}
+
+impl<Context> Workspace<Context> {
+    pub(crate) fn new(factory: ContextFactory<Context>) -> Self {
+        Self {
+            factory,
+            current: FIRST_SESSION.to_string(),
+            inactive: BTreeMap::new(),
+        }
+    }
+
+    /// Start session `name` with a new Context, moving `context` to the background
+    pub(crate) fn create(&mut self, name: &str, context: &mut Context) -> Result<()> {
+        if name == self.current || self.inactive.contains_key(name) {
+            return Err(Error::DuplicateSession(name.to_string()));
+        }
+        self.inactive.insert(name.to_string(), (self.factory)());
+        self.switch(name, context)
+    }
+
+    /// Continue session `name`, exchanging its Context with `context`
+    pub(crate) fn switch(&mut self, name: &str, context: &mut Context) -> Result<()> {
+        if name == self.current {
+            return Ok(());
+        }
+        let mut other = self
+            .inactive
+            .remove(name)
+            .ok_or_else(|| Error::UnknownSession(name.to_string()))?;
+        std::mem::swap(context, &mut other);
+        let previous = std::mem::replace(&mut self.current, name.to_string());
+        self.inactive.insert(previous, other);
+        Ok(())
+    }
+
+    pub(crate) fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Names of all sessions in alphabetical order, the current one marked with `*`
+    pub(crate) fn list(&self) -> Vec<String> {
+        let mut names: Vec<&str> = self.inactive.keys().map(String::as_str).collect();
+        names.push(&self.current);
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| match name == self.current {
+                true => format!("* {}", name),
+                false => format!("  {}", name),
+            })
+            .collect()
+    }
+}
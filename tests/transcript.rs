@@ -0,0 +1,52 @@
+use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use reedline_repl_rs::testing::{assert_transcript, run_transcript};
+use reedline_repl_rs::{Error, Repl, Result};
+
+fn hello(args: ArgMatches, _context: &mut u32) -> Result<Option<String>> {
+    Ok(Some(format!(
+        "Hello, {}",
+        args.get_one::<String>("who").unwrap()
+    )))
+}
+
+fn count(_args: ArgMatches, context: &mut u32) -> Result<Option<String>> {
+    *context += 1;
+    Ok(Some(context.to_string()))
+}
+
+fn repl() -> Repl<u32, Error> {
+    Repl::new(0)
+        .with_name("MyApp")
+        .with_command(
+            Command::new("hello").arg(Arg::new("who").required(true)),
+            hello,
+        )
+        .with_command(Command::new("count"), count)
+}
+
+#[test]
+fn transcript_matches_session() {
+    assert_transcript(
+        &mut repl(),
+        "MyApp〉hello Friend
+Hello, Friend
+MyApp〉count
+1
+MyApp〉count
+2
+MyApp〉bye
+Error: Unknown command 'bye'",
+    );
+}
+
+#[test]
+fn run_transcript_returns_actual_session() {
+    let actual = run_transcript(&mut repl(), "MyApp〉count\n5\nMyApp〉count").unwrap();
+    assert_eq!(actual, "MyApp〉count\n1\nMyApp〉count\n2\n");
+}
+
+#[test]
+#[should_panic(expected = "transcript differs at line 2")]
+fn differing_output_panics() {
+    assert_transcript(&mut repl(), "MyApp〉hello Friend\nHello, Foe");
+}